@@ -0,0 +1,142 @@
+//! LZSS compression as used for compressed PBO entries
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+const WINDOW_SIZE: usize = 4095;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 18;
+/// Candidate positions checked per byte; keeps compression of large files fast
+const MAX_CANDIDATES: usize = 64;
+
+/// Compresses data, appending the checksum of the uncompressed data.
+///
+/// Data is written in blocks of up to eight items, each preceded by a flag byte. A set bit marks
+/// a literal byte, a cleared bit a two byte back-reference of 3 to 18 bytes up to 4095 bytes back.
+///
+/// # Examples
+///
+/// ```
+/// # use armake2::lzss;
+/// let data = b"abcabcabcabcabcabc".to_vec();
+/// let compressed = lzss::compress(&data);
+///
+/// assert!(compressed.len() < data.len());
+/// assert_eq!(lzss::decompress(&compressed, data.len()).unwrap(), data);
+/// ```
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output: Vec<u8> = Vec::with_capacity(input.len());
+    let mut candidates: HashMap<[u8; 3], Vec<usize>> = HashMap::new();
+
+    let mut flag_index = 0;
+    let mut flag_bit = 8;
+    let mut pos = 0;
+
+    while pos < input.len() {
+        if flag_bit == 8 {
+            flag_index = output.len();
+            output.push(0);
+            flag_bit = 0;
+        }
+
+        let (distance, length) = find_match(input, pos, &candidates);
+
+        if length >= MIN_MATCH {
+            output.push((distance & 0xFF) as u8);
+            output.push((((distance >> 4) & 0xF0) | (length - MIN_MATCH)) as u8);
+        } else {
+            output[flag_index] |= 1 << flag_bit;
+            output.push(input[pos]);
+        }
+
+        let step = length.max(1);
+        for i in pos..pos + step {
+            if i + MIN_MATCH <= input.len() {
+                let key = [input[i], input[i + 1], input[i + 2]];
+                candidates.entry(key).or_insert_with(Vec::new).push(i);
+            }
+        }
+
+        flag_bit += 1;
+        pos += step;
+    }
+
+    output.extend_from_slice(&checksum(input).to_le_bytes());
+
+    output
+}
+
+fn find_match(input: &[u8], pos: usize, candidates: &HashMap<[u8; 3], Vec<usize>>) -> (usize, usize) {
+    if pos + MIN_MATCH > input.len() {
+        return (0, 0);
+    }
+
+    let key = [input[pos], input[pos + 1], input[pos + 2]];
+    let max_length = MAX_MATCH.min(input.len() - pos);
+    let mut best = (0, 0);
+
+    if let Some(positions) = candidates.get(&key) {
+        for &start in positions.iter().rev().take(MAX_CANDIDATES) {
+            let distance = pos - start;
+            if distance > WINDOW_SIZE { break; }
+
+            // Matches may overlap the current position, which repeats the matched bytes
+            let length = (0..max_length).take_while(|&i| input[start + i] == input[pos + i]).count();
+
+            if length > best.1 {
+                best = (distance, length);
+                if length == max_length { break; }
+            }
+        }
+    }
+
+    best
+}
+
+/// Decompresses data created by `compress`, verifying the trailing checksum.
+pub fn decompress(input: &[u8], expected_length: usize) -> Result<Vec<u8>, Error> {
+    let mut output: Vec<u8> = Vec::with_capacity(expected_length);
+    let mut pos = 0;
+
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+
+    while output.len() < expected_length {
+        let flags = *input.get(pos).ok_or_else(|| invalid("Unexpected end of LZSS data"))?;
+        pos += 1;
+
+        for bit in 0..8 {
+            if output.len() >= expected_length { break; }
+
+            if flags & (1 << bit) != 0 {
+                output.push(*input.get(pos).ok_or_else(|| invalid("Unexpected end of LZSS data"))?);
+                pos += 1;
+            } else {
+                let b1 = *input.get(pos).ok_or_else(|| invalid("Unexpected end of LZSS data"))? as usize;
+                let b2 = *input.get(pos + 1).ok_or_else(|| invalid("Unexpected end of LZSS data"))? as usize;
+                pos += 2;
+
+                let distance = b1 | ((b2 & 0xF0) << 4);
+                let length = (b2 & 0x0F) + MIN_MATCH;
+
+                for _ in 0..length {
+                    // References before the start of the data are filled with spaces
+                    let byte = if distance > output.len() { 0x20 } else { output[output.len() - distance] };
+                    output.push(byte);
+                }
+            }
+        }
+    }
+
+    output.truncate(expected_length);
+
+    let stored = input.get(pos..pos + 4).ok_or_else(|| invalid("Missing LZSS checksum"))?;
+    if u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]) != checksum(&output) {
+        return Err(invalid("LZSS checksum mismatch"));
+    }
+
+    Ok(output)
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |sum, &byte| sum.wrapping_add(u32::from(byte)))
+}
@@ -1,8 +1,9 @@
+use std::borrow::Cow;
 use std::collections::{HashMap};
 use std::ffi::{OsStr};
 use std::fs::{File, create_dir_all, read_dir};
 use std::io::{Read, Write, Seek, SeekFrom, Error, Cursor};
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use linked_hash_map::{LinkedHashMap};
@@ -14,6 +15,7 @@ use crate::io::*;
 use crate::config::*;
 use crate::preprocess::*;
 use crate::binarize;
+use crate::lzss;
 
 struct PBOHeader {
     filename: String,
@@ -147,7 +149,18 @@ impl PBO {
     /// `exclude_patterns` contains glob patterns to exclude from the PBO, `includefolders` contain
     /// paths to search for absolute includes and should generally include the current working
     /// directory.
-    pub fn from_directory(directory: PathBuf, mut binarize: bool, exclude_patterns: &[String], includefolders: &[PathBuf]) -> Result<PBO, Error> {
+    pub fn from_directory(directory: PathBuf, binarize: bool, exclude_patterns: &[String], includefolders: &[PathBuf]) -> Result<PBO, Error> {
+        PBO::from_directory_filtered(directory, binarize, |relative| {
+            file_allowed(&relative.to_str().unwrap().replace("/", "\\"), &exclude_patterns)
+        }, includefolders)
+    }
+
+    /// Constructs a PBO from a directory with optional binarization, only including files for
+    /// which `filter` returns true.
+    ///
+    /// `filter` is passed the path each file is stored under, relative to `directory`, so a
+    /// binarized `config.cpp` is matched as `config.bin`.
+    pub fn from_directory_filtered<F: Fn(&Path) -> bool>(directory: PathBuf, mut binarize: bool, filter: F, includefolders: &[PathBuf]) -> Result<PBO, Error> {
         let file_list = list_files(&directory)?;
         let mut files: LinkedHashMap<String, Cursor<Box<[u8]>>> = LinkedHashMap::new();
         let mut header_extensions: HashMap<String,String> = HashMap::new();
@@ -162,11 +175,11 @@ impl PBO {
                 relative = relative.with_file_name("config.bin");
             }
 
+            if !filter(&relative) { continue; }
+
             let mut name: String = relative.to_str().unwrap().replace("/", "\\");
             let is_binarizable = Regex::new(".(rtm|p3d)$").unwrap().is_match(&name);
 
-            if !file_allowed(&name, &exclude_patterns) { continue; }
-
             let mut file = File::open(&path)?;
 
             if name == "$PBOPREFIX$" {
@@ -220,6 +233,15 @@ impl PBO {
 
     /// Writes PBO to output.
     pub fn write<O: Write>(&self, output: &mut O) -> Result<(), Error> {
+        self.write_with(output, false)
+    }
+
+    /// Writes PBO to output, LZSS compressing every file that gets smaller by it.
+    pub fn write_compressed<O: Write>(&self, output: &mut O) -> Result<(), Error> {
+        self.write_with(output, true)
+    }
+
+    fn write_with<O: Write>(&self, output: &mut O, compress: bool) -> Result<(), Error> {
         let mut headers: Cursor<Vec<u8>> = Cursor::new(Vec::new());
 
         let ext_header = PBOHeader {
@@ -245,17 +267,28 @@ impl PBO {
         }
         headers.write_cstring("".to_string())?;
 
-        let mut files_sorted: Vec<(String,&Cursor<Box<[u8]>>)> = self.files.iter().map(|(a,b)| (a.clone(),b)).collect();
+        let mut files_sorted: Vec<(String,Cow<[u8]>)> = self.files.iter().map(|(a,b)| (a.clone(),Cow::Borrowed(&b.get_ref()[..]))).collect();
         files_sorted.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
 
-        for (name, cursor) in &files_sorted {
+        for (name, data) in files_sorted.iter_mut() {
+            let original_size = data.len() as u32;
+            let mut packing_method = 0;
+
+            if compress && !data.is_empty() {
+                let compressed = lzss::compress(data);
+                if compressed.len() < data.len() {
+                    *data = Cow::Owned(compressed);
+                    packing_method = 0x4370_7273;
+                }
+            }
+
             let header = PBOHeader {
                 filename: name.clone(),
-                packing_method: 0,
-                original_size: cursor.get_ref().len() as u32,
+                packing_method,
+                original_size,
                 reserved: 0,
                 timestamp: 0,
-                data_size: cursor.get_ref().len() as u32,
+                data_size: data.len() as u32,
             };
 
             header.write(&mut headers)?;
@@ -272,9 +305,9 @@ impl PBO {
         output.write_all(headers.get_ref())?;
         h.update(headers.get_ref()).unwrap();
 
-        for (_, cursor) in &files_sorted {
-            output.write_all(cursor.get_ref())?;
-            h.update(cursor.get_ref()).unwrap();
+        for (_, data) in &files_sorted {
+            output.write_all(data)?;
+            h.update(data).unwrap();
         }
 
         output.write_all(&[0])?;
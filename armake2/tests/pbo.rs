@@ -0,0 +1,27 @@
+use std::fs::{File};
+use std::io::{Write};
+
+use tempfile::{tempdir};
+
+use armake2::pbo::*;
+
+#[test]
+fn test_from_directory_excludes_binarized_names() {
+    let dir = tempdir().unwrap();
+    let addon = dir.path().join("addon");
+    std::fs::create_dir(&addon).unwrap();
+
+    File::create(addon.join("config.cpp")).unwrap().write_all(b"class CfgPatches {};").unwrap();
+    File::create(addon.join("readme.txt")).unwrap().write_all(b"readme").unwrap();
+
+    // Excludes are matched after config.cpp is renamed to config.bin
+    let pbo = PBO::from_directory(addon.clone(), true, &["config.cpp".to_string()], &Vec::new()).unwrap();
+    assert!(pbo.files.contains_key("config.bin"));
+    assert!(pbo.files.contains_key("readme.txt"));
+
+    let pbo = PBO::from_directory(addon.clone(), true, &["config.bin".to_string(), "*.txt".to_string()], &Vec::new()).unwrap();
+    assert!(pbo.files.is_empty());
+
+    let pbo = PBO::from_directory(addon, false, &["config.cpp".to_string()], &Vec::new()).unwrap();
+    assert_eq!(pbo.files.keys().collect::<Vec<_>>(), vec!["readme.txt"]);
+}
@@ -0,0 +1,120 @@
+//! Build cache for incremental packing.
+//!
+//! Packed PBOs are kept in `{build_path}/.laat-cache`, next to a hash of the addon folder and
+//! pack settings they were built from. When an addon hashes the same on the next `laat pack`, the
+//! cached PBO is copied into the release instead of being packed again.
+
+use crate::context::BuildContext;
+use crate::Result;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Name of the cache folder inside the build folder
+pub const CACHE_FOLDER: &str = ".laat-cache";
+
+#[derive(Debug, Clone)]
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(build_context: &BuildContext) -> Self {
+        Self {
+            dir: Path::new(&build_context.build_path).join(CACHE_FOLDER),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Hash every file in `dir` (by relative path and contents), along with any `settings` that
+    /// affect how it is packed.
+    pub fn hash_dir(dir: &Path, settings: &[String]) -> Result<String> {
+        let mut hasher = Sha256::new();
+
+        for setting in settings {
+            hasher.update(setting.as_bytes());
+            hasher.update([0]);
+        }
+
+        let walkdir = walkdir::WalkDir::new(dir).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+
+        for entry in walkdir {
+            let entry = entry?;
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative = entry.path().strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+            let contents = std::fs::read(entry.path())?;
+
+            hasher.update(relative.as_bytes());
+            hasher.update([0]);
+            hasher.update((contents.len() as u64).to_le_bytes());
+            hasher.update(&contents);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// The cached copy of `pbo_name`, if it was built from inputs with the same `hash`
+    pub fn cached_pbo(&self, pbo_name: &str, hash: &str) -> Option<PathBuf> {
+        let cached_hash = std::fs::read_to_string(self.hash_path(pbo_name)).ok()?;
+        let pbo_path = self.pbo_path(pbo_name);
+
+        if cached_hash.trim() == hash && pbo_path.is_file() {
+            Some(pbo_path)
+        } else {
+            None
+        }
+    }
+
+    /// Keep a copy of a freshly packed PBO for the next run
+    pub fn store_pbo(&self, pbo_name: &str, hash: &str, pbo: &Path) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::copy(pbo, self.pbo_path(pbo_name))?;
+        std::fs::write(self.hash_path(pbo_name), hash)?;
+
+        Ok(())
+    }
+
+    fn pbo_path(&self, pbo_name: &str) -> PathBuf {
+        self.dir.join(pbo_name)
+    }
+
+    fn hash_path(&self, pbo_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.hash", pbo_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("data"))?;
+        std::fs::write(dir.path().join("config.cpp"), "class CfgPatches {};")?;
+        std::fs::write(dir.path().join("data/readme.txt"), "a")?;
+
+        let settings = vec!["prefix=LAAT\\Test".to_string()];
+        let hash = |settings: &[String]| BuildCache::hash_dir(dir.path(), settings);
+
+        let first = hash(&settings)?;
+        let same = hash(&settings)?;
+        let other_settings = hash(&[])?;
+
+        std::fs::write(dir.path().join("data/readme.txt"), "b")?;
+        let changed = hash(&settings)?;
+
+
+        assert_eq!(first, same);
+        assert_ne!(first, other_settings);
+        assert_ne!(first, changed);
+
+        Ok(())
+    }
+}
@@ -1,17 +1,189 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use crate::Result;
+use crate::progress::{Progress, ProgressSink};
+use crate::report::ReportCollector;
+use crate::{LaatError, Result};
 use tokio::io::AsyncReadExt;
 
+/// Config path that reads the config from stdin instead, i.e. `generate-config | laat build -f -`
+pub const STDIN_CONFIG: &str = "-";
+
+/// Load a config file, layering `<name>.local.<ext>` (i.e. `LAAT.local.toml`) on top of it if
+/// that file exists. A config read from stdin (`-`) isn't layered.
 pub async fn get_config_from_path(path: PathBuf) -> Result<LaatConfig> {
+    let local_path = local_config_path(&path);
+    let local_path = if path != Path::new(STDIN_CONFIG) && local_path.is_file() {
+        Some(local_path)
+    } else {
+        None
+    };
+
+    get_layered_config(path, local_path).await
+}
+
+/// Load a base config and deep-merge an optional override file on top of it.
+///
+/// Tables are merged key by key and any other value in the override replaces the base value.
+/// Arrays replace as well, unless the key is prefixed with `+` (i.e. `"+plugins" = ["music"]`),
+/// in which case the override's elements are appended to the base array.
+pub async fn get_layered_config(
+    path: PathBuf,
+    override_path: Option<PathBuf>,
+) -> Result<LaatConfig> {
+    let mut value = read_config_value(&path).await?;
+
+    if let Some(override_path) = override_path {
+        debug!("Merging config overrides from {}", override_path.display());
+        merge_values(&mut value, read_config_value(&override_path).await?);
+    }
+
+    interpolate_env(&mut value)?;
+    let config: LaatConfig = value.try_into()?;
+    config.validate()?;
+
+    debug!("Extra: {:?}", config.extra);
+
+    Ok(config)
+}
+
+/// Read a TOML or JSON (`.json`) config file, or the config on stdin for `-`
+async fn read_config_value(path: &Path) -> Result<toml::Value> {
+    if path == Path::new(STDIN_CONFIG) {
+        let mut contents = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut contents)
+            .await
+            .map_err(|why| format!("Failed to read the config from stdin: {}", why))?;
+
+        return parse_stdin_config(&contents);
+    }
+
+    if !path.is_file() {
+        return Err(format!("Config file {} doesn't exist", path.display()).into());
+    }
+
     let mut file = tokio::fs::File::open(path).await?;
     let mut contents = String::new();
     file.read_to_string(&mut contents).await?;
-    let config: LaatConfig = toml::from_str(&contents)?;
 
-    debug!("Extra: {:?}", config.extra);
+    parse_config_value(&contents, path)
+}
 
-    Ok(config)
+fn parse_config_value(contents: &str, path: &Path) -> Result<toml::Value> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => {
+            let value: serde_json::Value = serde_json::from_str(contents)?;
+
+            // Plugin settings are stored as TOML values, so convert the whole document.
+            toml::Value::try_from(value).map_err(|why| {
+                format!("{} can not be represented as a LAAT config: {}", path.display(), why).into()
+            })
+        }
+        _ => Ok(toml::from_str(contents)?),
+    }
+}
+
+/// Stdin has no extension to go by, but a TOML document can't start with `{` like a JSON one does
+fn parse_stdin_config(contents: &str) -> Result<toml::Value> {
+    let format = if contents.trim_start().starts_with('{') {
+        "stdin.json"
+    } else {
+        "stdin.toml"
+    };
+
+    parse_config_value(contents, Path::new(format))
+}
+
+/// `LAAT.toml` -> `LAAT.local.toml`
+fn local_config_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}.local.{}", stem, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}.local", stem)),
+    }
+}
+
+fn merge_values(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                if let Some(key) = key.strip_prefix('+') {
+                    match value {
+                        toml::Value::Array(values) if base.get(key).is_some_and(toml::Value::is_array) => {
+                            if let Some(toml::Value::Array(existing)) = base.get_mut(key) {
+                                existing.extend(values);
+                            }
+                        }
+                        value => {
+                            base.insert(key.to_string(), value);
+                        }
+                    }
+                } else if let Some(existing) = base.get_mut(&key) {
+                    merge_values(existing, value);
+                } else {
+                    base.insert(key, value);
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Replace `${VAR}` in every string value with the value of the environment variable `VAR`.
+/// `$${` is written as a literal `${`.
+fn interpolate_env(value: &mut toml::Value) -> Result<()> {
+    match value {
+        toml::Value::String(string) => *string = interpolate_str(string)?,
+        toml::Value::Array(array) => {
+            for value in array.iter_mut() {
+                interpolate_env(value)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_key, value) in table.iter_mut() {
+                interpolate_env(value)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn interpolate_str(input: &str) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+
+        let variable = &rest[start + 2..];
+        let end = variable
+            .find('}')
+            .ok_or_else(|| format!("Unclosed ${{ in config value {:?}", input))?;
+        let name = &variable[..end];
+
+        let value = std::env::var(name).map_err(|_| LaatError::MissingEnvVar {
+            name: name.to_string(),
+            value: input.to_string(),
+        })?;
+
+        output.push_str(&value);
+        rest = &variable[end + 1..];
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -19,6 +191,18 @@ pub struct LaatConfig {
     pub prefix: String,
     pub name: String,
 
+    /// Mod author, or a list of authors, used in mission credits and mod.cpp
+    #[serde(default)]
+    pub author: Option<Authors>,
+
+    /// Logo shown in the launcher, copied into the release by `laat pack`
+    #[serde(default)]
+    pub logo: Option<String>,
+
+    /// Mod version, i.e. "1.2.0". Added to mod.cpp, generated addons and PBO headers.
+    #[serde(default)]
+    pub version: Option<String>,
+
     #[serde(default = "default_build_path")]
     pub build_path: String,
     #[serde(default = "default_assets_path")]
@@ -29,6 +213,8 @@ pub struct LaatConfig {
     pub release_path: String,
     #[serde(default = "default_keys_path")]
     pub keys_path: String,
+    #[serde(default = "default_templates_path")]
+    pub templates_path: String,
 
     #[serde(default)]
     pub plugins: Vec<String>,
@@ -39,20 +225,325 @@ pub struct LaatConfig {
     #[serde(default)]
     pub pack: PackConfig,
 
+    /// Signing settings. When present, PBOs are signed after packing.
+    #[serde(default)]
+    pub sign: Option<SignConfig>,
+
+    /// Let plugins overwrite files that were already added to an addon
+    #[serde(default)]
+    pub allow_overwrite: bool,
+
+    /// Build each plugin's addons in its own folder, `build_path/<plugin>`, so plugins running at
+    /// the same time don't share intermediate files. `laat pack` packs the addons of every folder.
+    #[serde(default)]
+    pub isolate_plugin_builds: bool,
+
+    /// Strip comments, trailing whitespace and blank lines from the generated config.cpp files.
+    /// Off by default so the build folder stays readable.
+    #[serde(default)]
+    pub minify_configs: bool,
+
+    /// Skip plugins in `plugins` that LAAT doesn't know with a warning, instead of failing
+    #[serde(default)]
+    pub allow_unknown_plugins: bool,
+
+    /// How many plugins, PBOs and other tasks (i.e. missions or file copies) may run at the same
+    /// time. Also set with `--jobs`.
+    #[serde(default = "default_max_parallel_builds")]
+    pub max_parallel_builds: usize,
+
+    /// How many times a failed file read or write is retried before the build fails, i.e. when
+    /// antivirus briefly locks a file
+    #[serde(default = "default_io_retries")]
+    pub io_retries: usize,
+
+    /// Keep the unpacked addons in the build folder after `laat ship` packs them, to inspect the
+    /// generated files. Also set with `--keep-build`.
+    #[serde(default)]
+    pub keep_build: bool,
+
+    /// Rebuild every PBO instead of reusing cached ones. Set with `--force`.
+    #[serde(skip)]
+    pub force: bool,
+
+    /// Log what would be written instead of touching the build folder. Set with `--dry-run`.
+    #[serde(skip)]
+    pub dry_run: bool,
+
+    /// Compare the generated files with the last build instead of writing them. Set with
+    /// `--diff`.
+    #[serde(skip)]
+    pub diff: bool,
+
+    /// Name of the plugin this context was handed to, if any
+    #[serde(skip)]
+    pub plugin: Option<String>,
+
+    /// Where plugins record the addons and missions they build, for the `BuildReport`
+    #[serde(skip)]
+    pub report: ReportCollector,
+
+    /// Where plugins report their progress, nowhere unless set with
+    /// `LaatCompiler::set_progress_sink`
+    #[serde(skip)]
+    pub progress: ProgressSink,
+
     #[serde(flatten)]
     pub extra: toml::Value
 }
 
 impl LaatConfig {
+    /// A config with every setting but `prefix` and `name` at its default, as if loaded from a
+    /// LAAT.toml containing only those two.
+    pub fn new(prefix: impl Into<String>, name: impl Into<String>) -> Result<Self> {
+        let mut table = toml::value::Table::new();
+        table.insert("prefix".to_string(), toml::Value::String(prefix.into()));
+        table.insert("name".to_string(), toml::Value::String(name.into()));
+
+        let config: LaatConfig = toml::Value::Table(table).try_into()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Set the `[key]` section of a plugin's settings, replacing any existing one
+    pub fn set_plugin_settings<T: Serialize>(&mut self, key: &str, settings: T) -> Result<()> {
+        let settings = toml::Value::try_from(settings)?;
+
+        match &mut self.extra {
+            toml::Value::Table(table) => {
+                table.insert(key.to_string(), settings);
+            }
+            extra => {
+                let mut table = toml::value::Table::new();
+                table.insert(key.to_string(), settings);
+                *extra = toml::Value::Table(table);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that `prefix` is usable in class names and PBO prefixes, and that `name` is safe to
+    /// put in a quoted config string.
+    pub fn validate(&self) -> Result<()> {
+        if self.prefix.is_empty() {
+            return Err(invalid("prefix", "prefix in LAAT.toml must not be empty".to_string()));
+        }
+
+        if let Some((index, c)) = self
+            .prefix
+            .char_indices()
+            .find(|(_, c)| !(c.is_ascii_alphanumeric() || *c == '_'))
+        {
+            return Err(invalid("prefix", format!(
+                "Invalid character {:?} at position {} in prefix {:?}: the prefix may only contain letters, digits and underscores",
+                c, index, self.prefix
+            )));
+        }
+
+        if self.name.trim().is_empty() {
+            return Err(invalid("name", "name in LAAT.toml must not be empty".to_string()));
+        }
+
+        check_quotable("name", &self.name)?;
+
+        self.pack.validate()?;
+
+        if let Some(folder) = &self.release.mod_folder {
+            if folder.trim_start_matches('@').is_empty() || folder.contains(['/', '\\']) || folder.contains("..") {
+                return Err(invalid("release.mod_folder", format!(
+                    "Invalid mod_folder {:?} in [release]: expected a single folder name, i.e. \"@{}\"",
+                    folder, self.prefix
+                )));
+            }
+        }
+
+        for author in self.author.iter().flat_map(Authors::names) {
+            check_quotable("author", author)?;
+        }
+
+        if let Some(version) = &self.version {
+            check_quotable("version", version)?;
+        }
+
+        // Written as-is into mod.cpp
+        for key in ["overview", "picture", "logoSmall"] {
+            if let Some(value) = self.extra.get(key).and_then(toml::Value::as_str) {
+                check_quotable(key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How many tasks of one kind (plugins, PBOs, missions, file copies, ...) may run at once.
+    /// Plugins should size their concurrency by it.
+    pub fn jobs(&self) -> usize {
+        self.max_parallel_builds.max(1)
+    }
+
+    /// `prefix` namespaced for a plugin, i.e. `17th_missions`, for class names that mustn't clash
+    /// with the ones other plugins generate
+    pub fn namespaced_prefix(&self, namespace: &str) -> String {
+        format!("{}_{}", self.prefix, namespace)
+    }
+
+    /// Deserialize the `[key]` section of LAAT.toml into a plugin's settings
+    pub fn plugin_settings<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        let settings = self
+            .extra
+            .get(key)
+            .ok_or_else(|| LaatError::MissingConfigKey {
+                key: key.to_string(),
+                found: self.plugin_sections(),
+            })?;
+
+        settings.clone().try_into().map_err(|why: toml::de::Error| {
+            invalid(key, format!("Invalid settings for plugin {} in [{}]: {}", key, key, field_error(key, &why)))
+        })
+    }
+
+    /// Names of the `[section]` tables in LAAT.toml that aren't LAAT's own settings, sorted
+    fn plugin_sections(&self) -> Vec<String> {
+        let mut sections = self
+            .extra
+            .as_table()
+            .into_iter()
+            .flatten()
+            .filter(|(_, value)| value.is_table())
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        sections.sort();
+
+        sections
+    }
+
+    /// Like `plugin_settings`, but falls back to the default settings if the section is missing
+    pub fn plugin_settings_or_default<T: DeserializeOwned + Default>(&self, key: &str) -> Result<T> {
+        if self.extra.get(key).is_some() {
+            self.plugin_settings(key)
+        } else {
+            Ok(T::default())
+        }
+    }
+
     pub fn released_addon_path(&self) -> String {
         let release_path = format!(
-            "{}/@{}",
+            "{}/{}",
             self.release_path,
-            self.prefix
+            self.mod_folder()
         );
 
         release_path
     }
+
+    /// Report that `done` of `total` steps of the current plugin's build are finished
+    pub fn report_progress(&self, done: usize, total: usize, message: impl Into<String>) {
+        self.progress.report(Progress {
+            plugin: self.plugin.clone().unwrap_or_default(),
+            done,
+            total,
+            message: message.into(),
+        });
+    }
+
+    /// Folder the addons of `plugin` are built in: `build_path/<plugin>` with
+    /// `isolate_plugin_builds`, otherwise `build_path`
+    pub fn plugin_build_path(&self, plugin: &str) -> PathBuf {
+        if self.isolate_plugin_builds {
+            Path::new(&self.build_path).join(plugin)
+        } else {
+            PathBuf::from(&self.build_path)
+        }
+    }
+
+    /// `plugin_build_path` of the plugin this context was handed to, or `build_path`
+    pub fn addon_build_path(&self) -> PathBuf {
+        match &self.plugin {
+            Some(plugin) => self.plugin_build_path(plugin),
+            None => PathBuf::from(&self.build_path),
+        }
+    }
+
+    /// A path from LAAT.toml (i.e. a plugin setting) as an absolute path in the project folder,
+    /// the working directory LAAT runs in (see `--project-dir`). Absolute paths are returned as
+    /// they are.
+    pub fn project_path(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        Ok(std::env::current_dir()?.join(path))
+    }
+
+    /// The `@Mod` folder name in `release_path`
+    pub fn mod_folder(&self) -> String {
+        match &self.release.mod_folder {
+            Some(folder) if folder.starts_with('@') => folder.clone(),
+            Some(folder) => format!("@{}", folder),
+            None => format!("@{}", self.prefix),
+        }
+    }
+}
+
+/// Describe a deserialization error with the full path of the field, i.e. `missions.repeat.cols`
+/// instead of `repeat.cols`
+fn field_error(key: &str, why: &toml::de::Error) -> String {
+    let message = why.to_string();
+
+    let (reason, path) = match message.rsplit_once(" for key `") {
+        Some((reason, field)) => (reason, Some(format!("{}.{}", key, field.trim_end_matches('`')))),
+        None => (message.as_str(), None),
+    };
+
+    match (reason.strip_prefix("missing field `"), path) {
+        (Some(field), path) => {
+            format!("missing field `{}.{}`", path.as_deref().unwrap_or(key), field.trim_end_matches('`'))
+        }
+        (None, Some(path)) => format!("{} (field `{}`)", reason, path),
+        (None, None) => reason.to_string(),
+    }
+}
+
+fn invalid(key: &str, reason: String) -> LaatError {
+    LaatError::InvalidConfig {
+        key: key.to_string(),
+        reason,
+    }
+}
+
+/// Check that `value` is safe to put in a quoted config string
+fn check_quotable(field: &str, value: &str) -> Result<()> {
+    if let Some((index, c)) = value
+        .char_indices()
+        .find(|(_, c)| *c == '"' || *c == '\\' || c.is_control())
+    {
+        return Err(invalid(field, format!(
+            "Invalid character {:?} at position {} in {} {:?}: the {} may not contain quotes, backslashes or control characters",
+            c, index, field, value, field
+        )));
+    }
+
+    Ok(())
+}
+
+/// A single author, or a list of them
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Authors {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Authors {
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Authors::One(name) => vec![name.as_str()],
+            Authors::Many(names) => names.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Every author, comma separated
+    pub fn credit(&self) -> String {
+        self.names().join(", ")
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
@@ -62,7 +553,74 @@ pub struct PackConfig {
     #[serde(default)]
     pub excludes: Vec<String>,
     #[serde(default)]
-    pub header_extensions: Vec<String>
+    pub header_extensions: Vec<String>,
+    /// PBO compression, "none" (the default) or "lzss"
+    #[serde(default)]
+    pub compression: Option<String>,
+}
+
+impl PackConfig {
+    /// Check that every header extension is a `key=value` pair
+    pub fn validate(&self) -> Result<()> {
+        for extension in &self.header_extensions {
+            match extension.split_once('=') {
+                Some((key, _)) if !key.trim().is_empty() => {}
+                _ => {
+                    return Err(invalid("pack.header_extensions", format!(
+                        "Invalid header extension {:?} in [pack]: expected a key=value pair, i.e. \"version=1.0\"",
+                        extension
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expand glob patterns (i.e. `addons/*`) in `include_folders` relative to the project root.
+    /// Literal paths are passed through unchanged.
+    pub fn expanded_include_folders(&self) -> Result<Vec<PathBuf>> {
+        let mut folders = Vec::new();
+
+        for folder in &self.include_folders {
+            let pattern = folder.to_string_lossy();
+
+            if !pattern.contains(['*', '?', '[']) {
+                folders.push(folder.clone());
+                continue;
+            }
+
+            let matches = glob::glob(&pattern)?
+                .filter_map(|entry| match entry {
+                    Ok(path) => Some(path),
+                    Err(why) => {
+                        warn!("Error reading include folder: {}", why);
+                        None
+                    }
+                })
+                .filter(|path| path.is_dir())
+                .collect::<Vec<_>>();
+
+            if matches.is_empty() {
+                warn!("Include folder pattern {:?} did not match any folders", pattern);
+            }
+
+            folders.extend(matches);
+        }
+
+        Ok(folders)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SignConfig {
+    /// Path to the .biprivatekey used to sign PBOs
+    pub private_key: PathBuf,
+    /// Name of the key, used for the generated .bikey
+    pub key_name: String,
+    /// Write the matching .bikey into the release
+    #[serde(default = "default_emit_bikey")]
+    pub emit_bikey: bool,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
@@ -71,28 +629,322 @@ pub struct ReleaseConfig {
     pub app_id: usize,
     #[serde(default)]
     pub workshop_id: usize,
+    /// Name of the mod folder in `release_path`, defaults to `@<prefix>`
+    #[serde(default)]
+    pub mod_folder: Option<String>,
+}
+
+fn default_emit_bikey() -> bool {
+    true
 }
 
 fn arma_app_id() -> usize {
     107410
 }
 
-fn default_build_path() -> String {
+pub(crate) fn default_build_path() -> String {
     "build".to_string()
 }
 
-fn default_assets_path() -> String {
+pub(crate) fn default_assets_path() -> String {
     "assets".to_string()
 }
 
-fn default_addons_path() -> String {
+pub(crate) fn default_addons_path() -> String {
     "addons".to_string()
 }
 
-fn default_release_path() -> String {
+pub(crate) fn default_release_path() -> String {
     "release".to_string()
 }
 
-fn default_keys_path() -> String {
+pub(crate) fn default_keys_path() -> String {
     "keys".to_string()
 }
+
+fn default_templates_path() -> String {
+    "templates".to_string()
+}
+
+fn default_io_retries() -> usize {
+    3
+}
+
+fn default_max_parallel_builds() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_env() -> Result<()> {
+        std::env::set_var("LAAT_TEST_BUILD_NUMBER", "42");
+
+        let mut value: toml::Value = toml::from_str(
+            r#"
+            release_path = "release/${LAAT_TEST_BUILD_NUMBER}"
+
+            [missions]
+            maps = ["Altis", "${LAAT_TEST_BUILD_NUMBER}-${LAAT_TEST_BUILD_NUMBER}"]
+            "#,
+        )?;
+        interpolate_env(&mut value)?;
+
+        assert_eq!(value["release_path"].as_str(), Some("release/42"));
+        assert_eq!(value["missions"]["maps"][1].as_str(), Some("42-42"));
+
+        let escaped = interpolate_str("$${LAAT_TEST_BUILD_NUMBER} is ${LAAT_TEST_BUILD_NUMBER}, $${unclosed")?;
+        assert_eq!(escaped, "${LAAT_TEST_BUILD_NUMBER} is 42, ${unclosed");
+
+        let err = interpolate_str("release/${LAAT_TEST_UNSET_VARIABLE}").unwrap_err();
+        assert!(err.to_string().contains("LAAT_TEST_UNSET_VARIABLE"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_values() -> Result<()> {
+        let mut base: toml::Value = toml::from_str(
+            r#"
+            prefix = "LAAT"
+            plugins = ["music"]
+
+            [missions]
+            maps = ["Altis"]
+            respawn_delay = 5
+            "#,
+        )?;
+
+        let overlay: toml::Value = toml::from_str(
+            r#"
+            "+plugins" = ["missions"]
+
+            [missions]
+            maps = ["Tanoa"]
+            strict = true
+            "#,
+        )?;
+
+        merge_values(&mut base, overlay);
+
+        let expected: toml::Value = toml::from_str(
+            r#"
+            prefix = "LAAT"
+            plugins = ["music", "missions"]
+
+            [missions]
+            maps = ["Tanoa"]
+            respawn_delay = 5
+            strict = true
+            "#,
+        )?;
+
+        assert_eq!(base, expected);
+        assert_eq!(local_config_path(Path::new("dev/LAAT.toml")), Path::new("dev/LAAT.local.toml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plugin_settings() -> Result<()> {
+        #[derive(Deserialize)]
+        struct Settings {
+            file: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Named {
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let config: LaatConfig = toml::from_str(
+            r#"
+            prefix = "LAAT"
+            name = "LAAT Test Mod"
+
+            [kits]
+            file = "kits.toml"
+
+            [music]
+            file = 5
+            "#,
+        )?;
+
+        assert_eq!(config.plugin_settings::<Settings>("kits")?.file, "kits.toml");
+        assert_eq!(config.namespaced_prefix("kits"), "LAAT_kits");
+
+        let err = config.plugin_settings::<Settings>("missions").err().unwrap();
+        assert!(err.to_string().starts_with("Missing settings for plugin missions"));
+        assert!(err.to_string().ends_with("Found [kits], [music]"));
+        assert!(matches!(err, LaatError::MissingConfigKey { key, .. } if key == "missions"));
+
+        let err = config.plugin_settings::<Settings>("kit").err().unwrap();
+        assert!(err.to_string().ends_with("did you mean [kits]?"));
+
+        let err = config.plugin_settings::<Settings>("music").err().unwrap();
+        assert!(err.to_string().starts_with("Invalid settings for plugin music"));
+        assert!(err.to_string().contains("`music.file`"), "{}", err);
+
+        let err = config.plugin_settings::<Named>("kits").err().unwrap();
+        assert!(err.to_string().ends_with("missing field `kits.name`"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_error() {
+        #[derive(Debug, Deserialize)]
+        struct Repeat {
+            #[allow(dead_code)]
+            cols: usize,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Settings {
+            #[allow(dead_code)]
+            repeat: Repeat,
+        }
+
+        // field_error picks the field out of toml's messages, so pin their wording here
+        let why = toml::Value::try_into::<Settings>(toml::toml! { [repeat] cols = "two" }).unwrap_err();
+        assert!(why.to_string().ends_with(" for key `repeat.cols`"), "{}", why);
+        assert_eq!(
+            field_error("missions", &why),
+            "invalid type: string \"two\", expected usize (field `missions.repeat.cols`)"
+        );
+
+        let why = toml::Value::try_into::<Settings>(toml::toml! { [repeat] }).unwrap_err();
+        assert!(why.to_string().starts_with("missing field `"), "{}", why);
+        assert_eq!(field_error("missions", &why), "missing field `missions.repeat.cols`");
+    }
+
+    #[test]
+    fn test_project_path() -> Result<()> {
+        let config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        let cwd = std::env::current_dir()?;
+
+        assert_eq!(config.project_path("kits.toml")?, cwd.join("kits.toml"));
+        assert_eq!(config.project_path("mod/kits.toml")?, cwd.join("mod/kits.toml"));
+        assert_eq!(config.project_path(cwd.join("abs.toml"))?, cwd.join("abs.toml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plugin_build_path() -> Result<()> {
+        let mut config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+
+        assert_eq!(config.plugin_build_path("music"), Path::new("build"));
+
+        config.isolate_plugin_builds = true;
+        assert_eq!(config.plugin_build_path("music"), Path::new("build/music"));
+        assert_eq!(config.addon_build_path(), Path::new("build"));
+
+        config.plugin = Some("missions".to_string());
+        assert_eq!(config.addon_build_path(), Path::new("build/missions"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let config = |prefix: &str, name: &str| -> Result<LaatConfig> {
+            let mut config: LaatConfig = toml::from_str("prefix = \"LAAT\"\nname = \"LAAT\"")?;
+            config.prefix = prefix.to_string();
+            config.name = name.to_string();
+            Ok(config)
+        };
+
+        config("17th", "17th Infantry Division")?.validate()?;
+
+        let err = config("17th Div", "17th Infantry Division")?.validate().unwrap_err();
+        assert!(err.to_string().starts_with("Invalid character ' ' at position 4 in prefix"));
+
+        let err = config("17th", "17th \"Infantry\"")?.validate().unwrap_err();
+        assert!(err.to_string().starts_with("Invalid character '\"' at position 5 in name"));
+
+        assert!(config("", "17th")?.validate().is_err());
+
+        let mut with_extensions = config("17th", "17th Infantry Division")?;
+        with_extensions.pack.header_extensions = vec!["version=1.0".to_string()];
+        with_extensions.validate()?;
+        with_extensions.pack.header_extensions.push("version".to_string());
+        assert!(with_extensions.validate().is_err());
+
+        let mut with_folder = config("17th", "17th Infantry Division")?;
+        assert_eq!(with_folder.released_addon_path(), "release/@17th");
+        with_folder.release.mod_folder = Some("17th_Mod".to_string());
+        with_folder.validate()?;
+        assert_eq!(with_folder.released_addon_path(), "release/@17th_Mod");
+        with_folder.release.mod_folder = Some("../@17th".to_string());
+        assert!(with_folder.validate().is_err());
+
+        let mut with_author = config("17th", "17th Infantry Division")?;
+        with_author.author = Some(Authors::One("Pvt. \"Wagar\"".to_string()));
+        assert!(with_author.validate().is_err());
+        with_author.author = Some(Authors::Many(vec!["Pvt. Wagar".to_string(), "Sgt.\\Smith".to_string()]));
+        assert!(with_author.validate().is_err());
+
+        let mut with_overview: LaatConfig = toml::from_str("prefix = \"LAAT\"\nname = \"LAAT\"\noverview = \"Hold\"")?;
+        with_overview.validate()?;
+        with_overview.extra = toml::from_str("overview = 'Hold the \"line\"'")?;
+        let err = with_overview.validate().unwrap_err();
+        assert!(err.to_string().starts_with("Invalid character '\"' at position 9 in overview"));
+        with_overview.extra = toml::from_str("logoSmall = 'logo\"small.paa'")?;
+        assert!(with_overview.validate().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_config() -> Result<()> {
+        let value = parse_config_value(
+            r#"{
+                "prefix": "LAAT",
+                "name": "LAAT Test Mod",
+                "plugins": ["missions"],
+                "missions": { "maps": ["Altis"], "respawn_delay": 5, "composition_rotation": 90.0 }
+            }"#,
+            Path::new("LAAT.json"),
+        )?;
+        let config: LaatConfig = value.try_into()?;
+
+        assert_eq!(config.plugins, vec!["missions"]);
+        assert_eq!(config.extra["missions"]["respawn_delay"].as_integer(), Some(5));
+        assert_eq!(config.extra["missions"]["composition_rotation"].as_float(), Some(90.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdin_config() -> Result<()> {
+        let toml = parse_stdin_config("prefix = \"LAAT\"\nname = \"LAAT Test Mod\"")?;
+        let json = parse_stdin_config("\n  { \"prefix\": \"LAAT\", \"name\": \"LAAT Test Mod\" }")?;
+
+        assert_eq!(toml, json);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_authors() -> Result<()> {
+        let one: LaatConfig = toml::from_str("prefix = \"LAAT\"\nname = \"LAAT\"\nauthor = \"Pvt. Wagar\"")?;
+        let many: LaatConfig = toml::from_str("prefix = \"LAAT\"\nname = \"LAAT\"\nauthor = [\"Pvt. Wagar\", \"Sgt. Smith\"]")?;
+
+        assert_eq!(one.author, Some(Authors::One("Pvt. Wagar".to_string())));
+        assert_eq!(many.author.as_ref().map(Authors::names), Some(vec!["Pvt. Wagar", "Sgt. Smith"]));
+        assert_eq!(many.author.map(|author| author.credit()).as_deref(), Some("Pvt. Wagar, Sgt. Smith"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_config() {
+        let err = get_config_from_path("LAAT.release.toml".into()).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Config file LAAT.release.toml doesn't exist");
+    }
+}
@@ -1,7 +1,13 @@
+use crate::diff;
+use crate::report::AddonReport;
+use crate::retry::retry_io;
 use crate::Result;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use tokio::io::AsyncWriteExt;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::Semaphore;
 
 pub type BuildContext = super::config::LaatConfig;
 
@@ -18,7 +24,30 @@ pub struct AddonManager {
     /// Map containing ./assets paths and maps them to Addon prefixed paths.
     asset_map: HashMap<PathBuf, PathBuf>,
 
-    file_map: HashMap<PathBuf, String>,
+    /// Files added with `add_file`/`add_bytes`, by their addon prefixed path
+    file_map: HashMap<PathBuf, StagedFile>,
+
+    /// What added each file in `file_map`, for reporting conflicts.
+    file_sources: HashMap<PathBuf, String>,
+
+    /// What the files added next are for (i.e. "mission LAAT_missions_VRMission"), set with
+    /// `set_source`. Defaults to the plugin building the addon.
+    source: Option<String>,
+
+    /// Temporary folder the added files wait in until the addon is built, so their contents
+    /// aren't all held in memory. Created by the first added file (unless it's a dry run or a
+    /// diff), removed on drop.
+    staging: Option<TempDir>,
+}
+
+/// A file added to the addon, staged on disk
+struct StagedFile {
+    /// Path of the contents in the staging folder. Dry runs and diffs don't write the files, so
+    /// they aren't staged either.
+    path: Option<PathBuf>,
+    size: u64,
+    /// SHA-256 of the contents, to tell whether a file added twice changed
+    digest: Vec<u8>,
 }
 
 impl AddonManager {
@@ -29,6 +58,9 @@ impl AddonManager {
             build_context,
             asset_map: HashMap::new(),
             file_map: HashMap::new(),
+            file_sources: HashMap::new(),
+            source: None,
+            staging: None,
         }
     }
 
@@ -42,8 +74,9 @@ impl AddonManager {
         format!(r"{}/{}", self.build_context.prefix, self.addon).into()
     }
 
+    /// Folder the addon is built in, see `BuildContext::addon_build_path`
     pub fn build_path(&self) -> PathBuf {
-        self.build_context.build_path.clone().into()
+        self.build_context.addon_build_path()
     }
 
     /// Add an asset to the AssetManager
@@ -78,19 +111,28 @@ impl AddonManager {
     async fn copy_assets(&self) -> Result<()> {
         let mut futs = Vec::new();
 
+        let retries = self.build_context.io_retries;
+        let permits = Arc::new(Semaphore::new(self.build_context.jobs()));
+
         for (asset, addon_path) in self.asset_map.clone().into_iter() {
             debug!("Copying {} > {}", asset.display(), addon_path.display());
             let mut dest = self.build_path();
             dest.push(addon_path);
 
+            let permit = permits.clone().acquire_owned().await?;
+
             let fut = tokio::spawn(async move {
+                let _permit = permit;
+
                 if let Some(parent) = dest.parent() {
-                    if let Err(why) = tokio::fs::create_dir_all(parent).await {
+                    let description = format!("create {}", parent.display());
+                    if let Err(why) = retry_io(retries, &description, || tokio::fs::create_dir_all(parent)).await {
                         error!("Failed to create folder: {:?}. Error: {}", parent, why);
                     }
                 }
 
-                if let Err(why) = tokio::fs::copy(&asset, &dest).await {
+                let description = format!("copy {} to {}", asset.display(), dest.display());
+                if let Err(why) = retry_io(retries, &description, || tokio::fs::copy(&asset, &dest)).await {
                     error!("Failed to copy {:?} to {:?}. Error: {}", asset, dest, why);
                 }
             });
@@ -103,28 +145,133 @@ impl AddonManager {
         Ok(())
     }
 
+    /// Name what the files added from now on are for in conflict messages, i.e. the mission
+    /// they belong to
+    pub fn set_source(&mut self, source: impl Into<String>) {
+        self.source = Some(source.into());
+    }
+
+    fn current_source(&self) -> String {
+        match (&self.source, &self.build_context.plugin) {
+            (Some(source), _) => source.clone(),
+            (None, Some(plugin)) => format!("the {} plugin", plugin),
+            (None, None) => format!("addon {}", self.addon),
+        }
+    }
+
     /// Set the value to write to target file
-    pub fn add_file(&mut self, buffer: String, path: PathBuf) {
+    pub fn add_file(&mut self, buffer: String, path: PathBuf) -> Result<()> {
+        self.add_bytes(buffer.into_bytes(), path)
+    }
+
+    /// Set a rendered config (i.e. `config.cpp`) to write to target file, minified with
+    /// `minify_config` when `minify_configs` is set in LAAT.toml
+    pub fn add_config(&mut self, config: String, path: PathBuf) -> Result<()> {
+        if self.build_context.minify_configs {
+            self.add_file(crate::minify_config(&config), path)
+        } else {
+            self.add_file(config, path)
+        }
+    }
+
+    /// Set the raw bytes to write to target file (i.e. binarized configs)
+    ///
+    /// Adding the same path twice with different contents is an error, unless `allow_overwrite`
+    /// is set in LAAT.toml, in which case the last write wins.
+    pub fn add_bytes(&mut self, buffer: Vec<u8>, path: PathBuf) -> Result<()> {
+        let source = self.current_source();
+
         let mut file_path = PathBuf::new();
         file_path.push(self.addon_path());
         file_path.push(path);
 
-        self.file_map.insert(file_path, buffer);
+        let digest = Sha256::digest(&buffer).to_vec();
+
+        if let Some(existing) = self.file_map.get(&file_path) {
+            if existing.digest != digest {
+                let previous = &self.file_sources[&file_path];
+
+                if !self.build_context.allow_overwrite {
+                    return Err(format!(
+                        "File {} was added twice with different contents (first by {}, then by {})",
+                        file_path.display(),
+                        previous,
+                        source
+                    )
+                    .into());
+                }
+
+                warn!(
+                    "Overwriting {} (first added by {}, now by {})",
+                    file_path.display(),
+                    previous,
+                    source
+                );
+            }
+        }
+
+        if self.build_context.dry_run {
+            info!("Would write {} ({} bytes)", file_path.display(), buffer.len());
+        }
+
+        let staged = StagedFile {
+            path: if self.build_context.dry_run || self.build_context.diff {
+                None
+            } else {
+                Some(self.stage(&file_path, &buffer)?)
+            },
+            size: buffer.len() as u64,
+            digest,
+        };
+
+        self.file_sources.insert(file_path.clone(), source);
+        self.file_map.insert(file_path, staged);
+
+        Ok(())
+    }
+
+    /// Write `buffer` for `file_path` into the staging folder, returning where it was written
+    fn stage(&mut self, file_path: &Path, buffer: &[u8]) -> Result<PathBuf> {
+        let staging = match &self.staging {
+            Some(staging) => staging.path().to_owned(),
+            None => {
+                let staging = tempfile::Builder::new().prefix("laat_").tempdir()?;
+                let path = staging.path().to_owned();
+                self.staging = Some(staging);
+
+                path
+            }
+        };
+
+        let staged = staging.join(file_path);
+
+        if let Some(parent) = staged.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&staged, buffer)?;
+
+        Ok(staged)
     }
 
     #[instrument(err, skip(self))]
     async fn write_files(&self) -> Result<()> {
-        for (path, string) in self.file_map.clone().into_iter() {
+        for (path, staged) in self.file_map.iter() {
+            let staged = match &staged.path {
+                Some(staged) => staged,
+                None => continue,
+            };
+
             debug!("Writing file: {}", path.display());
             let mut file_path = self.build_path();
             file_path.push(path);
 
             if let Some(parent) = file_path.parent() {
-                tokio::fs::create_dir_all(parent).await?;
+                let description = format!("create {}", parent.display());
+                retry_io(self.build_context.io_retries, &description, || tokio::fs::create_dir_all(parent)).await?;
             }
 
-            let mut file = tokio::fs::File::create(file_path).await?;
-            file.write_all(string.as_bytes()).await?;
+            let description = format!("write {}", file_path.display());
+            retry_io(self.build_context.io_retries, &description, || tokio::fs::copy(staged, &file_path)).await?;
         }
 
         Ok(())
@@ -134,19 +281,99 @@ impl AddonManager {
         let mut addon_dir = self.build_path();
         addon_dir.push(self.addon_path());
 
-        tokio::fs::create_dir_all(addon_dir).await?;
+        let description = format!("create {}", addon_dir.display());
+        retry_io(self.build_context.io_retries, &description, || tokio::fs::create_dir_all(&addon_dir)).await?;
+
+        Ok(())
+    }
+
+    /// Remove this addon's folder from the build folder
+    pub async fn clean_addon(&self) -> Result<()> {
+        let mut addon_dir = self.build_path();
+        addon_dir.push(self.addon_path());
+
+        if addon_dir.exists() {
+            debug!("Removing addon folder: {}", addon_dir.display());
+            tokio::fs::remove_dir_all(addon_dir).await?;
+        }
 
         Ok(())
     }
 
     /// Build the addon
     pub async fn build_addon(&self) -> Result<()> {
+        if self.build_context.dry_run {
+            for (asset, addon_path) in self.asset_map.iter() {
+                info!("Would copy {} > {}", asset.display(), addon_path.display());
+            }
+
+            info!("Would create {}.pbo", self.addon);
+
+            return Ok(());
+        }
+
+        if self.build_context.diff {
+            return self.diff_addon();
+        }
+
         self.create_addon_folder().await?;
 
         tokio::try_join!(self.write_files(), self.copy_assets())?;
 
+        let file_count = self.file_map.len() + self.asset_map.len();
+        let size = self.size();
+
+        if file_count == 0 {
+            warn!("Built {} without any files", self.addon_path().display());
+        } else {
+            info!("Built {}: {} files, {} bytes", self.addon_path().display(), file_count, size);
+        }
+
+        self.build_context.report.add_addon(AddonReport {
+            path: self.addon_path().to_string_lossy().replace('\\', "/"),
+            files: file_count,
+            size,
+        });
+
         Ok(())
     }
+
+    /// Compare the files of this addon with the ones the last build left in the build folder,
+    /// instead of writing them
+    fn diff_addon(&self) -> Result<()> {
+        let mut files = self
+            .file_map
+            .iter()
+            .map(|(path, staged)| (path.clone(), staged.digest.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        for (asset, addon_path) in &self.asset_map {
+            files.insert(addon_path.clone(), Sha256::digest(&std::fs::read(asset)?).to_vec());
+        }
+
+        let changes = diff::diff_folder(&self.build_path(), &self.addon_path(), &files)?;
+        diff::log_changes(&self.addon_path(), &changes, files.len());
+
+        self.build_context.report.add_addon(AddonReport {
+            path: self.addon_path().to_string_lossy().replace('\\', "/"),
+            files: files.len(),
+            size: self.size(),
+        });
+        self.build_context.report.add_changes(changes);
+
+        Ok(())
+    }
+
+    /// Total size of the added files and assets, in bytes
+    fn size(&self) -> u64 {
+        self.file_map.values().map(|staged| staged.size).sum::<u64>()
+            + self
+                .asset_map
+                .keys()
+                .filter_map(|asset| std::fs::metadata(asset).ok())
+                .map(|metadata| metadata.len())
+                .sum::<u64>()
+    }
 }
 
 #[cfg(test)]
@@ -160,6 +387,9 @@ mod tests {
         BuildContext {
             prefix: "LAAT".to_string(),
             name: "LAAT Test Mod".to_string(),
+            author: None,
+            version: None,
+            logo: None,
             build_path: "build".to_string(),
             assets_path: "assets".to_string(),
             addons_path: "addons".to_string(),
@@ -169,12 +399,29 @@ mod tests {
                 include_folders: vec![],
                 excludes: vec![],
                 header_extensions: vec![],
+                compression: None,
             },
+            sign: None,
+            allow_overwrite: false,
+            allow_unknown_plugins: false,
+            isolate_plugin_builds: false,
+            minify_configs: false,
+            max_parallel_builds: 1,
+            io_retries: 0,
+            keep_build: false,
+            force: false,
+            dry_run: false,
+            diff: false,
+            plugin: None,
+            report: Default::default(),
+            progress: Default::default(),
             extra: Value::Float(0.0),
             keys_path: "keys".to_string(),
+            templates_path: "templates".to_string(),
             release: ReleaseConfig {
                 app_id: 0,
                 workshop_id: 0,
+                mod_folder: None,
             },
         }
     }
@@ -204,4 +451,72 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_add_file_conflict() -> Result<()> {
+        let mut manager = AddonManager::from_context("Test".to_string(), build_context());
+
+        manager.set_source("mission LAAT_VRMission");
+        manager.add_file("a".to_string(), "config.cpp".into())?;
+        manager.add_file("a".to_string(), "config.cpp".into())?;
+
+        manager.set_source("mission LAAT_AltisMission");
+        let err = manager.add_file("b".to_string(), "config.cpp".into()).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("(first by mission LAAT_VRMission, then by mission LAAT_AltisMission)"));
+
+        let mut context = build_context();
+        context.allow_overwrite = true;
+        let mut manager = AddonManager::from_context("Test".to_string(), context);
+
+        manager.add_file("a".to_string(), "config.cpp".into())?;
+        manager.add_file("b".to_string(), "config.cpp".into())?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_staged_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut context = build_context();
+        context.build_path = dir.path().display().to_string();
+
+        let mut manager = AddonManager::from_context("Test".to_string(), context);
+        manager.add_file("class CfgPatches {};".to_string(), "config.cpp".into())?;
+        manager.add_bytes(vec![0; 16], "data/blob.bin".into())?;
+
+        // Contents wait on disk rather than in memory
+        let staged = manager.file_map[Path::new("LAAT/Test/data/blob.bin")].path.clone().unwrap();
+        assert_eq!(std::fs::read(&staged)?, vec![0; 16]);
+
+        manager.build_addon().await?;
+
+        assert_eq!(std::fs::read_to_string(dir.path().join("LAAT/Test/config.cpp"))?, "class CfgPatches {};");
+        assert_eq!(std::fs::read(dir.path().join("LAAT/Test/data/blob.bin"))?, vec![0; 16]);
+
+        drop(manager);
+        assert!(!staged.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_skips_staging() -> Result<()> {
+        let mut context = build_context();
+        context.dry_run = true;
+
+        let mut manager = AddonManager::from_context("Test".to_string(), context);
+        manager.add_bytes(vec![0; 16], "data/blob.bin".into())?;
+
+        let staged = &manager.file_map[Path::new("LAAT/Test/data/blob.bin")];
+        assert!(staged.path.is_none());
+        assert!(manager.staging.is_none());
+        assert_eq!(manager.size(), 16);
+
+        // Conflicts are still caught from the digests
+        assert!(manager.add_bytes(vec![1; 16], "data/blob.bin".into()).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,144 @@
+//! `laat build --diff`: compare what a build would write with the files the last build left in
+//! the build folder, instead of overwriting them.
+//!
+//! Files are compared by their SHA-256, so only a change in contents counts.
+
+use crate::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// How a file differs from the last build
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Changed,
+    Removed,
+}
+
+/// A file that differs from the last build
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileChange {
+    /// Path in the build folder, with `/` as the separator (i.e. `LAAT/Music/config.cpp`)
+    pub path: String,
+    pub change: Change,
+}
+
+/// SHA-256 of every file in `root/folder`, by its path relative to `root`. Empty if the folder
+/// doesn't exist.
+pub fn digest_folder(root: &Path, folder: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut digests = BTreeMap::new();
+
+    if !root.join(folder).is_dir() {
+        return Ok(digests);
+    }
+
+    for entry in walkdir::WalkDir::new(root.join(folder)) {
+        let entry = entry?;
+
+        if entry.file_type().is_file() {
+            let digest = Sha256::digest(&std::fs::read(entry.path())?).to_vec();
+            digests.insert(entry.path().strip_prefix(root)?.to_owned(), digest);
+        }
+    }
+
+    Ok(digests)
+}
+
+/// Compare the new `files` of `folder` (SHA-256 by path relative to `root`) with the files in
+/// `root/folder`, sorted by path
+pub fn diff_folder(root: &Path, folder: &Path, files: &BTreeMap<PathBuf, Vec<u8>>) -> Result<Vec<FileChange>> {
+    let existing = digest_folder(root, folder)?;
+
+    let mut changes = files
+        .iter()
+        .filter_map(|(path, digest)| match existing.get(path) {
+            None => Some((path, Change::Added)),
+            Some(existing) if existing != digest => Some((path, Change::Changed)),
+            Some(_) => None,
+        })
+        .chain(
+            existing
+                .keys()
+                .filter(|path| !files.contains_key(*path))
+                .map(|path| (path, Change::Removed)),
+        )
+        .map(|(path, change)| FileChange {
+            path: path.to_string_lossy().replace('\\', "/"),
+            change,
+        })
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(changes)
+}
+
+/// Log a line per changed file of `folder`, and how many of its `files` didn't change
+pub fn log_changes(folder: &Path, changes: &[FileChange], files: usize) {
+    let count = |change| changes.iter().filter(|file| file.change == change).count();
+    let unchanged = files - count(Change::Added) - count(Change::Changed);
+
+    if changes.is_empty() {
+        info!("{}: {} files unchanged", folder.display(), unchanged);
+        return;
+    }
+
+    info!(
+        "{}: {} added, {} changed, {} removed, {} unchanged",
+        folder.display(),
+        count(Change::Added),
+        count(Change::Changed),
+        count(Change::Removed),
+        unchanged
+    );
+
+    for file in changes {
+        info!("  {:<8} {}", format!("{:?}", file.change).to_lowercase(), file.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_folder() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("LAAT/Music/data"))?;
+        std::fs::write(dir.path().join("LAAT/Music/config.cpp"), "class CfgPatches {};")?;
+        std::fs::write(dir.path().join("LAAT/Music/data/old.ogg"), "ogg")?;
+        std::fs::write(dir.path().join("LAAT/Music/data/same.ogg"), "same")?;
+
+        let digest = |contents: &str| Sha256::digest(contents.as_bytes()).to_vec();
+        let files = vec![
+            (PathBuf::from("LAAT/Music/config.cpp"), digest("class CfgPatches { class LAAT_Music {}; };")),
+            (PathBuf::from("LAAT/Music/data/same.ogg"), digest("same")),
+            (PathBuf::from("LAAT/Music/data/new.ogg"), digest("new")),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+        let changes = diff_folder(dir.path(), Path::new("LAAT/Music"), &files);
+        let missing = diff_folder(dir.path(), Path::new("LAAT/Missions"), &files);
+
+        let changes = changes?
+            .into_iter()
+            .map(|file| (file.path, file.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("LAAT/Music/config.cpp".to_string(), Change::Changed),
+                ("LAAT/Music/data/new.ogg".to_string(), Change::Added),
+                ("LAAT/Music/data/old.ogg".to_string(), Change::Removed),
+            ]
+        );
+
+        // Nothing built before, so everything is new
+        assert!(missing?.iter().all(|file| file.change == Change::Added));
+
+        Ok(())
+    }
+}
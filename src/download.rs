@@ -0,0 +1,171 @@
+//! Downloads of files hosted on a web server, i.e. shared compositions. Only built with the
+//! `http` feature.
+//!
+//! Downloaded files are kept with the ETag or Last-Modified date the server sent for them, so the
+//! next download only asks for the file if it changed. When the server can't be reached, a cached
+//! copy is used.
+
+use crate::Result;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Download `url` to `target`, unless the copy already at `target` is unchanged. With `optional`,
+/// a missing file (404) isn't an error: `target` is removed and `false` returned.
+pub(crate) async fn download(url: &str, target: &Path, optional: bool) -> Result<bool> {
+    let url = url.to_string();
+    let target = target.to_owned();
+
+    tokio::task::spawn_blocking(move || download_blocking(&url, &target, optional)).await?
+}
+
+fn download_blocking(url: &str, target: &Path, optional: bool) -> Result<bool> {
+    let validator_path = validator_path(target);
+    let validator = if target.is_file() {
+        std::fs::read_to_string(&validator_path).ok()
+    } else {
+        None
+    };
+
+    let mut request = ureq::get(url);
+    if let Some((header, value)) = validator.as_deref().and_then(|validator| validator.split_once(": ")) {
+        request = request.set(header, value);
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) if optional => {
+            for file in [target, validator_path.as_path()] {
+                if file.exists() {
+                    std::fs::remove_file(file)?;
+                }
+            }
+
+            return Ok(false);
+        }
+        Err(ureq::Error::Status(status, _)) => {
+            return Err(format!("Failed to download {}: the server answered with status {}", url, status).into())
+        }
+        Err(why) if target.is_file() => {
+            warn!("Failed to download {}, using the cached copy", why);
+            return Ok(true);
+        }
+        Err(why) if optional => {
+            warn!("Failed to download {}, skipping it", why);
+            return Ok(false);
+        }
+        Err(why) => return Err(format!("Failed to download {}", why).into()),
+    };
+
+    if response.status() == 304 {
+        debug!("{} is unchanged, using the cached copy", url);
+        return Ok(true);
+    }
+
+    // Request header that asks for the file only if it changed since this download
+    let validator = match (response.header("ETag"), response.header("Last-Modified")) {
+        (Some(etag), _) => Some(format!("If-None-Match: {}", etag)),
+        (None, Some(modified)) => Some(format!("If-Modified-Since: {}", modified)),
+        (None, None) => None,
+    };
+
+    let mut buffer = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut buffer)
+        .map_err(|why| format!("Failed to download {}: {}", url, why))?;
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(target, buffer)?;
+
+    match validator {
+        Some(validator) => std::fs::write(&validator_path, validator)?,
+        None if validator_path.exists() => std::fs::remove_file(&validator_path)?,
+        None => {}
+    }
+
+    info!("Downloaded {}", url);
+
+    Ok(true)
+}
+
+/// Where the validator (the conditional request header) of the file at `target` is kept
+fn validator_path(target: &Path) -> PathBuf {
+    let mut path = target.as_os_str().to_owned();
+    path.push(".validator");
+
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Answer one request per response with the given status line and headers, returning the
+    /// request headers that were received
+    fn serve(responses: Vec<&'static str>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                    request.push('\n');
+                }
+
+                requests.push(request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+
+            requests
+        });
+
+        (address, server)
+    }
+
+    #[tokio::test]
+    async fn test_download() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("composition.sqe");
+
+        let (address, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nfirst",
+            "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ]);
+
+        let url = format!("{}/camp/composition.sqe", address);
+        let first = download(&url, &target, false).await;
+        let contents = std::fs::read_to_string(&target);
+        let unchanged = download(&url, &target, false).await;
+        let missing = download(&url, &target, true).await;
+        let removed = !target.exists() && !validator_path(&target).exists();
+        let requests = server.join().unwrap();
+
+        assert!(first?);
+        assert_eq!(contents?, "first");
+        assert!(unchanged?);
+        assert!(requests[1].contains("If-None-Match: \"v1\""));
+        assert!(!missing?);
+        assert!(removed);
+
+        // Nothing cached to fall back on
+        let refused = download(&url, &dir.path().join("header.sqe"), false).await;
+        assert!(refused.unwrap_err().to_string().starts_with("Failed to download"));
+
+        Ok(())
+    }
+}
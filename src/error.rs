@@ -0,0 +1,109 @@
+//! Errors returned by LAAT.
+//!
+//! Most failures carry enough context to be shown to the user as is. The variants below cover the
+//! cases callers may want to handle on their own, i.e. a plugin with no settings in LAAT.toml.
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LaatError {
+    /// A plugin's `[section]` is missing from LAAT.toml. `found` lists the sections that are there.
+    #[error(
+        "Missing settings for plugin {key}: add a [{key}] section to LAAT.toml{}",
+        found_sections(key, found)
+    )]
+    MissingConfigKey { key: String, found: Vec<String> },
+
+    /// A setting in LAAT.toml has a value LAAT can't use. `reason` names the setting.
+    #[error("{reason}")]
+    InvalidConfig { key: String, reason: String },
+
+    /// `${VAR}` in LAAT.toml references an environment variable that isn't set
+    #[error("Environment variable {name} used in config value {value:?} is not set")]
+    MissingEnvVar { name: String, value: String },
+
+    /// A composition (or mission.sqm used as one) couldn't be read
+    #[error("Invalid composition {}: {reason}", path.display())]
+    CompositionParse { path: PathBuf, reason: String },
+
+    /// A template failed to render
+    #[error("Failed to render template {name}: {source}")]
+    TemplateRender {
+        name: String,
+        #[source]
+        source: Box<handlebars::RenderError>,
+    },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Any other error, described by its message
+    #[error("{0}")]
+    Other(String),
+
+    /// Errors from libraries LAAT builds on
+    #[error(transparent)]
+    External(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<String> for LaatError {
+    fn from(message: String) -> Self {
+        LaatError::Other(message)
+    }
+}
+
+impl From<&str> for LaatError {
+    fn from(message: &str) -> Self {
+        LaatError::Other(message.to_string())
+    }
+}
+
+macro_rules! external_errors {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl From<$error> for LaatError {
+                fn from(error: $error) -> Self {
+                    LaatError::External(Box::new(error))
+                }
+            }
+        )*
+    };
+}
+
+external_errors!(
+    toml::de::Error,
+    toml::ser::Error,
+    serde_json::Error,
+    handlebars::TemplateError,
+    handlebars::RenderError,
+    walkdir::Error,
+    glob::PatternError,
+    glob::GlobError,
+    std::path::StripPrefixError,
+    std::str::Utf8Error,
+    std::env::VarError,
+    tokio::task::JoinError,
+    tokio::sync::AcquireError,
+    ogg_metadata::OggMetadataError,
+    zip::result::ZipError,
+);
+
+/// The sections in LAAT.toml, with a hint when one of them looks like a typo of `key`
+fn found_sections(key: &str, found: &[String]) -> String {
+    if found.is_empty() {
+        return " (LAAT.toml has no plugin sections)".to_string();
+    }
+
+    let similar = found.iter().find(|section| {
+        let (section, key) = (section.to_lowercase(), key.to_lowercase());
+        section == key || section.trim_end_matches('s') == key.trim_end_matches('s')
+    });
+
+    let sections = found.iter().map(|section| format!("[{}]", section)).collect::<Vec<_>>().join(", ");
+
+    match similar {
+        Some(section) => format!(". Found {}, did you mean [{}]?", sections, section),
+        None => format!(". Found {}", sections),
+    }
+}
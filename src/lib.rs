@@ -23,22 +23,34 @@ extern crate async_trait;
 #[macro_use]
 extern crate tracing;
 
-use crate::config::LaatConfig;
+use crate::cache::{BuildCache, CACHE_FOLDER};
 use crate::context::BuildContext;
-use armake2::pbo::cmd_build;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::pack::{Compression, Excludes};
+use crate::progress::ProgressSink;
+use crate::report::{BuildReport, ReportCollector};
 use futures_util::future::join_all;
+use futures_util::stream::{FuturesUnordered, StreamExt};
 use handlebars::Handlebars;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Read;
-use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+use tracing::Instrument;
 
-pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub use crate::config::{Authors, LaatConfig, PackConfig, ReleaseConfig, SignConfig};
+pub use crate::error::LaatError;
+/// Re-exported for its config types, i.e. the `Config` taken by `plugins::set_mission_entities`
+pub use armake2;
+
+pub type Error = LaatError;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -48,6 +60,10 @@ const GITIGNORE: &str = r"
 build
 release
 ";
+const EXAMPLE_COMPOSITION: &[(&str, &str)] = &[
+    ("header.sqe", include_str!("../templates/example/header.sqe")),
+    ("composition.sqe", include_str!("../templates/example/composition.sqe")),
+];
 
 #[derive(Clone, Debug, StructOpt, Serialize)]
 pub struct InitSettings {
@@ -59,9 +75,53 @@ pub struct InitSettings {
     /// Prefix for the mod
     prefix: String,
 
+    #[structopt(short, long)]
+    /// Name of the mod, defaults to "<prefix> Aux Mod"
+    name: Option<String>,
+
     #[structopt(short, long, default_value = "Avery Wagar")]
     /// Name of the mod author
     author: String,
+
+    #[structopt(long)]
+    /// Create the project even if the destination folder is not empty
+    force: bool,
+}
+
+/// Values for the release's mod.cpp
+#[derive(Serialize)]
+struct ModCpp {
+    name: String,
+    /// Every author, comma separated
+    author: Option<String>,
+    /// The authors one by one, for custom templates
+    authors: Vec<String>,
+    version: Option<String>,
+    picture: Option<String>,
+    logo: Option<String>,
+    #[serde(rename = "logoSmall")]
+    logo_small: Option<String>,
+    overview: Option<String>,
+}
+
+/// Values for the release's meta.cpp
+#[derive(Serialize)]
+struct MetaCpp {
+    name: String,
+    workshop_id: usize,
+}
+
+/// Values for the starter LAAT.toml
+#[derive(Serialize)]
+struct ProjectTemplate<'a> {
+    prefix: &'a str,
+    name: String,
+    author: &'a str,
+    build_path: String,
+    assets_path: String,
+    addons_path: String,
+    release_path: String,
+    keys_path: String,
 }
 
 #[derive(Clone, Debug, StructOpt, Serialize)]
@@ -94,44 +154,346 @@ pub struct ReleaseSettings {
 /// LAAT Compiler
 pub struct LaatCompiler {
     config: LaatConfig,
-    /// List of compiler plugins
-    plugins: HashMap<String, Box<dyn Plugin>>,
+    /// List of compiler plugins, sorted so dependencies run first
+    plugins: Vec<Box<dyn Plugin>>,
 }
 
 impl LaatCompiler {
     #[instrument(skip(self))]
-    pub async fn build(&self, plugin_filter: Option<String>) -> Result<()> {
+    pub async fn build(&self, plugin_filter: Option<String>) -> Result<BuildReport> {
         info!("Generating Arma 3 Addons...");
-        self.clean_build().await?;
 
-        for (name, plugin) in self.plugins.iter() {
-            if let Some(plugin_filter) = &plugin_filter {
-                if plugin_filter == name {
-                    debug!("Running {}.", name);
-                    plugin.build(self.get_context()).await?;
-                }
-            } else {
+        if !self.config.dry_run && !self.config.diff {
+            self.clean_build(!self.config.force).await?;
+        }
+
+        let plugins = self.filter_plugins(&plugin_filter);
+        let mut report = BuildReport::default();
+
+        for plugin in plugins.iter() {
+            debug!("Preparing {}.", plugin.name());
+            plugin.pre_build(self.get_plugin_context(*plugin)).await?;
+        }
+
+        self.build_plugins(&plugins, &mut report).await?;
+
+        for plugin in plugins.iter() {
+            debug!("Finishing {}.", plugin.name());
+            plugin.post_build(self.get_plugin_context(*plugin)).await?;
+        }
+
+        self.config.report.drain_into(&mut report);
+        report.log_summary();
+
+        if self.config.diff {
+            // Addons of plugins left out of this build weren't compared
+            if plugins.len() == self.plugins.len() {
+                report.changes.extend(self.removed_addons(&report)?);
+            }
+
+            info!("Diff finished, nothing was written");
+        } else if self.config.dry_run {
+            info!("Dry run finished, nothing was written");
+        } else {
+            info!(
+                "Success! Mod has been generated at: ./{}",
+                self.get_context().build_path
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Run the plugins' build steps, up to `jobs` at a time. A plugin starts once
+    /// all of its dependencies have finished, and is skipped if one of them failed. Every plugin
+    /// is allowed to finish before the failures are reported.
+    async fn build_plugins<'a>(
+        &'a self,
+        plugins: &[&'a dyn Plugin],
+        report: &mut BuildReport,
+    ) -> Result<()> {
+        let jobs = self.config.jobs();
+        let names = plugins.iter().map(|plugin| plugin.name()).collect::<Vec<_>>();
+
+        let run = |plugin: &'a dyn Plugin| {
+            let name = plugin.name();
+            let span = info_span!("plugin", name = %name);
+            let build = plugin.build(self.get_plugin_context(plugin));
+
+            async move {
                 debug!("Running {}.", name);
-                plugin.build(self.get_context()).await?;
+
+                let start = Instant::now();
+                let result = build.await;
+
+                (name, start.elapsed(), result)
+            }
+            .instrument(span)
+        };
+
+        let mut pending = plugins.to_vec();
+        let mut finished: HashMap<String, bool> = HashMap::new();
+        let mut running = FuturesUnordered::new();
+        let mut failed = Vec::new();
+
+        loop {
+            let mut index = 0;
+
+            while index < pending.len() && running.len() < jobs {
+                let plugin = pending[index];
+                // Dependencies that aren't part of this build (i.e. filtered out) don't block
+                let dependencies = plugin
+                    .dependencies()
+                    .into_iter()
+                    .filter(|dependency| names.contains(dependency))
+                    .collect::<Vec<_>>();
+
+                if dependencies.iter().any(|dependency| finished.get(dependency) == Some(&false)) {
+                    warn!("Skipping {}, as one of its dependencies failed", plugin.name());
+                    report.add_plugin(plugin.name(), Duration::default(), false);
+                    finished.insert(plugin.name(), false);
+                    pending.remove(index);
+                } else if dependencies.iter().all(|dependency| finished.contains_key(dependency)) {
+                    running.push(run(plugin));
+                    pending.remove(index);
+                } else {
+                    index += 1;
+                }
+            }
+
+            match running.next().await {
+                Some((name, duration, result)) => {
+                    report.add_plugin(name.clone(), duration, result.is_ok());
+
+                    if let Err(why) = &result {
+                        error!("Plugin {} failed: {}", name, why);
+                        failed.push(name.clone());
+                    }
+
+                    finished.insert(name, result.is_ok());
+                }
+                None => break,
             }
         }
 
-        info!(
-            "Success! Mod has been generated at: ./{}",
-            self.get_context().build_path
-        );
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            report.log_summary();
+            Err(format!("Build failed for plugins: {}", failed.join(", ")).into())
+        }
+    }
+
+    /// Check LAAT.toml, the templates and every plugin's settings without building. Every
+    /// problem is logged, and the result is an error if there was any.
+    #[instrument(skip(self))]
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if let Err(why) = create_project_handlebars(&self.config).and_then(|handlebars| check_templates(&handlebars)) {
+            problems.push(why);
+        }
+
+        if let Some(logo) = &self.config.logo {
+            if !Path::new(logo).is_file() {
+                problems.push(LaatError::InvalidConfig {
+                    key: "logo".to_string(),
+                    reason: format!("logo {} in LAAT.toml is not a file", logo),
+                });
+            }
+        }
+
+        for plugin in self.plugins.iter() {
+            debug!("Validating {}.", plugin.name());
+            problems.extend(plugin.validate(&self.config));
+        }
+
+        if problems.is_empty() {
+            info!("LAAT.toml and the settings of {} plugins are valid", self.plugins.len());
+            return Ok(());
+        }
+
+        for problem in problems.iter() {
+            error!("{}", problem);
+        }
+
+        let plural = if problems.len() == 1 { "" } else { "s" };
+        Err(format!("Found {} problem{} in LAAT.toml", problems.len(), plural).into())
+    }
+
+    /// Run each plugin's clean step, removing what it generated. Without a filter the whole
+    /// build folder is cleared afterwards as well.
+    #[instrument(skip(self))]
+    pub async fn clean(&self, plugin_filter: Option<String>) -> Result<()> {
+        for plugin in self.filter_plugins(&plugin_filter) {
+            debug!("Cleaning {}.", plugin.name());
+            plugin.clean(self.get_plugin_context(plugin)).await?;
+        }
+
+        if plugin_filter.is_none() {
+            self.clean_build(false).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Plugins matching the filter, or every plugin when there is none
+    fn filter_plugins(&self, plugin_filter: &Option<String>) -> Vec<&dyn Plugin> {
+        self.plugins
+            .iter()
+            .filter(|plugin| match plugin_filter {
+                Some(plugin_filter) => plugin_filter == &plugin.name(),
+                None => true,
+            })
+            .map(|plugin| plugin.as_ref())
+            .collect()
+    }
+
+    /// Limit the plugins that run to `only` (when not empty), minus `skip`. Every name must be
+    /// a registered plugin.
+    pub fn select_plugins(&mut self, only: &[String], skip: &[String]) -> Result<()> {
+        let available: Vec<String> = plugins::plugins().iter().map(|plugin| plugin.name()).collect();
+
+        for name in only.iter().chain(skip) {
+            if !available.contains(name) {
+                return Err(format!(
+                    "Unknown plugin: {}. Available plugins: {}",
+                    name,
+                    available.join(", ")
+                )
+                .into());
+            }
+
+            if !self.config.plugins.contains(name) {
+                warn!("Plugin {} is not enabled in LAAT.toml", name);
+            }
+        }
+
+        self.plugins.retain(|plugin| {
+            let name = plugin.name();
+
+            (only.is_empty() || only.contains(&name)) && !skip.contains(&name)
+        });
 
         Ok(())
     }
 
+    /// Ignore the build cache and rebuild everything
+    pub fn set_force(&mut self, force: bool) {
+        self.config.force = force;
+    }
+
+    /// Limit how many tasks run at once, overriding `max_parallel_builds` in LAAT.toml
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.config.max_parallel_builds = jobs;
+    }
+
+    /// Keep the unpacked addons after `ship`, on top of `keep_build` in LAAT.toml
+    pub fn set_keep_build(&mut self, keep_build: bool) {
+        self.config.keep_build |= keep_build;
+    }
+
+    /// Collect the build's outputs with `collector`, i.e. one also installed as a tracing layer
+    /// to record the warnings logged while building
+    pub fn set_report_collector(&mut self, collector: ReportCollector) {
+        self.config.report = collector;
+    }
+
+    /// Send the progress plugins report while building to `sink`, i.e. for a progress bar
+    pub fn set_progress_sink(&mut self, sink: ProgressSink) {
+        self.config.progress = sink;
+    }
+
+    /// Compare the generated files with the last build instead of writing them
+    pub fn set_diff(&mut self, diff: bool) {
+        self.config.diff = diff;
+    }
+
+    /// Only log the files and PBOs a build would produce
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.config.dry_run = dry_run;
+    }
+
     fn get_context(&self) -> BuildContext {
         self.config.clone()
     }
 
-    pub async fn clean_build(&self) -> Result<()> {
+    /// The context handed to `plugin`, whose addons are built in its `plugin_build_path`
+    fn get_plugin_context(&self, plugin: &dyn Plugin) -> BuildContext {
+        let mut context = self.get_context();
+        context.plugin = Some(plugin.name());
+        context
+    }
+
+    /// Files of the addons in the build folder that `report` doesn't list, as the build that made
+    /// it wouldn't have written them
+    fn removed_addons(&self, report: &BuildReport) -> Result<Vec<diff::FileChange>> {
+        let mut changes = Vec::new();
+
+        for root in self.addon_build_roots() {
+            let addons = walkdir::WalkDir::new(&root)
+                .min_depth(2)
+                .max_depth(2)
+                .into_iter()
+                .filter_entry(|entry| entry.file_name() != CACHE_FOLDER);
+
+            for entry in addons {
+                let entry = entry?;
+                let folder = entry.path().strip_prefix(&root)?.to_owned();
+                let built = report
+                    .addons
+                    .iter()
+                    .any(|addon| Path::new(&addon.path) == folder);
+
+                if entry.file_type().is_dir() && !built {
+                    let removed = diff::diff_folder(&root, &folder, &Default::default())?;
+                    diff::log_changes(&folder, &removed, 0);
+                    changes.extend(removed);
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Folders holding the `<prefix>/<addon>` folders to pack. With `isolate_plugin_builds` that's
+    /// the build folder of every plugin that has one.
+    fn addon_build_roots(&self) -> Vec<PathBuf> {
+        if self.config.isolate_plugin_builds {
+            self.plugins
+                .iter()
+                .map(|plugin| self.config.plugin_build_path(&plugin.name()))
+                .filter(|path| path.is_dir())
+                .collect()
+        } else {
+            vec![PathBuf::from(&self.config.build_path)]
+        }
+    }
+
+    pub async fn clean_build(&self, keep_cache: bool) -> Result<()> {
         info!("Clearing build directory");
 
-        if let Err(why) = tokio::fs::remove_dir_all(self.get_context().build_path).await {
+        let build_path = self.get_context().build_path;
+
+        if keep_cache {
+            if let Ok(mut entries) = tokio::fs::read_dir(&build_path).await {
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.file_name() == CACHE_FOLDER {
+                        continue;
+                    }
+
+                    let result = if entry.file_type().await?.is_dir() {
+                        tokio::fs::remove_dir_all(entry.path()).await
+                    } else {
+                        tokio::fs::remove_file(entry.path()).await
+                    };
+
+                    if let Err(why) = result {
+                        warn!("Failed to clear {}: {}", entry.path().display(), why);
+                    }
+                }
+            }
+        } else if let Err(why) = tokio::fs::remove_dir_all(&build_path).await {
             warn!("Failed to clear build folder: {}", why);
         }
 
@@ -143,7 +505,7 @@ impl LaatCompiler {
     }
 
     #[instrument(skip(self))]
-    pub async fn pack(&self, sign: bool, windows: bool) -> Result<()> {
+    pub async fn pack(&self, sign: bool, windows: bool) -> Result<Manifest> {
         info!("Packaging project...");
         let release_path = self.get_context().released_addon_path();
 
@@ -152,11 +514,43 @@ impl LaatCompiler {
 
         self.create_pbos(&release_path, windows).await?;
 
-        if sign {
-            self.sign_pbos(&release_path, windows).await?;
+        if sign || self.config.sign.is_some() {
+            self.sign_release(&release_path, windows).await?;
         }
 
-        Ok(())
+        self.write_manifest(&release_path).await
+    }
+
+    /// Build, pack and sign the mod. The unpacked addons are removed afterwards, keeping the
+    /// build cache, unless `keep_build` is set.
+    pub async fn ship(&self, windows: bool) -> Result<BuildReport> {
+        let mut report = self.build(None).await?;
+        report.pbos = self.pack(true, windows).await?.files;
+
+        if self.config.keep_build {
+            info!("Kept the unpacked addons in {}", self.config.build_path);
+        } else {
+            info!("Removing the unpacked addons, pass --keep-build to inspect them");
+            self.clean_build(true).await?;
+        }
+
+        self.config.report.drain_into(&mut report);
+
+        Ok(report)
+    }
+
+    /// Write `manifest.json` with the size and SHA-256 of every PBO in the mod folder
+    pub async fn write_manifest(&self, release_path: &str) -> Result<Manifest> {
+        let path = PathBuf::from(release_path);
+        let name = self.config.name.clone();
+        let version = self.config.version.clone();
+
+        let manifest = tokio::task::spawn_blocking(move || Manifest::from_release(&path, name, version)).await??;
+
+        info!("Writing {} for {} PBOs", MANIFEST_FILE, manifest.files.len());
+        create_and_write_file(Path::new(release_path).join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?).await?;
+
+        Ok(manifest)
     }
 
     #[instrument(skip(self))]
@@ -218,22 +612,81 @@ impl LaatCompiler {
 
         info!("Signing PBOs...");
 
-        self.sign_pbos(&release_path, false).await?;
+        self.sign_release(&release_path, false).await?;
 
         Ok(())
     }
 
+    /// Sign the released PBOs, using the `[sign]` settings when present and otherwise the first
+    /// key pair found in `keys_path`.
+    async fn sign_release(&self, release_path: &str, windows: bool) -> Result<()> {
+        match &self.config.sign {
+            Some(sign_config) => self.sign_pbos_with_config(sign_config, release_path, windows).await,
+            None => self.sign_pbos(release_path, windows).await,
+        }
+    }
+
     #[instrument(skip(self, release_path), err)]
     pub async fn sign_pbos(&self, release_path: &str, windows: bool) -> Result<()> {
         let (privkey_path, pubkey_path) = self.get_keys().await?;
 
-        let addon_path = if windows { "Addons" } else { "addons" };
-
         let key_path = if windows { "Keys" } else { "keys" };
 
+        let file_name = pubkey_path
+            .file_name()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or("key.bikey".to_string());
+
+        info!("Copying key file: {}", file_name);
+
+        tokio::fs::copy(
+            pubkey_path,
+            format!("{}/{}/{}", release_path, key_path, file_name),
+        )
+        .await?;
+
+        self.sign_pbo_files(&privkey_path, release_path, windows).await
+    }
+
+    /// Sign PBOs with the private key configured in the `[sign]` section. Signing is skipped,
+    /// with a warning, when the key doesn't exist.
+    #[instrument(skip(self, sign_config, release_path), err)]
+    pub async fn sign_pbos_with_config(
+        &self,
+        sign_config: &SignConfig,
+        release_path: &str,
+        windows: bool,
+    ) -> Result<()> {
+        if !sign_config.private_key.is_file() {
+            warn!(
+                "Private key {:?} not found, skipping signing",
+                sign_config.private_key
+            );
+            return Ok(());
+        }
+
+        if sign_config.emit_bikey {
+            let key_path = if windows { "Keys" } else { "keys" };
+            let bikey_path = format!("{}/{}/{}.bikey", release_path, key_path, sign_config.key_name);
+
+            info!("Writing key file: {}", bikey_path);
+
+            let mut private_key = std::fs::File::open(&sign_config.private_key)?;
+            let public_key = armake2::sign::BIPrivateKey::read(&mut private_key)?.to_public_key();
+            public_key.write(&mut std::fs::File::create(bikey_path)?)?;
+        }
+
+        self.sign_pbo_files(&sign_config.private_key, release_path, windows).await
+    }
+
+    /// Write a `.bisign` next to every PBO in the release
+    async fn sign_pbo_files(&self, privkey_path: &Path, release_path: &str, windows: bool) -> Result<()> {
+        let addon_path = if windows { "Addons" } else { "addons" };
+
         let walkdir = walkdir::WalkDir::new(format!("{}/{}", release_path, addon_path));
 
         let mut sign_futs = Vec::new();
+        let permits = Arc::new(Semaphore::new(self.config.jobs()));
 
         for entry in walkdir {
             match entry {
@@ -250,9 +703,12 @@ impl LaatCompiler {
                         debug!(?path, ?privkey_path, "Signing: {:?}", path);
 
                         // Sign
-                        let privkey_path = privkey_path.clone();
+                        let privkey_path = privkey_path.to_owned();
+                        let permit = permits.clone().acquire_owned().await?;
 
                         let fut = tokio::task::spawn_blocking(move || {
+                            let _permit = permit;
+
                             if let Err(why) = armake2::sign::cmd_sign(
                                 privkey_path,
                                 path,
@@ -270,19 +726,6 @@ impl LaatCompiler {
             }
         }
 
-        let file_name = pubkey_path
-            .file_name()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or("key.bikey".to_string());
-
-        info!("Copying key file: {}", file_name);
-
-        tokio::fs::copy(
-            pubkey_path,
-            format!("{}/{}/{}", release_path, key_path, file_name),
-        )
-        .await?;
-
         join_all(sign_futs).await;
 
         Ok(())
@@ -290,9 +733,29 @@ impl LaatCompiler {
 
     #[instrument(skip(self), err)]
     pub async fn create_pbos(&self, release_path: &str, windows: bool) -> Result<()> {
-        let walkdir = walkdir::WalkDir::new(self.get_context().build_path)
-            .min_depth(2)
-            .max_depth(2);
+        let walkdir = self.addon_build_roots().into_iter().flat_map(|root| {
+            walkdir::WalkDir::new(root)
+                .min_depth(2)
+                .max_depth(2)
+                .into_iter()
+                .filter_entry(|entry| entry.file_name() != CACHE_FOLDER)
+        });
+        let mut addon_names = HashSet::new();
+
+        let include_folders = self.config.pack.expanded_include_folders()?;
+        // Headers from the include folders end up in the binarized configs, so their contents
+        // are part of every addon's hash
+        let include_hashes = include_folders
+            .iter()
+            .filter(|folder| folder.is_dir())
+            .map(|folder| Ok(format!("{}={}", folder.display(), BuildCache::hash_dir(folder, &[])?)))
+            .collect::<Result<Vec<_>>>()?;
+        let excludes = Excludes::new(&self.config.pack.excludes)?.with_ignore_file(Path::new(pack::IGNORE_FILE))?;
+        let project_ignore = std::fs::read_to_string(pack::IGNORE_FILE).unwrap_or_default();
+        let compression = Compression::from_config(&self.config.pack.compression)?;
+        let cache = BuildCache::new(&self.config);
+        let force = self.config.force;
+        let permits = Arc::new(Semaphore::new(self.config.jobs()));
 
         let mut pbo_futs = Vec::new();
 
@@ -302,21 +765,39 @@ impl LaatCompiler {
             match entry {
                 Ok(entry) => {
                     if entry.file_type().is_dir() {
+                        if !addon_names.insert(entry.file_name().to_ascii_lowercase()) {
+                            return Err(format!(
+                                "Found addon {} in more than one build folder, their PBOs would overwrite each other",
+                                entry.file_name().to_string_lossy()
+                            )
+                            .into());
+                        }
+
                         // Is Addon - make pbo
                         let LaatConfig {
-                            prefix, mut pack, ..
+                            prefix, version, mut pack, ..
                         } = self.get_context().clone();
 
                         let release_path = release_path.to_string();
+                        let include_folders = include_folders.clone();
+                        let include_hashes = include_hashes.clone();
+                        let excludes = excludes.clone();
+                        let project_ignore = project_ignore.clone();
+                        let cache = cache.clone();
+                        let permit = permits.clone().acquire_owned().await?;
+                        let addon = entry.file_name().to_string_lossy().to_string();
 
                         let fut = tokio::task::spawn_blocking(move || {
+                            let _permit = permit;
+
                             let mut build_pbo = || {
                                 debug!("Creating PBO: {}", entry.path().display());
 
                                 let file_name = entry.file_name().to_string_lossy();
                                 let pbo_name = format!("{}.pbo", file_name);
 
-                                // Check for $PBOPREFIX$ file.
+                                // Check for $PBOPREFIX$ file. It only sets the prefix, the
+                                // other header extensions still apply.
                                 let mut pbo_prefix_path = entry.path().to_owned();
                                 pbo_prefix_path.push(PBOPREFIX);
 
@@ -325,8 +806,14 @@ impl LaatCompiler {
                                         let mut pbo_prefix = String::new();
                                         file.read_to_string(&mut pbo_prefix)?;
 
-                                        pack.header_extensions.clear();
-                                        pack.header_extensions.push(pbo_prefix);
+                                        // Either a bare prefix, or key=value lines
+                                        for line in pbo_prefix.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                                            pack.header_extensions.push(if line.contains('=') {
+                                                line.to_string()
+                                            } else {
+                                                format!("prefix={}", line)
+                                            });
+                                        }
                                     }
 
                                     Err(_why) => {
@@ -335,26 +822,72 @@ impl LaatCompiler {
                                     }
                                 }
 
-                                let mut output = std::fs::File::create(format!(
-                                    "{}/{}/{}",
-                                    release_path, addon_path, pbo_name
-                                ))?;
+                                // Stamp the mod version, unless a header extension sets one
+                                if let Some(version) = &version {
+                                    if !pack.header_extensions.iter().any(|extension| extension.starts_with("version=")) {
+                                        pack.header_extensions.push(format!("version={}", version));
+                                    }
+                                }
+
+                                let output_path: PathBuf =
+                                    format!("{}/{}/{}", release_path, addon_path, pbo_name).into();
+
+                                let settings = pack
+                                    .header_extensions
+                                    .iter()
+                                    .cloned()
+                                    .chain(pack.excludes.iter().cloned())
+                                    .chain(include_hashes.iter().cloned())
+                                    .chain(std::iter::once(format!("{:?}", compression)))
+                                    .chain(std::iter::once(project_ignore.clone()))
+                                    .collect::<Vec<_>>();
+                                let hash = BuildCache::hash_dir(entry.path(), &settings)?;
+
+                                if !force {
+                                    if let Some(cached) = cache.cached_pbo(&pbo_name, &hash) {
+                                        info!("{} is up to date", pbo_name);
+                                        std::fs::copy(cached, &output_path)?;
+                                        return Ok(());
+                                    }
+                                }
+
+                                let mut output = std::fs::File::create(&output_path)?;
 
-                                cmd_build(
+                                let summary = pack::build_pbo(
                                     entry.path().to_owned(),
                                     &mut output,
                                     &pack.header_extensions,
-                                    &pack.excludes,
-                                    &pack.include_folders,
+                                    &excludes,
+                                    &include_folders,
+                                    compression,
                                 )?;
 
+                                let size = std::fs::metadata(&output_path)?.len();
+                                if summary.files == 0 {
+                                    warn!("Packed {} without any files", output_path.display());
+                                } else {
+                                    info!(
+                                        "Packed {}: {} files, {} bytes uncompressed, {} bytes on disk",
+                                        output_path.display(),
+                                        summary.files,
+                                        summary.data_size,
+                                        size
+                                    );
+                                }
+
+                                if let Err(why) = cache.store_pbo(&pbo_name, &hash, &output_path) {
+                                    warn!("Failed to cache {}: {}", pbo_name, why);
+                                }
+
                                 Ok(())
                             };
 
                             let result: Result<()> = build_pbo();
-                            if let Err(why) = result {
+                            if let Err(why) = &result {
                                 error!("Error creating pbo: {}", why);
                             }
+
+                            (addon, result)
                         });
 
                         pbo_futs.push(fut);
@@ -364,16 +897,32 @@ impl LaatCompiler {
             }
         }
 
-        join_all(pbo_futs).await;
+        let mut failed = Vec::new();
+        for result in join_all(pbo_futs).await {
+            let (addon, result) = result?;
+
+            if result.is_err() {
+                failed.push(addon);
+            }
+        }
+
+        if !failed.is_empty() {
+            failed.sort();
+            return Err(format!("Failed to pack addons: {}", failed.join(", ")).into());
+        }
 
         Ok(())
     }
 
+    /// Clear and recreate the mod folder. Anything else in `release_path`, like the staged
+    /// workshop metadata, is left alone.
     pub async fn setup_release_folder(&self, release_path: &str, windows: bool) -> Result<()> {
         info!("Clearing release directory...");
 
-        if let Err(why) = tokio::fs::remove_dir_all(self.get_context().release_path).await {
-            warn!("Failed to clear build folder: {}", why);
+        if Path::new(release_path).exists() {
+            if let Err(why) = tokio::fs::remove_dir_all(release_path).await {
+                warn!("Failed to clear release folder: {}", why);
+            }
         }
 
         // Create file structure
@@ -390,11 +939,50 @@ impl LaatCompiler {
         Ok(())
     }
 
+    /// Write the launcher metadata: `mod.cpp`, and `meta.cpp` when the mod has a workshop id.
+    /// A `logo` file is copied next to them.
     pub async fn create_mod_cpp(&self, release_path: &str) -> Result<()> {
-        let handlebars = create_handlebars()?;
-        let rendered = handlebars.render("mod.cpp", &self.get_context())?;
-        let mut file = std::fs::File::create(format!("{}/mod.cpp", release_path))?;
-        file.write_fmt(format_args!("{}", rendered))?;
+        let context = self.get_context();
+        let handlebars = create_project_handlebars(&context)?;
+
+        let extra = |key: &str| context.extra.get(key).and_then(toml::Value::as_str).map(String::from);
+
+        let logo = match &context.logo {
+            Some(logo) if Path::new(logo).is_file() => {
+                let file_name = Path::new(logo)
+                    .file_name()
+                    .ok_or_else(|| format!("Failed to get file name for logo: {}", logo))?;
+
+                tokio::fs::copy(logo, Path::new(release_path).join(file_name)).await?;
+
+                Some(file_name.to_string_lossy().to_string())
+            }
+            logo => logo.clone(),
+        };
+
+        let mod_cpp = ModCpp {
+            name: context.name.clone(),
+            author: context.author.as_ref().map(Authors::credit),
+            authors: context.author.iter().flat_map(Authors::names).map(String::from).collect(),
+            version: context.version.clone(),
+            picture: extra("picture"),
+            logo_small: extra("logoSmall").or_else(|| logo.clone()),
+            logo,
+            overview: extra("overview"),
+        };
+
+        let rendered = render_template(&handlebars, "mod.cpp", &mod_cpp)?;
+        create_and_write_file(format!("{}/mod.cpp", release_path), rendered).await?;
+
+        if context.release.workshop_id != 0 {
+            let rendered = render_template(&handlebars, "meta.cpp", &MetaCpp {
+                name: context.name.clone(),
+                workshop_id: context.release.workshop_id,
+            })?;
+            create_and_write_file(format!("{}/meta.cpp", release_path), rendered).await?;
+        } else {
+            debug!("No workshop_id in [release], skipping meta.cpp");
+        }
 
         Ok(())
     }
@@ -402,10 +990,20 @@ impl LaatCompiler {
     pub async fn from_path(path: PathBuf) -> Result<Self> {
         let config = config::get_config_from_path(path).await?;
 
-        let mut plugins = HashMap::new();
+        let plugins = plugins::get_plugins(&config.plugins, config.allow_unknown_plugins)?;
+
+        Self::new(config, plugins)
+    }
+
+    /// Create a compiler from a config and the plugins to run, i.e. when using LAAT as a library.
+    /// The plugins don't have to be listed in the config's `plugins`.
+    pub fn new(config: LaatConfig, plugins: Vec<Box<dyn Plugin>>) -> Result<Self> {
+        config.validate()?;
+
+        let plugins = plugins::sort_plugins(plugins)?;
 
-        for plugin in config.plugins.iter() {
-            plugins.insert(plugin.to_string(), plugins::get_plugin(plugin)?);
+        if config.version.is_none() {
+            warn!("No version set in LAAT.toml, builds won't be stamped with a version");
         }
 
         Ok(Self { config, plugins })
@@ -415,6 +1013,29 @@ impl LaatCompiler {
     pub async fn init(init: InitSettings) -> Result<Self> {
         let handlebars = create_handlebars()?;
 
+        if !init.force && is_non_empty_dir(&init.path).await? {
+            return Err(format!(
+                "{} already exists and is not empty, pass --force to create the project anyway",
+                init.path.display()
+            )
+            .into());
+        }
+
+        let template = ProjectTemplate {
+            prefix: &init.prefix,
+            name: init.name.clone().unwrap_or_else(|| format!("{} Aux Mod", init.prefix)),
+            author: &init.author,
+            build_path: config::default_build_path(),
+            assets_path: config::default_assets_path(),
+            addons_path: config::default_addons_path(),
+            release_path: config::default_release_path(),
+            keys_path: config::default_keys_path(),
+        };
+
+        // Check the settings before writing anything
+        let contents = render_template(&handlebars, "laat.toml", &template)?;
+        toml::from_str::<LaatConfig>(&contents)?.validate()?;
+
         // Create project folders
         for folder in PROJECT_FOLDERS {
             let mut path = init.path.clone();
@@ -427,7 +1048,6 @@ impl LaatCompiler {
         let mut laat_toml = init.path.clone();
         laat_toml.push("LAAT.toml");
 
-        let contents = handlebars.render("laat.toml", &init)?;
         create_and_write_file(&laat_toml, contents).await?;
 
         let mut gitignore = init.path.clone();
@@ -435,8 +1055,28 @@ impl LaatCompiler {
 
         create_and_write_file(&gitignore, GITIGNORE).await?;
 
+        // Example composition for the missions plugin
+        let composition = init.path.join(&template.assets_path).join("compositions/Example");
+        tokio::fs::create_dir_all(&composition).await?;
+
+        for (file, contents) in EXAMPLE_COMPOSITION {
+            create_and_write_file(composition.join(file), *contents).await?;
+        }
+
+        info!("Created LAAT project {} in {}", template.name, init.path.display());
+        info!("Next steps:");
+        info!("  cd {}", init.path.display());
+        info!("  Put your addon folders in ./{}", template.addons_path);
+        info!("  Run `laat build` to generate addons, then `laat pack` to create PBOs");
+
         // Init LAAT
-        Self::from_path(init.path).await
+        Self::from_path(init.path.join("LAAT.toml")).await
+    }
+
+    /// Where the missions plugin places every object of the compositions on `map` (the first of
+    /// its `maps` by default), as JSON or CSV
+    pub async fn preview(&self, map: Option<&str>, format: plugins::PreviewFormat) -> Result<String> {
+        plugins::preview(&self.config, map, format).await
     }
 
     /// Release mod to Steam Workshop
@@ -444,6 +1084,16 @@ impl LaatCompiler {
     pub async fn release(&self, release: ReleaseSettings) -> Result<()> {
         let context = self.get_context();
 
+        // Check for SteamCMD before asking for the change log, rather than failing after it
+        if find_executable(STEAMCMD, &std::env::var_os("PATH").unwrap_or_default()).is_none() {
+            return Err(format!(
+                "{} was not found on PATH. Install SteamCMD (https://developer.valvesoftware.com/wiki/SteamCMD) \
+                 and make sure `{}` runs in a terminal, then release again",
+                STEAMCMD, STEAMCMD
+            )
+            .into());
+        }
+
         // 1. Get Changelog
         let change_log = if let Some(log_file) = release.change_log_file {
             debug!("Loading change log");
@@ -458,7 +1108,10 @@ impl LaatCompiler {
 
             // TODO: Default changelog file
 
-            let editor = std::env::var("EDITOR")?;
+            let editor = std::env::var("EDITOR").map_err(|_| {
+                "Set EDITOR to write the change log in your editor, or pass the change notes, a change log \
+                 file (-f) or --no-change-log"
+            })?;
 
             info!("Waiting for {} to close...", editor);
             let mut editor = tokio::process::Command::new(editor)
@@ -483,20 +1136,17 @@ impl LaatCompiler {
         // 2. Strip " from changelog
         let changenotes = change_log.replace("\"", "");
 
-        let mut content_folder: PathBuf = std::env::var("PWD")?.into();
-        content_folder.push(context.released_addon_path());
-
-        // 3. render workshop_upload.vdf
-        let workshop_item = WorkshopItem {
-            app_id: context.release.app_id,
-            file_id: context.release.workshop_id,
-            content_folder,
-            changenotes,
+        // 3. render workshop_upload.vdf, with the title, description and preview from [workshop]
+        let settings = if context.extra.get(plugins::WORKSHOP_SETTINGS_KEY).is_some() {
+            plugins::WorkshopSettings::from_build_config(&context)?
+        } else {
+            plugins::WorkshopSettings::default()
         };
+        let workshop_item = settings.workshop_item(&context, settings.preview_file(&context)?, Some(changenotes))?;
 
         debug!(?workshop_item, "Rendering SteamCMD VDF");
-        let handlebars = create_handlebars()?;
-        let rendered = handlebars.render("workshop_upload.vdf", &workshop_item)?;
+        let handlebars = create_project_handlebars(&context)?;
+        let rendered = render_template(&handlebars, "workshop_upload.vdf", &workshop_item)?;
         let vdf_path: PathBuf = "/tmp/workshop_upload.vdf".into();
 
         // Write to temp file
@@ -505,7 +1155,7 @@ impl LaatCompiler {
 
         // 4. bash "steamcmd +login steamuser steampass steamguard +workshop_build_item ${PWD}/test.vdf +quit"
         info!("Starting SteamCMD");
-        let mut steamcmd = tokio::process::Command::new("steamcmd");
+        let mut steamcmd = tokio::process::Command::new(STEAMCMD);
         let mut steamcmd = steamcmd
             .arg("+login")
             .arg(release.username)
@@ -527,41 +1177,254 @@ impl LaatCompiler {
 }
 
 #[derive(Debug, Serialize)]
-struct WorkshopItem {
-    app_id: usize,
-    file_id: usize,
-    content_folder: PathBuf,
-    changenotes: String,
+pub(crate) struct WorkshopItem {
+    pub(crate) app_id: usize,
+    pub(crate) file_id: usize,
+    pub(crate) content_folder: PathBuf,
+    pub(crate) preview_file: Option<PathBuf>,
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) changenotes: Option<String>,
+}
+
+/// Build a project without going through LAAT.toml or the CLI
+///
+/// # Examples
+///
+/// ```no_run
+/// # async fn build() -> laat::Result<()> {
+/// let mut config = laat::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+/// config.set_plugin_settings("missions", toml::toml! {
+///     maps = ["VR"]
+///     composition = "./compositions/Base"
+///     ignore_center = false
+///     missions_folder = "./missions"
+/// })?;
+///
+/// let report = laat::run_build(config, vec![Box::new(laat::plugins::MissionPlugin)]).await?;
+/// println!("Built in {:?}", report.duration());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run_build(config: LaatConfig, plugins: Vec<Box<dyn Plugin>>) -> Result<BuildReport> {
+    LaatCompiler::new(config, plugins)?.build(None).await
+}
+
+/// Steam's command line client, which `laat release` uploads with. Packing needs no external
+/// tools, armake2 builds PBOs in-process.
+const STEAMCMD: &str = "steamcmd";
+
+/// Path of the executable `name` in one of the folders of `path` (i.e. the `PATH` variable)
+fn find_executable(name: &str, path: &std::ffi::OsStr) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) { &["exe", "cmd", "bat"] } else { &[""] };
+
+    std::env::split_paths(path).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|extension| dir.join(name).with_extension(extension))
+            .find(|candidate| candidate.is_file())
+    })
 }
 
+/// Folder in `templates_path` with the partials templates can include, i.e. `{{> header}}`
+const PARTIALS_FOLDER: &str = "partials";
+
+/// Built-in templates, as `(name, source)`. Plugins render these by name.
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("music_addon", include_str!("../templates/music/cfg_music.ht")),
+    ("missions_addon", include_str!("../templates/missions/cfg_missions.ht")),
+    ("laat.toml", include_str!("../templates/laat.toml.ht")),
+    ("mission.sqm", include_str!("../templates/mission.sqm.ht")),
+    ("workshop_upload.vdf", include_str!("../templates/workshop_upload.vdf.ht")),
+    ("mod.cpp", include_str!("../templates/mod.cpp.ht")),
+    ("meta.cpp", include_str!("../templates/meta.cpp.ht")),
+    ("cba_settings_addon", include_str!("../templates/cba_settings/cfg_settings.ht")),
+    ("cba_settings_init", include_str!("../templates/cba_settings/xeh_preinit.ht")),
+    ("cba_settings_userconfig", include_str!("../templates/cba_settings/userconfig.ht")),
+];
+
 pub fn create_handlebars<'a>() -> Result<Handlebars<'a>> {
     let mut handlebars = Handlebars::new();
 
-    handlebars.register_template_string(
-        "music_addon",
-        include_str!("../templates/music/cfg_music.ht"),
-    )?;
+    for (name, source) in BUILTIN_TEMPLATES {
+        handlebars
+            .register_template_string(name, source)
+            .map_err(|why| format!("Failed to register built-in template {}: {}", name, why))?;
+    }
 
-    handlebars.register_template_string(
-        "missions_addon",
-        include_str!("../templates/missions/cfg_missions.ht"),
-    )?;
+    check_templates(&handlebars)?;
 
-    handlebars.register_template_string("laat.toml", include_str!("../templates/laat.toml.ht"))?;
+    handlebars.register_helper("sanitize_classname", Box::new(helpers::sanitize_classname));
+    handlebars.register_helper("upper", Box::new(helpers::upper));
 
-    handlebars
-        .register_template_string("mission.sqm", include_str!("../templates/mission.sqm.ht"))?;
+    Ok(handlebars)
+}
 
-    handlebars.register_template_string(
-        "workshop_upload.vdf",
-        include_str!("../templates/workshop_upload.vdf.ht"),
-    )?;
+/// Render a registered template, naming it in the error if rendering fails
+pub fn render_template<T: Serialize>(handlebars: &Handlebars, name: &str, data: &T) -> Result<String> {
+    handlebars.render(name, data).map_err(|source| LaatError::TemplateRender {
+        name: name.to_string(),
+        source: Box::new(source),
+    })
+}
 
-    handlebars.register_template_string("mod.cpp", include_str!("../templates/mod.cpp.ht"))?;
+/// Strip `//` and `/* */` comments outside of strings, trailing whitespace and blank lines from a
+/// rendered config. Indentation is kept, so the result still diffs line by line.
+pub fn minify_config(config: &str) -> String {
+    let mut stripped = String::with_capacity(config.len());
+    let mut chars = config.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // `""` inside a string is an escaped quote, which toggles twice
+                in_string = !in_string;
+                stripped.push(c);
+            }
+            '\n' => {
+                // Config strings can't span lines, so an unclosed quote doesn't eat the file
+                in_string = false;
+                stripped.push(c);
+            }
+            '/' if !in_string && chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&next| next != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if !in_string && chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    // Keep line breaks so preprocessor lines stay on their own line
+                    if next == '\n' {
+                        stripped.push(next);
+                    }
+                    previous = next;
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+
+    let mut minified = stripped
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    minified.push('\n');
+
+    minified
+}
+
+/// Make sure every built-in template is registered, so a missing one is reported up front
+/// rather than when a plugin renders it
+fn check_templates(handlebars: &Handlebars) -> Result<()> {
+    let missing: Vec<&str> = BUILTIN_TEMPLATES
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| !handlebars.has_template(name))
+        .collect();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Missing templates: {}", missing.join(", ")).into())
+    }
+}
+
+/// Handlebars helpers available in every template
+mod helpers {
+    use handlebars::handlebars_helper;
+
+    handlebars_helper!(sanitize_classname: |name: str| super::sanitize_classname(name));
+    handlebars_helper!(upper: |value: str| value.to_uppercase());
+}
+
+/// Replace every character that isn't allowed in an Arma class name with `_`
+pub fn sanitize_classname(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Create the Handlebars registry for a project. Any `*.hbs` file in the project's
+/// `templates_path` is registered under its file stem (i.e. `mission.sqm.hbs` > `mission.sqm`),
+/// overriding the built-in template of the same name.
+pub fn create_project_handlebars<'a>(build_context: &BuildContext) -> Result<Handlebars<'a>> {
+    let mut handlebars = create_handlebars()?;
+
+    let templates_path = Path::new(&build_context.templates_path);
+
+    if !templates_path.is_dir() {
+        return Ok(handlebars);
+    }
+
+    register_partials(&mut handlebars, &templates_path.join(PARTIALS_FOLDER))?;
+
+    let walkdir = walkdir::WalkDir::new(templates_path)
+        .min_depth(1)
+        .max_depth(1);
+
+    for entry in walkdir {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        if let Some(name) = file_name.strip_suffix(".hbs") {
+            if handlebars.has_template(name) {
+                info!("Overriding built-in template: {}", name);
+            } else {
+                debug!("Registering project template: {}", name);
+            }
+
+            handlebars
+                .register_template_file(name, entry.path())
+                .map_err(|why| format!("Failed to register template {}: {}", entry.path().display(), why))?;
+        }
+    }
 
     Ok(handlebars)
 }
 
+/// Register every `.hbs` file in `partials_path` as a partial, named by its path relative to the
+/// folder without the extension, i.e. `{{> header}}` or `{{> sqm/attributes}}`
+fn register_partials(handlebars: &mut Handlebars, partials_path: &Path) -> Result<()> {
+    if !partials_path.is_dir() {
+        return Ok(());
+    }
+
+    for entry in walkdir::WalkDir::new(partials_path).min_depth(1) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(partials_path)?;
+
+        let name = match relative.to_string_lossy().replace('\\', "/").strip_suffix(".hbs") {
+            Some(name) if entry.file_type().is_file() => name.to_string(),
+            _ => continue,
+        };
+
+        debug!("Registering partial: {}", name);
+
+        let source = std::fs::read_to_string(entry.path())?;
+        handlebars
+            .register_partial(&name, source)
+            .map_err(|why| format!("Failed to register partial {}: {}", entry.path().display(), why))?;
+    }
+
+    Ok(())
+}
+
+async fn is_non_empty_dir(path: &Path) -> Result<bool> {
+    if !path.is_dir() {
+        return Ok(false);
+    }
+
+    Ok(tokio::fs::read_dir(path).await?.next_entry().await?.is_some())
+}
+
 async fn create_and_write_file(
     file_path: impl AsRef<Path>,
     contents: impl Into<String>,
@@ -575,22 +1438,203 @@ async fn create_and_write_file(
 use plugins::Plugin;
 pub mod plugins {
     use super::context::BuildContext;
-    use crate::Result;
+    use crate::{LaatError, Result};
 
     #[async_trait]
-    pub trait Plugin {
+    pub trait Plugin: Send + Sync {
+        /// Runs before any plugin is built
+        async fn pre_build(&self, _: BuildContext) -> Result<()> {
+            Ok(())
+        }
+
         async fn build(&self, _: BuildContext) -> Result<()>;
+
+        /// Runs once every plugin has been built
+        async fn post_build(&self, _: BuildContext) -> Result<()> {
+            Ok(())
+        }
+
+        /// Remove whatever this plugin generated in the build folder
+        async fn clean(&self, _: BuildContext) -> Result<()> {
+            Ok(())
+        }
+
         fn name(&self) -> String;
+
+        /// Names of the plugins that must run before this one
+        fn dependencies(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        /// JSON schema of the settings this plugin reads from its `[section]` of LAAT.toml, if
+        /// it has any
+        fn settings_schema(&self) -> Option<serde_json::Value> {
+            None
+        }
+
+        /// Check this plugin's settings and the files they point at without building, returning
+        /// every problem found
+        fn validate(&self, _: &BuildContext) -> Vec<LaatError> {
+            Vec::new()
+        }
     }
 
     pub fn get_plugin(name: &str) -> Result<Box<dyn Plugin>> {
         plugins()
             .into_iter()
             .find(|p| p.name() == name)
-            .map(|p| Ok(p))
+            .map(Ok)
             .unwrap_or(Err(format!("Unknown Plugin: {}", name).into()))
     }
 
+    /// The plugins enabled by `names`, i.e. `plugins` in LAAT.toml. Unknown names are an error
+    /// listing them with the available plugins, or only a warning with `allow_unknown`.
+    pub fn get_plugins(names: &[String], allow_unknown: bool) -> Result<Vec<Box<dyn Plugin>>> {
+        let mut enabled = Vec::new();
+        let mut unknown = Vec::new();
+
+        for name in names {
+            match get_plugin(name) {
+                Ok(plugin) => enabled.push(plugin),
+                Err(_) => unknown.push(name.as_str()),
+            }
+        }
+
+        if unknown.is_empty() {
+            return Ok(enabled);
+        }
+
+        let available = plugins().iter().map(|plugin| plugin.name()).collect::<Vec<_>>();
+        let reason = format!(
+            "Unknown plugins in LAAT.toml: {}. Available plugins: {}",
+            unknown.join(", "),
+            available.join(", ")
+        );
+
+        if allow_unknown {
+            warn!("{}. Skipping them", reason);
+
+            Ok(enabled)
+        } else {
+            Err(LaatError::InvalidConfig {
+                key: "plugins".to_string(),
+                reason,
+            })
+        }
+    }
+
+    /// Sort plugins so that every plugin runs after its dependencies, keeping the given order
+    /// wherever dependencies allow it.
+    pub fn sort_plugins(mut plugins: Vec<Box<dyn Plugin>>) -> Result<Vec<Box<dyn Plugin>>> {
+        let names: Vec<String> = plugins.iter().map(|p| p.name()).collect();
+
+        for plugin in plugins.iter() {
+            for dependency in plugin.dependencies() {
+                if !names.contains(&dependency) {
+                    return Err(format!(
+                        "Plugin {} depends on {}, which is not enabled",
+                        plugin.name(),
+                        dependency
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let mut sorted: Vec<Box<dyn Plugin>> = Vec::with_capacity(plugins.len());
+
+        while !plugins.is_empty() {
+            // First plugin, in config order, whose dependencies have all been scheduled
+            let ready = plugins.iter().position(|plugin| {
+                plugin
+                    .dependencies()
+                    .iter()
+                    .all(|dependency| sorted.iter().any(|p| &p.name() == dependency))
+            });
+
+            match ready {
+                Some(idx) => sorted.push(plugins.remove(idx)),
+                None => {
+                    let cycle: Vec<String> = plugins.iter().map(|p| p.name()).collect();
+                    return Err(
+                        format!("Plugin dependency cycle between: {}", cycle.join(", ")).into(),
+                    );
+                }
+            }
+        }
+
+        Ok(sorted)
+    }
+
+    /// List every plugin with the settings it reads, or, with `json`, print each plugin's settings
+    /// schema as JSON, keyed by plugin name
+    pub fn describe_plugins(json: bool) -> Result<String> {
+        use std::fmt::Write;
+
+        if json {
+            let schemas = plugins()
+                .iter()
+                .map(|plugin| (plugin.name(), plugin.settings_schema().unwrap_or_default()))
+                .collect::<serde_json::Map<_, _>>();
+
+            return Ok(serde_json::to_string_pretty(&schemas)?);
+        }
+
+        let mut description = String::new();
+
+        for plugin in plugins() {
+            let _ = writeln!(description, "{}", plugin.name());
+
+            let schema = match plugin.settings_schema() {
+                Some(schema) => schema,
+                None => {
+                    let _ = writeln!(description, "  No settings");
+                    continue;
+                }
+            };
+
+            let required = schema["required"].as_array().cloned().unwrap_or_default();
+
+            for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+                let mut line = format!("  {}: {}", name, schema_type(property));
+
+                if required.iter().any(|required| required == name) {
+                    line.push_str(", required");
+                } else if let Some(default) = property.get("default").filter(|default| !default.is_null()) {
+                    let _ = write!(line, ", default {}", default);
+                }
+
+                if let Some(about) = property["description"].as_str() {
+                    let _ = write!(line, ". {}", about.replace('\n', " "));
+                }
+
+                let _ = writeln!(description, "{}", line);
+            }
+        }
+
+        Ok(description)
+    }
+
+    /// Short name of a property's type in a JSON schema, i.e. "string", "array" or "MapConfig"
+    fn schema_type(property: &serde_json::Value) -> String {
+        if let Some(kind) = property["type"].as_str() {
+            return kind.to_string();
+        }
+
+        if let Some(kinds) = property["type"].as_array() {
+            return kinds.iter().filter_map(|kind| kind.as_str()).collect::<Vec<_>>().join(" or ");
+        }
+
+        if let Some(reference) = property["$ref"].as_str() {
+            return reference.rsplit('/').next().unwrap_or(reference).to_string();
+        }
+
+        match property["anyOf"].as_array().or_else(|| property["allOf"].as_array()) {
+            Some(kinds) => kinds.iter().map(schema_type).collect::<Vec<_>>().join(" or "),
+            None => "any".to_string(),
+        }
+    }
+
     pub fn plugins() -> Vec<Box<dyn Plugin>> {
         vec![
             Box::new(MusicPlugin),
@@ -598,6 +1642,9 @@ pub mod plugins {
             Box::new(CustomsPlugin),
             Box::new(KitPlugin),
             Box::new(MissionPlugin),
+            Box::new(PackPlugin),
+            Box::new(WorkshopPlugin),
+            Box::new(CbaSettingsPlugin),
         ]
     }
 
@@ -614,9 +1661,351 @@ pub mod plugins {
     pub use kits::KitPlugin;
 
     mod missions;
-    pub use missions::MissionPlugin;
+    pub use missions::{set_mission_entities, MissionPlugin, PreviewFormat};
+    pub(crate) use missions::preview;
+
+    mod pack;
+    pub use pack::PackPlugin;
+
+    mod workshop;
+    pub use workshop::WorkshopPlugin;
+    pub(crate) use workshop::{WorkshopSettings, WORKSHOP_SETTINGS_KEY};
+
+    mod cba_settings;
+    pub use cba_settings::CbaSettingsPlugin;
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{BuildReport, LaatCompiler, LaatConfig};
+
+        struct TestPlugin(&'static str, &'static [&'static str]);
+
+        #[async_trait]
+        impl Plugin for TestPlugin {
+            async fn build(&self, _: BuildContext) -> Result<()> {
+                if self.0 == "broken" {
+                    return Err("broken".into());
+                }
+
+                Ok(())
+            }
+
+            fn name(&self) -> String {
+                self.0.to_string()
+            }
+
+            fn dependencies(&self) -> Vec<String> {
+                self.1.iter().map(|d| d.to_string()).collect()
+            }
+        }
+
+        fn names(plugins: Vec<Box<dyn Plugin>>) -> Vec<String> {
+            plugins.iter().map(|p| p.name()).collect()
+        }
+
+        #[tokio::test]
+        async fn test_build_plugins_failure() -> Result<()> {
+            let mut config: LaatConfig = toml::from_str("prefix = \"LAAT\"\nname = \"LAAT\"")?;
+            config.max_parallel_builds = 2;
+
+            let laat = LaatCompiler {
+                config,
+                plugins: sort_plugins(vec![
+                    Box::new(TestPlugin("broken", &[])),
+                    Box::new(TestPlugin("music", &[])),
+                    Box::new(TestPlugin("sign", &["broken"])),
+                    Box::new(TestPlugin("missions", &["music"])),
+                ])?,
+            };
+
+            let mut report = BuildReport::default();
+            let err = laat.build_plugins(&laat.filter_plugins(&None), &mut report).await.unwrap_err();
+
+            assert_eq!(err.to_string(), "Build failed for plugins: broken");
+
+            let mut results = report
+                .plugins
+                .iter()
+                .map(|plugin| (plugin.name.as_str(), plugin.success))
+                .collect::<Vec<_>>();
+            results.sort();
+
+            assert_eq!(results, vec![("broken", false), ("missions", true), ("music", true), ("sign", false)]);
+
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_run_build() -> Result<()> {
+            let build_path = tempfile::tempdir()?;
+
+            let mut config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+            config.build_path = build_path.path().display().to_string();
+            config.set_plugin_settings("music", toml::toml! { file = "music.toml" })?;
+
+            assert_eq!(config.extra["music"]["file"].as_str(), Some("music.toml"));
+
+            let report = crate::run_build(
+                config,
+                vec![Box::new(TestPlugin("missions", &["music"])), Box::new(TestPlugin("music", &[]))],
+            )
+            .await?;
+
+            let names = report.plugins.into_iter().map(|plugin| plugin.name).collect::<Vec<_>>();
+            assert_eq!(names, vec!["music", "missions"]);
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_validate() -> Result<()> {
+            let mut config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+            config.logo = Some("missing/logo.paa".to_string());
+            config.set_plugin_settings(
+                "missions",
+                toml::toml! {
+                    maps = ["VR"]
+                    composition = "missing/composition"
+                    cba_settings_file = "missing/cba_settings.sqf"
+                    ignore_center = false
+                    missions_folder = "./missions"
+                },
+            )?;
+            config.set_plugin_settings("kits", toml::toml! { file = "missing/kits.toml" })?;
+
+            let laat = LaatCompiler::new(config.clone(), vec![Box::new(MissionPlugin), Box::new(KitPlugin)])?;
+            let err = laat.validate().unwrap_err();
+            assert_eq!(err.to_string(), "Found 4 problems in LAAT.toml");
+
+            let problems = MissionPlugin.validate(&config);
+            assert!(matches!(&problems[0], LaatError::InvalidConfig { key, .. } if key == "missions.composition"));
+
+            config.logo = None;
+            config.set_plugin_settings("kits", toml::toml! { file = "Cargo.toml" })?;
+            config.set_plugin_settings(
+                "missions",
+                toml::toml! {
+                    maps = ["VR"]
+                    composition = "src"
+                    ignore_center = false
+                    missions_folder = "./missions"
+                },
+            )?;
+            assert!(LaatCompiler::new(config, vec![Box::new(MissionPlugin), Box::new(KitPlugin)])?.validate().is_ok());
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_describe_plugins() -> Result<()> {
+            let description = describe_plugins(false)?;
+
+            assert!(description.contains("music\n  No settings\n"));
+            assert!(description.contains("  addon_name: string, default \"Missions\". Name of the generated Addon."));
+            assert!(description.contains("  maps: array, required. List of maps to create missions for\n"));
+
+            let schemas: serde_json::Value = serde_json::from_str(&describe_plugins(true)?)?;
+            assert!(schemas["music"].is_null());
+            assert!(schemas["missions"]["properties"]["slots"].is_object());
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_sort_plugins() -> Result<()> {
+            let plugins: Vec<Box<dyn Plugin>> = vec![
+                Box::new(TestPlugin("sign", &["music", "missions"])),
+                Box::new(TestPlugin("music", &[])),
+                Box::new(TestPlugin("missions", &[])),
+            ];
+
+            assert_eq!(names(sort_plugins(plugins)?), vec!["music", "missions", "sign"]);
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_get_plugins() -> Result<()> {
+            let configured = ["music".to_string(), "missons".to_string(), "pak".to_string()];
+
+            let err = get_plugins(&configured, false).err().unwrap();
+            assert!(matches!(&err, LaatError::InvalidConfig { key, .. } if key == "plugins"));
+            assert!(err.to_string().contains("Unknown plugins in LAAT.toml: missons, pak. Available plugins: music, addons"));
+
+            assert_eq!(names(get_plugins(&configured, true)?), vec!["music"]);
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_sort_plugins_cycle() {
+            let plugins: Vec<Box<dyn Plugin>> = vec![
+                Box::new(TestPlugin("music", &[])),
+                Box::new(TestPlugin("a", &["b"])),
+                Box::new(TestPlugin("b", &["a"])),
+            ];
+
+            let err = sort_plugins(plugins).err().unwrap().to_string();
+
+            assert_eq!(err, "Plugin dependency cycle between: a, b");
+        }
+    }
 }
 
+pub mod cache;
 pub mod context;
+pub mod diff;
+pub mod error;
+pub mod manifest;
+pub mod pack;
+pub mod progress;
+pub mod report;
 
 mod config;
+#[cfg(feature = "http")]
+mod download;
+mod retry;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_helpers() -> Result<()> {
+        let handlebars = create_handlebars()?;
+
+        let rendered = handlebars.render_template(
+            "class {{sanitize_classname name}} {}; // {{upper name}}",
+            &serde_json::json!({ "name": "17th Zeus-Mission.Altis" }),
+        )?;
+
+        assert_eq!(rendered, "class 17th_Zeus_Mission_Altis {}; // 17TH ZEUS-MISSION.ALTIS");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_templates() -> Result<()> {
+        let handlebars = create_handlebars()?;
+        check_templates(&handlebars)?;
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_template_string("mod.cpp", "")?;
+
+        let err = check_templates(&handlebars).unwrap_err().to_string();
+        assert!(err.starts_with("Missing templates: music_addon, missions_addon"));
+        assert!(!err.contains("mod.cpp"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_executable() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let name = if cfg!(windows) { "steamcmd.exe" } else { "steamcmd" };
+        std::fs::write(dir.path().join(name), "")?;
+
+        let path = std::env::join_paths([Path::new("missing"), dir.path()]).unwrap();
+        let found = find_executable(STEAMCMD, &path);
+        let missing = find_executable("armake", &path);
+
+        assert_eq!(found, Some(dir.path().join(name)));
+        assert_eq!(missing, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partials() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("partials/sqm"))?;
+        std::fs::write(dir.path().join("partials/header.hbs"), "// {{name}}")?;
+        std::fs::write(dir.path().join("partials/sqm/version.hbs"), "version = 54;")?;
+        std::fs::write(dir.path().join("partials/notes.txt"), "not a partial")?;
+        std::fs::write(dir.path().join("mod.cpp.hbs"), "{{> header}} {{> sqm/version}}")?;
+
+        let mut config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        config.templates_path = dir.path().display().to_string();
+
+        let handlebars = create_project_handlebars(&config)?;
+        let rendered = render_template(&handlebars, "mod.cpp", &serde_json::json!({ "name": "LAAT" }))?;
+
+        assert_eq!(rendered, "// LAAT version = 54;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minify_config() {
+        let config = r#"// Generated by LAAT
+class CfgPatches {   
+
+  class LAAT_Music { /* no units
+    yet */
+    url = "https://example.com"; // Not a comment inside a string
+    text = "Say ""hi"" // still text";
+  };
+};
+"#;
+
+        assert_eq!(
+            minify_config(config),
+            "class CfgPatches {\n  class LAAT_Music {\n    url = \"https://example.com\";\n    text = \"Say \"\"hi\"\" // still text\";\n  };\n};\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_pbos_include_changes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let addon = dir.path().join("build/LAAT/Test");
+        let release = dir.path().join("release");
+        std::fs::create_dir_all(&addon)?;
+        std::fs::create_dir_all(release.join("addons"))?;
+        std::fs::create_dir_all(dir.path().join("include"))?;
+
+        std::fs::write(addon.join("config.cpp"), "#include \"\\shared.hpp\"\nclass CfgPatches { value = VALUE; };")?;
+        std::fs::write(dir.path().join("include/shared.hpp"), "#define VALUE 1")?;
+
+        let mut config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        config.build_path = dir.path().join("build").display().to_string();
+        config.pack.include_folders = vec![dir.path().join("include")];
+        let laat = LaatCompiler::new(config, vec![])?;
+
+        let release_path = release.display().to_string();
+        let pbo = release.join("addons/Test.pbo");
+
+        laat.create_pbos(&release_path, false).await?;
+        let first = std::fs::read(&pbo)?;
+
+        // Editing only the included header has to repack the addon rather than reuse the cache
+        std::fs::write(dir.path().join("include/shared.hpp"), "#define VALUE 2")?;
+        laat.create_pbos(&release_path, false).await?;
+
+        assert_ne!(std::fs::read(&pbo)?, first);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_pbos_failure() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let release = dir.path().join("release");
+        std::fs::create_dir_all(release.join("addons"))?;
+
+        for (addon, config_cpp) in &[("Good", "class CfgPatches {};"), ("Broken", "class CfgPatches {")] {
+            std::fs::create_dir_all(dir.path().join("build/LAAT").join(addon))?;
+            std::fs::write(dir.path().join("build/LAAT").join(addon).join("config.cpp"), config_cpp)?;
+        }
+
+        let mut config = LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        config.build_path = dir.path().join("build").display().to_string();
+        let laat = LaatCompiler::new(config, vec![])?;
+
+        let err = laat.create_pbos(&release.display().to_string(), false).await.unwrap_err();
+        assert_eq!(err.to_string(), "Failed to pack addons: Broken");
+        assert!(release.join("addons/Good.pbo").is_file());
+
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
 use laat::InitSettings;
 use laat::LaatCompiler;
+use laat::plugins::PreviewFormat;
 use laat::ReleaseSettings;
+use laat::report::{BuildReport, ReportCollector};
 use std::path::PathBuf;
 use structopt::StructOpt;
-use tracing::error;
+use tracing::{error, info};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Debug, StructOpt)]
@@ -11,17 +15,59 @@ struct Opts {
     #[structopt(subcommand)]
     command: Command,
 
-    #[structopt(short = "f", parse(from_os_str), default_value = "LAAT.toml")]
-    /// Point to your LAAT.toml file
+    #[structopt(short = "f", long = "config", parse(from_os_str), default_value = "LAAT.toml")]
+    /// Point to your LAAT.toml (or .json) file, i.e. to switch between dev and release configs.
+    /// `-` reads the config from stdin.
     config_file: PathBuf,
 
+    #[structopt(short = "C", long, parse(from_os_str))]
+    /// Run as if LAAT was started in this folder. Relative paths, including --config, resolve
+    /// against it.
+    project_dir: Option<PathBuf>,
+
     #[structopt(long)]
+    /// Same as -v
     debug: bool,
+
+    #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
+    /// Log more, -v for debug and -vv for trace output
+    verbose: u8,
+
+    #[structopt(short = "q", long, conflicts_with_all = &["verbose", "debug"])]
+    /// Only log warnings and errors
+    quiet: bool,
+
+    #[structopt(long)]
+    /// Ignore the build cache and repack every PBO
+    force: bool,
+
+    #[structopt(short = "j", long)]
+    /// How many tasks (plugins, PBOs, missions, file copies) may run at once, overriding
+    /// `max_parallel_builds`. Defaults to the number of CPU cores.
+    jobs: Option<usize>,
+
+    #[structopt(long)]
+    /// Keep the unpacked addons in the build folder after `ship`, to inspect the generated files
+    keep_build: bool,
+
+    #[structopt(long, parse(from_os_str))]
+    /// Write a JSON report of the plugins, addons, missions, PBOs and warnings of `build` or
+    /// `ship` to this file
+    report: Option<PathBuf>,
+
+    #[structopt(long, number_of_values = 1, use_delimiter = true)]
+    /// Only run these plugins (i.e. `--only missions,music`)
+    only: Vec<String>,
+
+    #[structopt(long, number_of_values = 1, use_delimiter = true)]
+    /// Don't run these plugins
+    skip: Vec<String>,
 }
 
 #[derive(Debug, StructOpt)]
 enum Command {
     /// Create a new LAAT project
+    #[structopt(visible_alias = "new")]
     Init(InitSettings),
     /// Create a new key pair
     Keygen {
@@ -29,11 +75,21 @@ enum Command {
         name: PathBuf,
     },
     /// Clean the build folder
-    Clean {},
+    Clean {
+        /// Plugin to filter too
+        plugin: Option<String>,
+    },
     /// Generate addons
     Build {
         /// Plugin to filter too
         plugin: Option<String>,
+        #[structopt(long)]
+        /// List the files and PBOs that would be generated without writing them
+        dry_run: bool,
+        #[structopt(long, conflicts_with = "dry-run")]
+        /// Compare the generated files with the last build in the build folder instead of
+        /// writing them, listing every added, changed and removed file
+        diff: bool,
     },
     /// Convert addons to PBOs
     Pack {
@@ -46,6 +102,28 @@ enum Command {
     },
     /// Sign your PBOs
     Sign {},
+    /// Show where the missions plugin places the objects of your compositions
+    Preview {
+        #[structopt(long)]
+        /// Map to place the compositions on, defaults to the first of `maps`
+        map: Option<String>,
+        #[structopt(long, default_value = "json", possible_values = &["json", "csv"])]
+        /// Output format
+        format: PreviewFormat,
+        #[structopt(short, long, parse(from_os_str))]
+        /// Write to this file instead of stdout
+        output: Option<PathBuf>,
+    },
+    /// Check LAAT.toml and every plugin's settings without building
+    #[structopt(visible_alias = "check")]
+    Validate {},
+    /// List the plugins and the settings each reads from LAAT.toml
+    #[structopt(visible_aliases = &["list-plugins", "info"])]
+    Plugins {
+        #[structopt(long)]
+        /// Print the JSON schema of every plugin's settings
+        json: bool,
+    },
     /// Publish your mod to the Steam Workshop
     Release(ReleaseSettings),
     /// Runs clean, build, pack, sign, and optionally release
@@ -56,6 +134,25 @@ enum Command {
     },
 }
 
+impl Command {
+    /// Whether the command builds a report to write with --report
+    fn writes_report(&self) -> bool {
+        matches!(self, Command::Build { .. } | Command::Ship { .. })
+    }
+
+    /// Whether the command runs the plugins, so --only and --skip apply to it
+    fn runs_plugins(&self) -> bool {
+        matches!(
+            self,
+            Command::Build { .. }
+                | Command::Clean { .. }
+                | Command::Validate {}
+                | Command::Pack { .. }
+                | Command::Ship { .. }
+        )
+    }
+}
+
 #[tokio::main]
 async fn main() {
     if let Err(why) = run().await {
@@ -77,40 +174,87 @@ fn setup_panic_hook() {
 async fn run() -> laat::Result<()> {
     let opts = Opts::from_args();
 
+    let collector = ReportCollector::default();
+
     setup_panic_hook();
-    init_logging(&opts)?;
+    init_logging(&opts, collector.clone())?;
 
-    run_command(opts).await
+    if let Some(project_dir) = &opts.project_dir {
+        std::env::set_current_dir(project_dir).map_err(|why| {
+            format!("Failed to open project folder {}: {}", project_dir.display(), why)
+        })?;
+    }
+
+    run_command(opts, collector).await
 }
 
-fn init_logging(opts: &Opts) -> laat::Result<()> {
+fn init_logging(opts: &Opts, collector: ReportCollector) -> laat::Result<()> {
     // Set up logging
-    let filter = if opts.debug {
-        EnvFilter::new("laat=debug")
-    } else {
-        EnvFilter::new("laat=info")
+    let verbose = opts.verbose.max(opts.debug as u8);
+
+    let level = match (opts.quiet, verbose) {
+        (true, _) => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
     };
 
-    if let Err(why) = tracing_subscriber::fmt().with_env_filter(filter).try_init() {
+    let filter = EnvFilter::new(format!("laat={}", level));
+
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter).finish().with(collector);
+
+    if let Err(why) = subscriber.try_init() {
         return Err(format!("Failed to set up logger: {}", why).into());
     }
 
     Ok(())
 }
 
-async fn run_command(opts: Opts) -> laat::Result<()> {
-    let laat = if let Command::Init(init) = &opts.command {
+async fn run_command(opts: Opts, collector: ReportCollector) -> laat::Result<()> {
+    if opts.report.is_some() && !opts.command.writes_report() {
+        return Err("--report only applies to build and ship".into());
+    }
+
+    if (!opts.only.is_empty() || !opts.skip.is_empty()) && !opts.command.runs_plugins() {
+        return Err("--only and --skip only apply to build, clean, validate, pack and ship".into());
+    }
+
+    // Doesn't need a project
+    if let Command::Plugins { json } = opts.command {
+        print!("{}", laat::plugins::describe_plugins(json)?);
+        return Ok(());
+    }
+
+    let mut laat = if let Command::Init(init) = &opts.command {
         LaatCompiler::init(init.clone()).await
     } else {
         LaatCompiler::from_path(opts.config_file).await
     }?;
 
+    laat.set_report_collector(collector);
+    laat.set_force(opts.force);
+    laat.set_keep_build(opts.keep_build);
+    if let Some(jobs) = opts.jobs {
+        if jobs == 0 {
+            return Err("--jobs needs to be at least 1".into());
+        }
+
+        laat.set_jobs(jobs);
+    }
+    laat.select_plugins(&opts.only, &opts.skip)?;
+
     match opts.command {
-        Command::Build { plugin } => {
-            laat.build(plugin).await?;
+        Command::Build { plugin, dry_run, diff } => {
+            laat.set_dry_run(dry_run);
+            laat.set_diff(diff);
+            let report = laat.build(plugin).await?;
+            write_report(&opts.report, &report)?;
+        }
+        Command::Validate {} => {
+            laat.validate()?;
         }
-        Command::Clean {} => {
-            laat.clean_build().await?;
+        Command::Clean { plugin } => {
+            laat.clean(plugin).await?;
         }
         Command::Pack { sign, windows } => {
             laat.pack(sign, windows).await?;
@@ -121,15 +265,32 @@ async fn run_command(opts: Opts) -> laat::Result<()> {
         Command::Sign {} => {
             laat.sign().await?;
         }
+        Command::Preview { map, format, output } => {
+            let preview = laat.preview(map.as_deref(), format).await?;
+
+            match output {
+                Some(output) => std::fs::write(output, preview)?,
+                None => print!("{}", preview),
+            }
+        }
         Command::Release(release) => {
             laat.release(release).await?;
         }
         Command::Ship { windows } => {
-            laat.build(None).await?;
-            laat.pack(true, windows).await?;
+            let report = laat.ship(windows).await?;
+            write_report(&opts.report, &report)?;
         }
         _ => {}
     }
     
     Ok(())
 }
+
+fn write_report(path: &Option<PathBuf>, report: &BuildReport) -> laat::Result<()> {
+    if let Some(path) = path {
+        std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+        info!("Wrote build report to {}", path.display());
+    }
+
+    Ok(())
+}
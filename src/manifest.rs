@@ -0,0 +1,94 @@
+//! Checksum manifest for released mods.
+//!
+//! `laat pack` writes a `manifest.json` into the mod folder, listing every PBO with its size and
+//! SHA-256, so server admins can verify a download. Entries are sorted by path and the file has no
+//! timestamps, so manifests of two versions can be diffed to see which PBOs changed.
+
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Name of the manifest in the mod folder
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Mod name
+    pub name: String,
+    pub version: Option<String>,
+    /// Every PBO in the mod folder, sorted by path
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the mod folder, with `/` as the separator (i.e. `addons/LAAT_Music.pbo`)
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: String,
+}
+
+impl Manifest {
+    /// Hash every `.pbo` in the mod folder at `release_path`
+    pub fn from_release(release_path: &Path, name: String, version: Option<String>) -> Result<Self> {
+        let mut files = Vec::new();
+
+        for entry in walkdir::WalkDir::new(release_path) {
+            let entry = entry?;
+
+            let is_pbo = entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("pbo"));
+
+            if !entry.file_type().is_file() || !is_pbo {
+                continue;
+            }
+
+            let contents = std::fs::read(entry.path())?;
+
+            files.push(ManifestEntry {
+                path: entry.path().strip_prefix(release_path)?.to_string_lossy().replace('\\', "/"),
+                size: contents.len() as u64,
+                sha256: format!("{:x}", Sha256::digest(&contents)),
+            });
+        }
+
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Self { name, version, files })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("addons"))?;
+        std::fs::write(dir.path().join("addons/b.pbo"), "abc")?;
+        std::fs::write(dir.path().join("addons/a.PBO"), "")?;
+        std::fs::write(dir.path().join("addons/b.pbo.LAAT.bisign"), "sig")?;
+        std::fs::write(dir.path().join("mod.cpp"), "name = \"LAAT\";")?;
+
+        let manifest = Manifest::from_release(dir.path(), "LAAT".to_string(), Some("1.0.0".to_string()))?;
+
+        let paths = manifest.files.iter().map(|file| file.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["addons/a.PBO", "addons/b.pbo"]);
+        assert_eq!(
+            manifest.files[1],
+            ManifestEntry {
+                path: "addons/b.pbo".to_string(),
+                size: 3,
+                sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            }
+        );
+
+        Ok(())
+    }
+}
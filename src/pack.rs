@@ -0,0 +1,309 @@
+//! PBO packing helpers used by `laat pack`.
+
+use crate::Result;
+use armake2::pbo::PBO;
+use glob::{MatchOptions, Pattern};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the gitignore-style ignore files, in the project root or an addon folder
+pub const IGNORE_FILE: &str = ".laatignore";
+
+/// Exclude patterns from `[pack] excludes` and `.laatignore` files.
+///
+/// Patterns are globs matched against each file's path relative to the addon root, using `/` as
+/// the separator. Patterns without a `/` (i.e. `*.psd` or `.git`) match any single component of
+/// the path, so they apply at every depth. Patterns with a `/` match the whole relative path,
+/// where `*` stays within a folder and `**` spans folders (i.e. `**/*.psd`, `**/.git/**`,
+/// `data/*.tga`).
+///
+/// Ignore files follow gitignore rules, relative to the addon root, and are checked after
+/// `excludes`. `!pattern` re-includes a file left out by `excludes` or an earlier ignore file,
+/// and `pattern/` only matches folders.
+#[derive(Debug, Clone)]
+pub struct Excludes {
+    patterns: Vec<Pattern>,
+    ignore_files: Vec<PathBuf>,
+    ignore: Option<Gitignore>,
+}
+
+impl Excludes {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .map_err(|why| format!("Invalid exclude pattern {:?}: {}", pattern, why).into())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            patterns,
+            ignore_files: Vec::new(),
+            ignore: None,
+        })
+    }
+
+    /// Also apply the ignore file at `path`, if there is one. Its rules take precedence over the
+    /// ones already added.
+    pub fn with_ignore_file(&self, path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(self.clone());
+        }
+
+        let mut ignore_files = self.ignore_files.clone();
+        ignore_files.push(path.to_owned());
+
+        let mut builder = GitignoreBuilder::new("");
+        for file in &ignore_files {
+            if let Some(why) = builder.add(file) {
+                return Err(format!("Invalid ignore file {}: {}", file.display(), why).into());
+            }
+        }
+
+        let ignore = builder
+            .build()
+            .map_err(|why| format!("Invalid ignore file {}: {}", path.display(), why))?;
+
+        Ok(Self {
+            patterns: self.patterns.clone(),
+            ignore_files,
+            ignore: Some(ignore),
+        })
+    }
+
+    /// Returns true if the file at `relative` (relative to the addon root) should not be packed
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        if relative.file_name().is_some_and(|name| name == IGNORE_FILE) {
+            return true;
+        }
+
+        if let Some(ignore) = &self.ignore {
+            let matched = ignore.matched_path_or_any_parents(relative, false);
+
+            if matched.is_ignore() {
+                return true;
+            } else if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        let components = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        let path = components.join("/");
+
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+
+        self.patterns.iter().any(|pattern| {
+            if pattern.as_str().contains('/') {
+                pattern.matches_with(&path, options)
+            } else {
+                components
+                    .iter()
+                    .any(|component| pattern.matches_with(component, options))
+            }
+        })
+    }
+}
+
+/// How files are stored in packed PBOs, from `[pack] compression`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    /// Store files as they are
+    None,
+    /// LZSS compress every file that gets smaller by it
+    Lzss,
+}
+
+impl Compression {
+    pub fn from_config(compression: &Option<String>) -> Result<Self> {
+        match compression.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("none") => Ok(Compression::None),
+            Some("lzss") => Ok(Compression::Lzss),
+            Some(other) => Err(format!(
+                "Unknown PBO compression {:?}, expected \"none\" or \"lzss\"",
+                other
+            )
+            .into()),
+        }
+    }
+}
+
+/// What went into a packed PBO
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PboSummary {
+    /// Number of files in the PBO
+    pub files: usize,
+    /// Total size of the files before compression, in bytes
+    pub data_size: u64,
+}
+
+/// Pack an addon folder into a PBO, binarizing configs and skipping excluded files.
+pub fn build_pbo<O: Write>(
+    input: PathBuf,
+    output: &mut O,
+    header_extensions: &[String],
+    excludes: &Excludes,
+    include_folders: &[PathBuf],
+    compression: Compression,
+) -> Result<PboSummary> {
+    let excludes = excludes.with_ignore_file(&input.join(IGNORE_FILE))?;
+
+    let mut pbo = PBO::from_directory_filtered(
+        input,
+        true,
+        |relative| {
+            let excluded = excludes.is_excluded(relative);
+            if excluded {
+                trace!("Excluding {}", relative.display());
+            }
+
+            !excluded
+        },
+        include_folders,
+    )?;
+
+    for extension in header_extensions {
+        let (key, value) = extension
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid header extension {:?}, expected key=value", extension))?;
+        pbo.header_extensions.insert(key.trim().to_string(), value.trim().to_string());
+    }
+
+    match compression {
+        Compression::None => pbo.write(output)?,
+        Compression::Lzss => pbo.write_compressed(output)?,
+    }
+
+    Ok(PboSummary {
+        files: pbo.files.len(),
+        data_size: pbo.files.values().map(|file| file.get_ref().len() as u64).sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excludes(patterns: &[&str]) -> Excludes {
+        Excludes::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_excludes_nested() {
+        let excludes = excludes(&["**/*.psd", "**/.git/**", "data/*.tga"]);
+
+        assert!(excludes.is_excluded(Path::new("logo.psd")));
+        assert!(excludes.is_excluded(Path::new("data/textures/logo.psd")));
+        assert!(excludes.is_excluded(Path::new(".git/config")));
+        assert!(excludes.is_excluded(Path::new("data/.git/objects/ab/cdef")));
+        assert!(excludes.is_excluded(Path::new("data/logo.tga")));
+
+        assert!(!excludes.is_excluded(Path::new("data/textures/logo.tga")));
+        assert!(!excludes.is_excluded(Path::new("data/textures/logo.paa")));
+        assert!(!excludes.is_excluded(Path::new("config.cpp")));
+    }
+
+    #[test]
+    fn test_excludes_by_name() {
+        let excludes = excludes(&["*.png", ".git"]);
+
+        assert!(excludes.is_excluded(Path::new("icon.png")));
+        assert!(excludes.is_excluded(Path::new("data/ui/icon.png")));
+        assert!(excludes.is_excluded(Path::new("data/.git/HEAD")));
+
+        assert!(!excludes.is_excluded(Path::new("data/ui/icon.paa")));
+        assert!(!excludes.is_excluded(Path::new("data/.gitignore")));
+    }
+
+    #[test]
+    fn test_ignore_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("project"), "# Sources\n*.psd\n!keep.psd\nsource/\n")?;
+        std::fs::write(dir.path().join("addon"), "!logo.tga\nkeep.psd\n")?;
+
+        let project = excludes(&["*.tga"]).with_ignore_file(&dir.path().join("project"));
+        let addon = project
+            .as_ref()
+            .map_err(|why| why.to_string())
+            .and_then(|project| project.with_ignore_file(&dir.path().join("addon")).map_err(|why| why.to_string()));
+        let (project, addon) = (project?, addon?);
+
+        assert!(project.is_excluded(Path::new("data/logo.psd")));
+        assert!(project.is_excluded(Path::new("data/source/notes.txt")));
+        assert!(project.is_excluded(Path::new("data/logo.tga")));
+        assert!(project.is_excluded(Path::new(IGNORE_FILE)));
+        assert!(!project.is_excluded(Path::new("data/keep.psd")));
+        assert!(!project.is_excluded(Path::new("data/source.paa")));
+
+        // Later ignore files win
+        assert!(addon.is_excluded(Path::new("data/keep.psd")));
+        assert!(!addon.is_excluded(Path::new("data/logo.tga")));
+        assert!(addon.is_excluded(Path::new("data/other.tga")));
+
+        // Missing ignore files are skipped
+        assert!(!excludes(&[]).with_ignore_file(Path::new("missing/.laatignore"))?.is_excluded(Path::new("a.psd")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_pbo_excludes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("data/source"))?;
+        std::fs::write(dir.path().join("data/logo.paa"), b"paa")?;
+        std::fs::write(dir.path().join("data/source/logo.psd"), b"psd")?;
+        std::fs::write(dir.path().join("readme.txt"), b"txt")?;
+
+        let mut output = Vec::new();
+        let result = build_pbo(
+            dir.path().to_path_buf(),
+            &mut output,
+            &["prefix=LAAT\\Test".to_string()],
+            &excludes(&["**/source/**"]),
+            &[],
+            Compression::None,
+        );
+
+        assert_eq!(result?, PboSummary { files: 2, data_size: 6 });
+
+        let pbo = PBO::read(&mut std::io::Cursor::new(output))?;
+        let mut files = pbo.files.keys().cloned().collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(files, vec!["data\\logo.paa", "readme.txt"]);
+        assert_eq!(pbo.header_extensions.get("prefix").unwrap(), "LAAT\\Test");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_pbo_compressed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let text = "class CfgPatches { class LAAT_Test { units[] = {}; }; };\n".repeat(50);
+        std::fs::write(dir.path().join("readme.txt"), &text)?;
+        std::fs::write(dir.path().join("a.bin"), [7])?;
+
+        let mut output = Vec::new();
+        build_pbo(dir.path().to_path_buf(), &mut output, &[], &excludes(&[]), &[], Compression::Lzss)?;
+
+        let pbo = PBO::read(&mut std::io::Cursor::new(output))?;
+        let stored = pbo.files.get("readme.txt").unwrap().get_ref();
+
+        assert!(stored.len() < text.len());
+        assert_eq!(armake2::lzss::decompress(stored, text.len())?, text.as_bytes());
+        // Too small to gain anything, so stored as is
+        assert_eq!(&pbo.files.get("a.bin").unwrap().get_ref()[..], &[7]);
+
+        assert!(Compression::from_config(&Some("zip".to_string())).is_err());
+        assert_eq!(Compression::from_config(&None)?, Compression::None);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
-use tokio::task::JoinHandle;
-use std::path::PathBuf;
-use std::{io, fs};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::fs;
+use crate::diff;
+use crate::report::AddonReport;
 use crate::BuildContext;
 use crate::Plugin;
 use crate::Result;
@@ -23,31 +25,79 @@ impl Plugin for AddonPlugin {
 pub async fn copy_addons(
     build_context: BuildContext,
 ) -> Result<()> {
-    copy_dir_all(build_context.addons_path.into(), format!("{}/{}", build_context.build_path, build_context.prefix).into())?;
+    if build_context.dry_run {
+        for entry in fs::read_dir(&build_context.addons_path)?.flatten() {
+            if entry.file_type()?.is_dir() {
+                info!("Would copy {} > {}/{}", entry.path().display(), build_context.prefix, entry.file_name().to_string_lossy());
+                info!("Would create {}.pbo", entry.file_name().to_string_lossy());
+            }
+        }
+
+        return Ok(());
+    }
+
+    if build_context.diff {
+        return diff_addons(&build_context);
+    }
+
+    copy_dir_all(build_context.addons_path.clone().into(), build_context.addon_build_path().join(&build_context.prefix))?;
 
     Ok(())
 }
 
 
+/// Compare every addon in `addons_path` with its copy from the last build
+fn diff_addons(build_context: &BuildContext) -> Result<()> {
+    let build_path = build_context.addon_build_path();
+
+    for entry in fs::read_dir(&build_context.addons_path)?.flatten() {
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let folder = Path::new(&build_context.prefix).join(entry.file_name());
+        let files = diff::digest_folder(Path::new(&build_context.addons_path), Path::new(&entry.file_name()))?
+            .into_iter()
+            .map(|(path, digest)| (Path::new(&build_context.prefix).join(path), digest))
+            .collect::<BTreeMap<_, _>>();
+
+        let changes = diff::diff_folder(&build_path, &folder, &files)?;
+        diff::log_changes(&folder, &changes, files.len());
+
+        let size = walkdir::WalkDir::new(entry.path())
+            .into_iter()
+            .flatten()
+            .filter_map(|file| file.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        build_context.report.add_addon(AddonReport {
+            path: folder.to_string_lossy().replace('\\', "/"),
+            files: files.len(),
+            size,
+        });
+        build_context.report.add_changes(changes);
+    }
+
+    Ok(())
+}
+
 #[instrument(err)]
 fn copy_dir_all(src: PathBuf, dst: PathBuf) -> tokio::io::Result<()> {
     debug!("Creating dir: {:?}", dst);
 
     fs::create_dir_all(&dst)?;
 
-    let mut dir = fs::read_dir(src)?;
-
-    while let Some(entry) = dir.next() {
-            if let Ok(entry) = entry {
-                // debug!("Copying: {:?}", entry);
-                let ty = entry.file_type()?;
-                if ty.is_dir() {
-                    copy_dir_all(entry.path(), dst.join(entry.file_name()))?;
-                } else {
-                    fs::copy(entry.path(), dst.join(entry.file_name()))?;
-                    debug!("Copied {:?}!", entry);
-                }
-            }
+    for entry in fs::read_dir(src)?.flatten() {
+        // debug!("Copying: {:?}", entry);
+        let ty = entry.file_type()?;
+        if ty.is_dir() {
+            copy_dir_all(entry.path(), dst.join(entry.file_name()))?;
+        } else {
+            fs::copy(entry.path(), dst.join(entry.file_name()))?;
+            debug!("Copied {:?}!", entry);
+        }
     }
 
     Ok(())
@@ -0,0 +1,450 @@
+//! Compiler Plugin for CBA settings, from the `[cba_settings]` section of LAAT.toml.
+//!
+//! Builds a `Settings` addon whose `XEH_preInit.sqf` registers every setting with
+//! `CBA_fnc_addSetting`, so they show up under the mod's category in the Addon Options. A
+//! `userconfig/cba_settings.sqf` with every default is added to the addon as a skeleton for server
+//! admins.
+
+use crate::context::AddonManager;
+use crate::{create_project_handlebars, render_template};
+use crate::{BuildContext, LaatError, Plugin, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const CBA_SETTINGS_KEY: &str = "cba_settings";
+
+#[derive(Debug)]
+pub struct CbaSettingsPlugin;
+
+#[async_trait]
+impl Plugin for CbaSettingsPlugin {
+    #[instrument(err, skip(build_context))]
+    async fn build(&self, build_context: BuildContext) -> Result<()> {
+        let settings = CbaSettings::from_build_config(&build_context)?;
+        let addon = SettingsAddon::new(&settings, &build_context)?;
+
+        let handlebars = create_project_handlebars(&build_context)?;
+        let mut manager = AddonManager::from_context(settings.addon_name.clone(), build_context.clone());
+
+        let config_cpp = render_template(&handlebars, "cba_settings_addon", &addon)?;
+        manager.add_config(config_cpp, "config.cpp".into())?;
+
+        let init = render_template(&handlebars, "cba_settings_init", &addon)?;
+        manager.add_file(init, "XEH_preInit.sqf".into())?;
+
+        let userconfig = render_template(&handlebars, "cba_settings_userconfig", &addon)?;
+        manager.add_file(userconfig, "userconfig/cba_settings.sqf".into())?;
+
+        info!("Building Addon {} with {} CBA settings...", settings.addon_name, addon.settings.len());
+        manager.build_addon().await
+    }
+
+    async fn clean(&self, build_context: BuildContext) -> Result<()> {
+        let settings = CbaSettings::from_build_config(&build_context)?;
+
+        AddonManager::from_context(settings.addon_name, build_context)
+            .clean_addon()
+            .await
+    }
+
+    fn name(&self) -> String {
+        "cba_settings".to_string()
+    }
+
+    fn settings_schema(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(schemars::schema_for!(CbaSettings)).ok()
+    }
+
+    fn validate(&self, build_context: &BuildContext) -> Vec<LaatError> {
+        CbaSettings::from_build_config(build_context).err().into_iter().collect()
+    }
+}
+
+fn default_addon_name() -> String {
+    "Settings".to_string()
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+struct CbaSettings {
+    #[serde(default = "default_addon_name")]
+    /// Name of the generated addon
+    addon_name: String,
+
+    #[serde(default)]
+    /// Category the settings are listed under in the Addon Options, defaults to the mod's name
+    category: Option<String>,
+
+    /// The settings to register
+    settings: Vec<CbaSetting>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+struct CbaSetting {
+    /// Variable holding the setting's value, i.e. `LAAT_respawnTime`
+    name: String,
+
+    #[serde(rename = "type")]
+    /// How the setting is edited: CHECKBOX, EDITBOX, LIST, SLIDER, COLOR or TIME
+    kind: SettingType,
+
+    #[serde(default)]
+    /// Shown in the Addon Options, defaults to `name`
+    title: Option<String>,
+
+    #[serde(default)]
+    tooltip: Option<String>,
+
+    #[serde(default)]
+    /// Group of settings within the category
+    subcategory: Option<String>,
+
+    /// A boolean for CHECKBOX, text for EDITBOX, one of `values` for LIST, a number for SLIDER and
+    /// TIME (in seconds), and [r, g, b] or [r, g, b, a] from 0 to 1 for COLOR
+    #[schemars(with = "serde_json::Value")]
+    default: toml::Value,
+
+    #[serde(default)]
+    /// Lowest value of a SLIDER or TIME
+    min: Option<f64>,
+
+    #[serde(default)]
+    /// Highest value of a SLIDER or TIME
+    max: Option<f64>,
+
+    #[serde(default)]
+    /// Decimals shown for a SLIDER
+    decimals: u32,
+
+    #[serde(default)]
+    /// Values a LIST chooses from
+    #[schemars(with = "Vec<serde_json::Value>")]
+    values: Vec<toml::Value>,
+
+    #[serde(default)]
+    /// Shown for the `values` of a LIST, defaults to the values themselves
+    labels: Vec<String>,
+
+    #[serde(default)]
+    /// Who sets the value: "client" (each player, unless the server forces it), "global" (the
+    /// server, for everyone) or "local" (each player, the server can't force it)
+    scope: SettingScope,
+
+    #[serde(default)]
+    /// Changes only apply after restarting the mission
+    need_restart: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+enum SettingType {
+    Checkbox,
+    Editbox,
+    List,
+    Slider,
+    Color,
+    Time,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum SettingScope {
+    #[default]
+    Client,
+    Global,
+    Local,
+}
+
+impl CbaSettings {
+    /// Load and check the `[cba_settings]` section
+    fn from_build_config(build_config: &BuildContext) -> Result<Self> {
+        let settings: CbaSettings = build_config.plugin_settings(CBA_SETTINGS_KEY)?;
+
+        if settings.addon_name.trim().is_empty() || settings.addon_name.contains(['/', '\\']) {
+            return Err(invalid_setting(
+                "addon_name",
+                format!("Invalid addon name {:?}: it can't be empty or contain slashes", settings.addon_name),
+            ));
+        }
+
+        let mut names: Vec<String> = Vec::new();
+
+        for setting in &settings.settings {
+            let is_identifier = setting.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && setting.name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+            if !is_identifier {
+                return Err(invalid_setting(
+                    "settings",
+                    format!("Invalid CBA setting name {:?}, expected letters, digits and _", setting.name),
+                ));
+            }
+
+            // SQF variables are case-insensitive
+            if names.iter().any(|name| name.eq_ignore_ascii_case(&setting.name)) {
+                return Err(invalid_setting(
+                    "settings",
+                    format!("CBA setting {} is defined more than once", setting.name),
+                ));
+            }
+            names.push(setting.name.clone());
+
+            setting.value_info()?;
+        }
+
+        Ok(settings)
+    }
+}
+
+impl CbaSetting {
+    /// The `valueInfo` argument of `CBA_fnc_addSetting` and the default as written in
+    /// `cba_settings.sqf`, both in SQF
+    fn value_info(&self) -> Result<(String, String)> {
+        let invalid = |reason: &str| {
+            invalid_setting(
+                "settings",
+                format!("CBA setting {} ({:?}): {}", self.name, self.kind, reason),
+            )
+        };
+
+        match self.kind {
+            SettingType::Checkbox => match &self.default {
+                toml::Value::Boolean(default) => Ok((default.to_string(), default.to_string())),
+                _ => Err(invalid("the default has to be true or false")),
+            },
+            SettingType::Editbox => match &self.default {
+                toml::Value::String(default) => Ok((sqf_string(default), sqf_string(default))),
+                _ => Err(invalid("the default has to be text")),
+            },
+            SettingType::Slider | SettingType::Time => {
+                let default = number(&self.default).ok_or_else(|| invalid("the default has to be a number"))?;
+                let (min, max) = match (self.min, self.max) {
+                    (Some(min), Some(max)) if min <= default && default <= max => (min, max),
+                    (Some(_), Some(_)) => return Err(invalid("the default has to be between min and max")),
+                    _ => return Err(invalid("min and max are required")),
+                };
+
+                let value_info = if self.kind == SettingType::Slider {
+                    format!("[{}, {}, {}, {}]", min, max, default, self.decimals)
+                } else {
+                    format!("[{}, {}, {}]", min, max, default)
+                };
+
+                Ok((value_info, default.to_string()))
+            }
+            SettingType::List => {
+                let values = self
+                    .values
+                    .iter()
+                    .map(sqf_value)
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|values| !values.is_empty())
+                    .ok_or_else(|| invalid("values has to list at least one number, text or boolean"))?;
+
+                let labels = if self.labels.is_empty() {
+                    self.values
+                        .iter()
+                        .map(|value| match value {
+                            toml::Value::String(value) => sqf_string(value),
+                            value => sqf_string(&value.to_string()),
+                        })
+                        .collect::<Vec<_>>()
+                } else if self.labels.len() == values.len() {
+                    self.labels.iter().map(|label| sqf_string(label)).collect()
+                } else {
+                    return Err(invalid("labels has to have one label per value"));
+                };
+
+                let index = self
+                    .values
+                    .iter()
+                    .position(|value| *value == self.default)
+                    .ok_or_else(|| invalid("the default has to be one of the values"))?;
+
+                Ok((
+                    format!("[[{}], [{}], {}]", values.join(", "), labels.join(", "), index),
+                    values[index].clone(),
+                ))
+            }
+            SettingType::Color => {
+                let color = self
+                    .default
+                    .as_array()
+                    .filter(|color| color.len() == 3 || color.len() == 4)
+                    .and_then(|color| color.iter().map(number).collect::<Option<Vec<_>>>())
+                    .filter(|color| color.iter().all(|channel| (0.0..=1.0).contains(channel)))
+                    .ok_or_else(|| invalid("the default has to be [r, g, b] or [r, g, b, a] from 0 to 1"))?;
+
+                let color = format!(
+                    "[{}]",
+                    color.iter().map(f64::to_string).collect::<Vec<_>>().join(", ")
+                );
+
+                Ok((color.clone(), color))
+            }
+        }
+    }
+}
+
+/// Everything the `cba_settings_*` templates render
+#[derive(Serialize)]
+struct SettingsAddon {
+    prefix: String,
+    version: Option<String>,
+    /// Mod name
+    name: String,
+    addon_name: String,
+    /// Path of `XEH_preInit.sqf` in the game, i.e. `\LAAT\Settings\XEH_preInit.sqf`
+    init_script: String,
+    settings: Vec<SettingEntry>,
+}
+
+/// A setting with every value already in SQF
+#[derive(Serialize)]
+struct SettingEntry {
+    name: String,
+    kind: SettingType,
+    title: String,
+    tooltip: String,
+    category: String,
+    subcategory: String,
+    value_info: String,
+    value: String,
+    scope: u8,
+    need_restart: bool,
+}
+
+impl SettingsAddon {
+    fn new(settings: &CbaSettings, build_config: &BuildContext) -> Result<Self> {
+        let category = settings.category.as_deref().unwrap_or(&build_config.name);
+
+        let entries = settings
+            .settings
+            .iter()
+            .map(|setting| {
+                let (value_info, value) = setting.value_info()?;
+
+                Ok(SettingEntry {
+                    name: setting.name.clone(),
+                    kind: setting.kind,
+                    title: sqf_string(setting.title.as_deref().unwrap_or(&setting.name)),
+                    tooltip: sqf_string(setting.tooltip.as_deref().unwrap_or_default()),
+                    category: sqf_string(category),
+                    subcategory: sqf_string(setting.subcategory.as_deref().unwrap_or_default()),
+                    value_info,
+                    value,
+                    scope: match setting.scope {
+                        SettingScope::Client => 0,
+                        SettingScope::Global => 1,
+                        SettingScope::Local => 2,
+                    },
+                    need_restart: setting.need_restart,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            prefix: build_config.prefix.clone(),
+            version: build_config.version.clone(),
+            name: build_config.name.clone(),
+            addon_name: settings.addon_name.clone(),
+            init_script: format!(r"\{}\{}\XEH_preInit.sqf", build_config.prefix, settings.addon_name),
+            settings: entries,
+        })
+    }
+}
+
+/// A quoted SQF string, with quotes doubled
+fn sqf_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// A number, text or boolean (or array of them) in SQF
+fn sqf_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Float(value) => Some(value.to_string()),
+        toml::Value::String(value) => Some(sqf_string(value)),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(sqf_value)
+            .collect::<Option<Vec<_>>>()
+            .map(|values| format!("[{}]", values.join(", "))),
+        _ => None,
+    }
+}
+
+fn number(value: &toml::Value) -> Option<f64> {
+    match value {
+        toml::Value::Integer(value) => Some(*value as f64),
+        toml::Value::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn invalid_setting(name: &str, reason: String) -> LaatError {
+    LaatError::InvalidConfig {
+        key: format!("{}.{}", CBA_SETTINGS_KEY, name),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cba_settings() -> Result<()> {
+        let mut config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+
+        let mut load = |settings: &str| {
+            config.set_plugin_settings(CBA_SETTINGS_KEY, toml::from_str::<toml::Value>(settings)?)?;
+            CbaSettings::from_build_config(&config).and_then(|settings| SettingsAddon::new(&settings, &config))
+        };
+
+        let addon = load(
+            r#"
+            [[settings]]
+            name = "LAAT_respawnTime"
+            type = "SLIDER"
+            title = "Respawn \"time\""
+            default = 30
+            min = 0
+            max = 300
+            scope = "global"
+
+            [[settings]]
+            name = "LAAT_side"
+            type = "LIST"
+            default = "west"
+            values = ["west", "east"]
+            labels = ["BLUFOR", "OPFOR"]
+            subcategory = "Spawning"
+            "#,
+        )?;
+        let duplicate = load("settings = [{ name = 'a', type = 'CHECKBOX', default = true }, { name = 'A', type = 'CHECKBOX', default = false }]");
+        let out_of_range = load("settings = [{ name = 'a', type = 'SLIDER', default = 5, min = 10, max = 20 }]");
+        let not_listed = load("settings = [{ name = 'a', type = 'LIST', default = 3, values = [1, 2] }]");
+
+        let handlebars = create_project_handlebars(&config)?;
+        let init = render_template(&handlebars, "cba_settings_init", &addon)?;
+        let init = init.split_whitespace().collect::<Vec<_>>().join(" ");
+        let userconfig = render_template(&handlebars, "cba_settings_userconfig", &addon)?;
+        let config_cpp = render_template(&handlebars, "cba_settings_addon", &addon)?;
+
+        assert_eq!(addon.init_script, r"\LAAT\Settings\XEH_preInit.sqf");
+        assert!(init.contains(
+            r#"[ "LAAT_respawnTime", "SLIDER", ["Respawn ""time""", ""], ["LAAT Test Mod", ""], [0, 300, 30, 0], 1, {}, false ] call CBA_fnc_addSetting;"#
+        ));
+        assert!(init.contains(r#"["LAAT Test Mod", "Spawning"], [["west", "east"], ["BLUFOR", "OPFOR"], 0], 0,"#));
+        assert!(userconfig.contains("LAAT_respawnTime = 30;\nLAAT_side = \"west\";\n"));
+        // The name the PBO is packed under, from its addon folder
+        assert!(config_cpp.contains(r#"fileName = "Settings.pbo";"#), "{}", config_cpp);
+
+        assert!(matches!(duplicate, Err(LaatError::InvalidConfig { reason, .. }) if reason.contains("more than once")));
+        assert!(matches!(out_of_range, Err(LaatError::InvalidConfig { reason, .. }) if reason.contains("between min and max")));
+        assert!(matches!(not_listed, Err(LaatError::InvalidConfig { reason, .. }) if reason.contains("one of the values")));
+
+        Ok(())
+    }
+}
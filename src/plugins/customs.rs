@@ -15,7 +15,7 @@ pub struct CustomsPlugin;
 impl Plugin for CustomsPlugin {
     async fn build(&self, build_config: BuildContext) -> Result<()> {
 
-        let mut manager = AddonManager::from_context(ADDON_NAME.to_string(), build_config);
+        let _manager = AddonManager::from_context(ADDON_NAME.to_string(), build_config);
 
 
         Ok(())
@@ -2,9 +2,11 @@
 //!
 
 use std::collections::HashMap;
+use std::path::Path;
 use crate::context::AddonManager;
 use super::{Plugin, BuildContext};
-use crate::Result;
+use crate::{LaatError, Result};
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 use tokio::io::AsyncReadExt;
 
@@ -68,7 +70,7 @@ impl Plugin for KitPlugin {
 
         debug!("Kit File: {:?}", kit_file);
 
-        let mut manager = AddonManager::from_context(ADDON_NAME.to_string(), build_config);
+        let _manager = AddonManager::from_context(ADDON_NAME.to_string(), build_config);
 
         // Create kits from components
         //
@@ -85,15 +87,55 @@ impl Plugin for KitPlugin {
     fn name(&self) -> String {
         "kits".to_string()
     }
+
+    fn settings_schema(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(schemars::schema_for!(KitSettings)).ok()
+    }
+
+    fn validate(&self, build_config: &BuildContext) -> Vec<LaatError> {
+        let settings: KitSettings = match build_config.plugin_settings_or_default(KITS_SETTINGS_KEY) {
+            Ok(settings) => settings,
+            Err(why) => return vec![why],
+        };
+
+        if Path::new(&settings.file).is_file() {
+            Vec::new()
+        } else {
+            vec![LaatError::InvalidConfig {
+                key: format!("{}.file", KITS_SETTINGS_KEY),
+                reason: format!("Kit file {} doesn't exist", settings.file),
+            }]
+        }
+    }
 }
 
-const FILE_FIELD: &str = "kits.file";
+const KITS_SETTINGS_KEY: &str = "kits";
 const DEFAULT_FILE: &str = "kits.toml";
 
+/// Settings from the [kits] section of LAAT.toml
+#[derive(Debug, Deserialize, JsonSchema)]
+struct KitSettings {
+    #[serde(default = "default_kit_file")]
+    /// TOML file listing the kits
+    file: String,
+}
+
+impl Default for KitSettings {
+    fn default() -> Self {
+        Self {
+            file: default_kit_file(),
+        }
+    }
+}
+
+fn default_kit_file() -> String {
+    DEFAULT_FILE.to_string()
+}
+
 async fn load_kit_config(build_config: &BuildContext) -> Result<KitFile> {
-    let file_path = build_config.extra.get(FILE_FIELD).map(|v| v.as_str()).flatten().unwrap_or(DEFAULT_FILE);
+    let settings: KitSettings = build_config.plugin_settings_or_default(KITS_SETTINGS_KEY)?;
 
-    let mut kit_file = tokio::fs::File::open(file_path).await?;
+    let mut kit_file = tokio::fs::File::open(settings.file).await?;
     let mut contents = String::new();
     kit_file.read_to_string(&mut contents).await?;
 
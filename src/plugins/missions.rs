@@ -1,20 +1,67 @@
 use std::path::Path;
+use std::io::Cursor;
 use std::io::Read;
 use armake2::config::ConfigArray;
 use crate::context::AddonManager;
-use crate::create_handlebars;
+use crate::create_project_handlebars;
+use crate::render_template;
+use crate::sanitize_classname;
+use crate::report::MissionReport;
+use crate::retry::retry_io;
+use crate::Authors;
 use crate::BuildContext;
 use crate::Plugin;
-use crate::Result;
+use crate::{LaatError, Result};
 use armake2::config::{Config, ConfigArrayElement, ConfigClass, ConfigEntry};
-use std::collections::HashMap;
+use futures_util::future::join_all;
+use glob::{MatchOptions, Pattern};
+use handlebars::Handlebars;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use std::f32::consts::TAU;
 use std::path::PathBuf;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 const MISSION_SETTINGS_KEY: &str = "missions";
+/// Optional placement file in a composition folder
+const TRANSFORM_FILE: &str = "transform.toml";
+
+/// Magic bytes at the start of a rapified config
+const RAP_MAGIC: &[u8] = b"\0raP";
 const CBA_SETTINGS: &str = "cba_settings_hasSettingsFile = 1;";
 
+/// World class names shipped with Arma 3 and its DLCs/CDLCs. Modded terrains can be allowed
+/// with `extra_maps`.
+const KNOWN_MAPS: &[&str] = &[
+    // Arma 3 & DLC
+    "Altis",
+    "Stratis",
+    "Tanoa",
+    "Malden",
+    "Enoch",
+    "VR",
+    // Creator DLC
+    "gm_weferlingen_summer",
+    "gm_weferlingen_winter",
+    "Cam_Lao_Nam",
+    "vn_khe_sanh",
+    "vn_the_bra",
+    "SefrouRamal",
+    "Stozec",
+    "SPE_Normandy",
+    "SPE_Mortain",
+];
+
+/// Largest world size of the Arma 3 terrains, in meters. Horizontal positions outside of
+/// `0..=MAX_WORLD_SIZE` are off the map on every terrain.
+const MAX_WORLD_SIZE: f32 = 30720.;
+
+/// Respawn types, in the order of their numeric SQM value
+const RESPAWN_TYPES: &[&str] = &["NONE", "BIRD", "INSTANT", "BASE", "GROUP", "SIDE"];
+
 const ON_PLAYER_DEATH: &str = "player setVariable [\"Saved_Loadout\",getUnitLoadout player];";
 const ON_PLAYER_RESPAWN: &str = "player setUnitLoadout (player getVariable [\"Saved_Loadout\",[]]);";
 
@@ -28,74 +75,71 @@ impl Plugin for MissionPlugin {
         // Extract MissionSettings from BuildContext
         let mission_settings = MissionSettings::from_build_config(&build_config)?;
 
-        // Load composition file
-        let composition = load_composition(
-            &mission_settings.composition,
-            mission_settings.composition_offset,
-            mission_settings.ignore_center
-        )
-        .await?;
+        // Load composition files
+        let compositions = load_compositions(&mission_settings, &build_config).await?;
 
         // For each Map create mission based on settings.
         let mut missions = create_missions(&mission_settings, &build_config).await?;
+        check_class_names(&missions, &build_config.prefix)?;
+
+        // Merge compositions into mission
+        for mission in missions.iter_mut() {
+            if let Err(why) = mission.merge_compositions(&compositions) {
+                if mission_settings.strict {
+                    return Err(why);
+                }
 
-        // Merge composition into mission
-        missions.iter_mut().for_each(|mission| {
-            if let Err(why) = mission.merge_composition(&composition) {
                 warn!("Failed to merge composition: {}", why);
             }
-        });
-
-        // Save mission to addon
-        let mut addon_manager =
-            AddonManager::from_context(&mission_settings.addon_name, build_config.clone());
-
-        let classes = missions
-            .into_iter()
-            .filter_map(|mission| {
-                let path: PathBuf =
-                    format!("missions/{}/mission.sqm", mission.mission_name()).into();
-
-                let sqm = match mission.to_sqm() {
-                    Ok(sqm) => sqm,
-                    Err(err) => {
-                        warn!("Error creating sqm: {}", err);
-                        return None;
-                    }
-                };
-
-                addon_manager.add_file(sqm, path.clone());
+        }
 
+        // The picture is copied into every mission folder next to the scripts
+        let mut scripts = mission_settings.scripts()?;
+        scripts.extend(mission_settings.picture()?);
 
-                // CBA settings
-                if let Some(cba_settings_path) = &mission_settings.cba_settings_file  {
-                    if let Err(why) = add_cba_settings(&cba_settings_path, &mut addon_manager, &mission) {
-                        error!("Failed to add CBA Settings ({:?}) to addon: {}", &cba_settings_path, why);
-                        return None;
+        // Catch compositions placed off the map
+        for mission in missions.iter() {
+            for composition in compositions.iter().filter(|composition| composition.includes_map(&mission.map_name)) {
+                if let Err(why) = composition.check_placement(&mission.map_name, mission.offset_override) {
+                    if mission_settings.strict {
+                        return Err(why);
                     }
-                }
 
-                // Keep inventory on spawn
-                if mission_settings.respawn_keep_inventory {
-                    keep_inventory_on_respawn(&mut addon_manager, &mission);
+                    warn!("{}", why);
                 }
+            }
+        }
 
-                Some((path, mission))
-            })
-            .collect::<Vec<_>>();
-
-        // Write config exposing Missions
-        info!("Writing config.cpp...");
-        let handlebars = create_handlebars()?;
+        // Group missions by the addon they go into
+        let mut addons: BTreeMap<String, Vec<Mission>> = BTreeMap::new();
+        for mission in missions {
+            addons
+                .entry(mission_settings.addon_name_for(&mission.map_name))
+                .or_default()
+                .push(mission);
+        }
 
-        let addon = Addon::from_parts(build_config.prefix, mission_settings.addon_name, classes);
-        let config_cpp = handlebars.render("missions_addon", &addon)?;
+        let total = addons.values().map(Vec::len).sum();
+        let mut built = 0;
+        for (addon_name, missions) in addons {
+            let count = missions.len();
+            build_missions_addon(&addon_name, missions, (built, total), &scripts, &mission_settings, &build_config)
+                .await?;
+            built += count;
+        }
+        build_config.report_progress(total, total, format!("Built {} missions", total));
 
-        addon_manager.add_file(config_cpp, "config.cpp".into());
+        Ok(())
+    }
 
+    async fn clean(&self, build_config: BuildContext) -> Result<()> {
+        let mission_settings = MissionSettings::from_build_config(&build_config)?;
 
-        info!("Building Addon...");
-        addon_manager.build_addon().await?;
+        for addon_name in mission_settings.addon_names() {
+            AddonManager::from_context(addon_name, build_config.clone())
+                .clean_addon()
+                .await?;
+        }
 
         Ok(())
     }
@@ -103,464 +147,4097 @@ impl Plugin for MissionPlugin {
     fn name(&self) -> String {
         "missions".to_string()
     }
+
+    fn settings_schema(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(schemars::schema_for!(MissionSettings)).ok()
+    }
+
+    fn validate(&self, build_config: &BuildContext) -> Vec<LaatError> {
+        match MissionSettings::from_build_config(build_config) {
+            Ok(mission_settings) => mission_settings.missing_files(),
+            Err(why) => vec![why],
+        }
+    }
+}
+
+/// Write `missions`, each with a copy of `scripts`, and a config.cpp exposing them into the addon
+/// `addon_name`, then build it. `progress` is how many of the plugin's missions were built before
+/// these, and how many there are in total.
+async fn build_missions_addon(
+    addon_name: &str,
+    missions: Vec<Mission>,
+    progress: (usize, usize),
+    scripts: &[(PathBuf, Vec<u8>)],
+    mission_settings: &MissionSettings,
+    build_config: &BuildContext,
+) -> Result<()> {
+    let mut addon_manager = AddonManager::from_context(addon_name, build_config.clone());
+
+    let mut classes = Vec::new();
+
+    for (idx, mission) in missions.into_iter().enumerate() {
+        let (built, total) = progress;
+        build_config.report_progress(
+            built + idx,
+            total,
+            format!("Building mission {} of {}: {}", built + idx + 1, total, mission.class_name()),
+        );
+
+        addon_manager.set_source(format!("mission {}", mission.class_name()));
+
+        let mission_folder = mission_settings.mission_folder(&mission);
+        let path = mission_folder.join("mission.sqm");
+
+        let sqm = if mission_settings.binarize_sqm {
+            mission.to_binarized_sqm()
+        } else {
+            mission.to_sqm().map(String::into_bytes)
+        };
+
+        let sqm = match sqm {
+            Ok(sqm) => sqm,
+            Err(err) if mission_settings.strict => return Err(err),
+            Err(err) => {
+                warn!("Error creating sqm: {}", err);
+                continue;
+            }
+        };
+
+        addon_manager.add_bytes(sqm, path.clone())?;
+
+        for (script, buffer) in scripts {
+            addon_manager.add_bytes(buffer.clone(), mission_folder.join(script))?;
+        }
+
+        // CBA settings
+        if let Some(cba_settings_path) = &mission_settings.cba_settings_file {
+            if let Err(why) = add_cba_settings(cba_settings_path, &mut addon_manager, &mission_folder) {
+                error!("Failed to add CBA Settings ({:?}) to addon: {}", &cba_settings_path, why);
+                continue;
+            }
+        }
+
+        // Keep inventory on spawn
+        if mission_settings.respawn_keep_inventory {
+            keep_inventory_on_respawn(&mut addon_manager, &mission_folder)?;
+        }
+
+        classes.push((path, mission));
+    }
+
+    for (path, mission) in &classes {
+        build_config.report.add_mission(MissionReport {
+            map: mission.map_name.clone(),
+            class: mission.class_name(),
+            addon: addon_name.to_string(),
+            path: path.parent().unwrap_or(path).to_string_lossy().replace('\\', "/"),
+        });
+    }
+
+    // Write config exposing Missions
+    info!("Writing config.cpp for {}...", addon_name);
+    let handlebars = create_project_handlebars(build_config)?;
+
+    let attributes = mission_settings.mission_attributes()?;
+    let addon = Addon::from_parts(
+        build_config.prefix.clone(),
+        build_config.version.clone(),
+        addon_name.to_string(),
+        &mission_settings.missions_subfolder,
+        attributes,
+        classes,
+    )?;
+    let config_cpp = render_template(&handlebars, "missions_addon", &addon)?;
+    addon_manager.set_source(format!("the config.cpp of {}", addon_name));
+
+    addon_manager.add_config(config_cpp, "config.cpp".into())?;
+
+    info!("Building Addon {}...", addon_name);
+    addon_manager.build_addon().await
 }
 
-#[instrument(err, skip(addon_manager, mission))]
-fn add_cba_settings(cba_settings_path: &Path, addon_manager: &mut AddonManager, mission: &Mission) -> Result<()> {
-    addon_manager.add_file(CBA_SETTINGS.to_string(), "description.ext".into());
+#[instrument(err, skip(addon_manager))]
+fn add_cba_settings(cba_settings_path: &Path, addon_manager: &mut AddonManager, mission_folder: &Path) -> Result<()> {
+    addon_manager.add_file(CBA_SETTINGS.to_string(), "description.ext".into())?;
 
     let mut cba_settings_string = String::new();
     let mut settings_file = std::fs::File::open(cba_settings_path)?;
     settings_file.read_to_string(&mut cba_settings_string)?;
 
-    addon_manager.add_file(cba_settings_string, format!("missions/{}/cba_settings.sqf", mission.mission_name()).into());
-    addon_manager.add_file(CBA_SETTINGS.to_string(), format!("missions/{}/description.ext", mission.mission_name()).into());
+    addon_manager.add_file(cba_settings_string, mission_folder.join("cba_settings.sqf"))?;
+    addon_manager.add_file(CBA_SETTINGS.to_string(), mission_folder.join("description.ext"))?;
 
     Ok(())
 }
 
-fn keep_inventory_on_respawn(addon_manager: &mut AddonManager, mission: &Mission) {
-    addon_manager.add_file(ON_PLAYER_RESPAWN.to_string(), format!("missions/{}/onPlayerRespawn.sqf", mission.mission_name()).into());
-    addon_manager.add_file(ON_PLAYER_DEATH.to_string(), format!("missions/{}/onPlayerKilled.sqf", mission.mission_name()).into());
+fn keep_inventory_on_respawn(addon_manager: &mut AddonManager, mission_folder: &Path) -> Result<()> {
+    addon_manager.add_file(ON_PLAYER_RESPAWN.to_string(), mission_folder.join("onPlayerRespawn.sqf"))?;
+    addon_manager.add_file(ON_PLAYER_DEATH.to_string(), mission_folder.join("onPlayerKilled.sqf"))?;
+
+    Ok(())
 }
 
-async fn add_mission_files(addon_manager: &mut AddonManager, mission_files_path: &PathBuf) -> Result<()> {
+/// A composition placed relative to the mission's composition offset
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+struct CompositionEntry {
+    path: PathBuf,
 
-    Ok(())
+    #[serde(default)]
+    /// X, Y, Z offset added on top of `composition_offset` (or the per-map offset). Defaults to
+    /// the composition's `transform.toml`.
+    offset: Option<(f32, f32, f32)>,
+
+    #[serde(default)]
+    /// Only place this composition on these maps. Placed on every map if unset here and in the
+    /// composition's `transform.toml`.
+    maps: Option<Vec<String>>,
+}
+
+impl CompositionEntry {
+    fn includes_map(&self, map_name: &str) -> bool {
+        self.maps
+            .as_ref()
+            .is_none_or(|maps| maps.iter().any(|map| map.eq_ignore_ascii_case(map_name)))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum CompositionConfig {
+    Single(PathBuf),
+    Multiple(Vec<CompositionEntry>),
+}
+
+impl CompositionConfig {
+    fn entries(&self) -> Vec<CompositionEntry> {
+        match self {
+            CompositionConfig::Single(path) => vec![CompositionEntry {
+                path: path.clone(),
+                offset: None,
+                maps: None,
+            }],
+            CompositionConfig::Multiple(entries) => entries.clone(),
+        }
+    }
+}
+
+/// Default placement shipped with a composition, from `transform.toml` in its folder. Anything
+/// set for the composition in LAAT.toml takes precedence.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CompositionTransform {
+    /// Like a `composition` entry's `offset`
+    offset: Option<(f32, f32, f32)>,
+    /// Like `composition_rotation`
+    rotation: Option<f32>,
+    /// Like `composition_scale`
+    scale: Option<f32>,
+    /// Like a `composition` entry's `maps`
+    maps: Option<Vec<String>>,
+}
+
+impl CompositionTransform {
+    /// Read the `transform.toml` of the composition at `path`, if it has one
+    async fn from_composition(path: &Path, io_retries: usize) -> Result<Self> {
+        let transform_path = path.join(TRANSFORM_FILE);
+
+        if !transform_path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let description = format!("read {}", transform_path.display());
+        let source = retry_io(io_retries, &description, || tokio::fs::read_to_string(&transform_path)).await?;
+
+        toml::from_str(&source).map_err(|why| LaatError::CompositionParse {
+            path: transform_path,
+            reason: why.to_string(),
+        })
+    }
+}
+
+/// Stamp every composition in a grid of `rows` by `cols` copies
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema)]
+struct RepeatConfig {
+    rows: usize,
+    cols: usize,
+    /// Distance, in meters, between copies. Columns go east and rows go north.
+    spacing: f32,
+}
+
+impl RepeatConfig {
+    /// X, Z, Y offset of every copy, starting with the original placement
+    fn offsets(&self) -> Vec<(f32, f32, f32)> {
+        (0..self.rows)
+            .flat_map(|row| {
+                (0..self.cols).map(move |col| (col as f32 * self.spacing, 0., row as f32 * self.spacing))
+            })
+            .collect()
+    }
+}
+
+/// Playable units to add to every mission, as one group per entry
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+struct SlotConfig {
+    /// WEST, EAST, INDEPENDENT (or GUER) or CIVILIAN
+    side: String,
+    /// Number of playable units in the group
+    count: usize,
+    /// Vehicle class of the units, i.e. "B_Soldier_F"
+    unit_class: String,
+
+    #[serde(default)]
+    /// X, Y, Z offset from where the first composition is placed
+    offset: (f32, f32, f32),
+}
+
+/// Sides, as `(accepted names, SQM value)`
+const SIDES: &[(&[&str], &str)] = &[
+    (&["WEST", "BLUFOR"], "West"),
+    (&["EAST", "OPFOR"], "East"),
+    (&["INDEPENDENT", "GUER", "RESISTANCE"], "Independent"),
+    (&["CIVILIAN", "CIV"], "Civilian"),
+];
+
+/// The SQM value for `side`, from the `[missions]` setting `setting`
+fn sqm_side(side: &str, setting: &str) -> Result<&'static str> {
+    SIDES
+        .iter()
+        .find(|(names, _)| names.iter().any(|name| name.eq_ignore_ascii_case(side)))
+        .map(|(_, side)| *side)
+        .ok_or_else(|| {
+            invalid_setting(
+                setting,
+                format!(
+                    "Unknown side in {}: {}. Expected one of: WEST, EAST, INDEPENDENT or CIVILIAN",
+                    setting, side
+                ),
+            )
+        })
+}
+
+impl SlotConfig {
+    /// The SQM value for `side`
+    fn sqm_side(&self) -> Result<&'static str> {
+        sqm_side(&self.side, "slots")
+    }
+}
+
+/// A respawn marker to add to every mission, used by the BASE respawn type
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+struct RespawnPositionConfig {
+    /// Side respawning at the marker, like for slots
+    side: String,
+
+    #[serde(default)]
+    /// Marker name, starting with the side's respawn marker (i.e. `respawn_west_hq`). Defaults to
+    /// the side's respawn marker, numbered when a side has several.
+    name: Option<String>,
+
+    #[serde(default)]
+    /// X, Y, Z offset from where the first composition is placed
+    offset: (f32, f32, f32),
+}
+
+/// Respawn marker name and marker color of each SQM side. Independent respawn markers are
+/// spelled "guerrila" in Arma.
+fn respawn_marker(side: &str) -> (&'static str, &'static str) {
+    match side {
+        "West" => ("respawn_west", "ColorWEST"),
+        "East" => ("respawn_east", "ColorEAST"),
+        "Independent" => ("respawn_guerrila", "ColorGUER"),
+        _ => ("respawn_civilian", "ColorCIV"),
+    }
 }
 
 type MapEntry = String;
 type MapOffsetEntry = (String, (f32, f32, f32));
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 #[serde(untagged)]
 enum MapConfig {
     Map(MapEntry),
     MapOffset(MapOffsetEntry)
 }
 
-#[derive(Debug, Deserialize)]
+impl MapConfig {
+    fn map_name(&self) -> &str {
+        match self {
+            MapConfig::Map(map_name) => map_name,
+            MapConfig::MapOffset((map_name, _)) => map_name,
+        }
+    }
+
+    /// The per-map offset, replacing `composition_offset`
+    fn offset_override(&self) -> Option<(f32, f32, f32)> {
+        match self {
+            MapConfig::Map(_) => None,
+            MapConfig::MapOffset((_, offset)) => Some(*offset),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 struct MissionSettings {
     #[serde(default = "default_addon_name")]
-    /// Name of the generated Addon
+    /// Name of the generated Addon. `{{map}}` is replaced with the map name, i.e.
+    /// `Missions_{{map}}` builds one addon per map.
     addon_name: String,
 
+    #[serde(default)]
+    /// Addon name for specific maps, overriding `addon_name`. Maps sharing a name share an addon.
+    map_addons: BTreeMap<String, String>,
+
+    #[serde(default)]
+    /// Category class the missions are nested under in CfgMissions, shown as a folder in the
+    /// scenario list. `{{map}}` is replaced with the map name, i.e. `{{map}}` groups them by map.
+    /// Without one, missions are listed directly in `MPMissions`.
+    category: Option<String>,
+
+    #[serde(default)]
+    /// Category for specific maps, overriding `category`
+    map_categories: BTreeMap<String, String>,
+
     /// List of maps to create missions for
     maps: Vec<MapConfig>,
 
+    #[serde(default)]
+    /// World class names of modded terrains to accept in `maps`
+    extra_maps: Vec<String>,
+
     /// Mission name
     #[serde(default = "default_mission_name")]
     mission_name: String,
 
-    /// Delay, in seconds between death and when allowed to respawn.
+    /// Delay, in seconds between death and when allowed to respawn. Fractions are allowed, 0
+    /// respawns instantly.
     #[serde(default = "default_respawn_delay")]
-    respawn_delay: usize,
+    respawn_delay: f32,
+
+    /// Respawn type, one of NONE, BIRD, INSTANT, BASE, GROUP or SIDE.
+    #[serde(default = "default_respawn_type")]
+    respawn_type: String,
 
     #[serde(default)]
     /// Keep inventory on respawn or not.
     respawn_keep_inventory: bool, 
 
-    /// Composition to add to missions
-    composition: PathBuf,
+    #[serde(default)]
+    /// Composition(s) to add to missions. Without one, missions only get the slots and respawn
+    /// markers.
+    composition: Option<CompositionConfig>,
 
     #[serde(default)]
     /// X, Y, Z offset for the composition.
     composition_offset: (f32, f32, f32),
 
+    #[serde(default)]
+    /// Rotation, in degrees, around the vertical axis for the composition. Defaults to the
+    /// composition's `transform.toml`, or 0.
+    composition_rotation: Option<f32>,
+
+    #[serde(default)]
+    /// Multiplier for the horizontal distance of each object from the composition's center.
+    /// Defaults to the composition's `transform.toml`, or 1.
+    composition_scale: Option<f32>,
+
     ignore_center: bool,
 
-    missions_folder: PathBuf,
-    cba_settings_file: Option<PathBuf>
-}
+    #[serde(default)]
+    /// Place a grid of copies of the compositions instead of a single one
+    repeat: Option<RepeatConfig>,
 
-impl MissionSettings {
-    pub fn from_build_config(build_config: &BuildContext) -> Result<MissionSettings> {
-        if let Some(mission_settings) = build_config.extra.get(MISSION_SETTINGS_KEY) {
-            let mission_settings: MissionSettings = mission_settings.clone().try_into()?;
+    #[serde(default)]
+    /// Hour the mission starts at, from 0 to 24 (i.e. 21.5 for 21:30)
+    time_of_day: Option<f32>,
 
-            Ok(mission_settings)
-        } else {
-            Err(format!(
-                "Failed to get field: {} from LAAT.toml",
-                MISSION_SETTINGS_KEY
-            )
-            .into())
-        }
-    }
-}
+    #[serde(default)]
+    /// Fog, from 0 to 1
+    fog: Option<f32>,
 
-fn default_addon_name() -> String {
-    "Missions".to_string()
-}
+    #[serde(default)]
+    /// Overcast, from 0 to 1
+    overcast: Option<f32>,
 
-fn default_mission_name() -> String {
-    "ZeusMission".to_string()
-}
+    #[serde(default)]
+    /// Rain, from 0 to 1
+    rain: Option<f32>,
 
-fn default_respawn_delay() -> usize {
-    2
-}
+    #[serde(default)]
+    /// Groups of playable units to add next to the compositions
+    slots: Vec<SlotConfig>,
 
-struct Composition {
-    header: Config,
-    composition: Config,
-    offset: (f32, f32, f32),
-    ignore_center: bool
-}
+    #[serde(default)]
+    /// Respawn markers to add next to the compositions
+    respawn_positions: Vec<RespawnPositionConfig>,
 
-impl Composition {
-    #[instrument(err)]
-    pub async fn from_path(path: &PathBuf, offset: (f32, f32, f32), ignore_center: bool) -> Result<Self> {
-        let (header, composition) = tokio::join!(
-            tokio::fs::File::open(format!("{}/header.sqe", path.display())),
-            tokio::fs::File::open(format!("{}/composition.sqe", path.display()))
-        );
+    #[serde(default)]
+    /// Addons (CfgPatches classes) every mission requires, i.e. the mods its compositions use
+    required_addons: Vec<String>,
 
-        let header = Config::read(&mut header?.into_std().await, None, &Vec::new())?;
-        let composition = Config::read(&mut composition?.into_std().await, None, &Vec::new())?;
+    #[serde(default)]
+    /// Addon each object class comes from, i.e. `{ rhsusf_m1a1tusksa_usarmy = "rhsusf_main" }`.
+    /// Missions with objects of these classes require the addon.
+    class_addons: BTreeMap<String, String>,
 
-        Ok(Composition {
-            header,
-            composition,
-            offset,
-            ignore_center
-        })
-    }
+    #[serde(default)]
+    /// Only merge composition objects of these classes, as globs matched against their type
+    /// (i.e. `Land_*`). Empty keeps every object.
+    include_classes: Vec<String>,
 
-    pub fn set_offset(&mut self, offset: (f32, f32, f32)) {
-        self.offset = offset;
-    }
+    #[serde(default)]
+    /// Leave composition objects of these classes out of the missions, as globs matched against
+    /// their type (i.e. `B_MRAP_01_*`)
+    exclude_classes: Vec<String>,
 
-    /// Get "center[]" from SQE, cast it into a tuple
-    pub fn get_center(&self) -> Result<(f32, f32, f32)> {
-        let config = self.composition.inner();
+    #[serde(default)]
+    /// Extra entries for every mission's CfgMissions class, i.e. `gameType = "Coop"`
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    mission_attributes: BTreeMap<String, toml::Value>,
 
-        if let Some(entries) = config.entries.clone() {
-            let map: HashMap<String, ConfigEntry> = entries.into_iter().collect();
+    #[serde(default)]
+    /// Extra entries for every mission.sqm's ScenarioData class, i.e. `joinUnassigned = 1`.
+    /// They replace generated entries of the same name, like `disabledAI`.
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    scenario_attributes: BTreeMap<String, toml::Value>,
 
-            if let Some(ConfigEntry::ArrayEntry(array)) = map.get("center") {
-                debug!("Center Array: {:?}", array);
+    #[serde(default)]
+    /// Text shown under the mission in the scenario list
+    overview_text: Option<String>,
 
-                let center = get_center_from_field(&array);
+    #[serde(default)]
+    /// Text shown on the loading screen
+    loading_text: Option<String>,
 
-                if !self.ignore_center {
-                    return Ok(center);
-                }
-                else {
-                    return Ok((0., 0., 0.));
-                }
-            };
-        }
+    #[serde(default)]
+    /// Image (`.paa` or `.jpg`) for the scenario list and the loading screen, copied into every
+    /// mission's folder
+    picture: Option<PathBuf>,
 
-        Err("Failed to get center[]".into())
-    }
+    #[serde(default)]
+    /// Write mission.sqm rapified instead of as text.
+    binarize_sqm: bool,
 
-    pub fn get_offset(&self) -> Result<(f32, f32, f32)> {
-        let (x1, y1, z1) = self.get_center()?;
-        let (x2, y2, z2) = self.offset;
+    #[serde(default)]
+    /// Fail the build when a map or composition can't be turned into a mission, instead of
+    /// skipping it with a warning.
+    strict: bool,
 
-        Ok((x1 + x2, y1 + y2, z1 + z2))
-    }
+    #[allow(dead_code)]
+    /// Folder the project's missions live in. Not read by the build yet.
+    missions_folder: PathBuf,
 
-    /// Get and offset items from the SQE
-    pub fn get_offseted_items(&self, offset_override: Option<(f32, f32, f32)>) -> Result<EntryList> {
-        let offset = if let Some(offset_override) = offset_override {
-            info!("Overriding offset...");
-            offset_override
-        }
-        else {
-            self.get_offset()?
-        };
+    #[serde(default = "default_missions_subfolder")]
+    /// Folder in the addon the missions are written to (i.e. `missions/<mission>/mission.sqm`),
+    /// and CfgMissions points at. May be nested, i.e. `data/missions`.
+    missions_subfolder: String,
 
-        let config = self.composition.inner();
+    /// CBA settings (`cba_settings.sqf`) to copy into every mission
+    cba_settings_file: Option<PathBuf>,
 
-        if let Some(entries) = config.entries.clone() {
-            let map: HashMap<String, ConfigEntry> = entries.into_iter().collect();
+    #[serde(default)]
+    /// Folder whose contents (i.e. `init.sqf` and a `scripts` folder) are copied into every
+    /// mission's folder, next to its mission.sqm
+    scripts_dir: Option<PathBuf>,
+}
 
-            if let Some(ConfigEntry::ClassEntry(items)) = map.get("items") {
-                if let Some(entries) = items.entries.clone() {
-                    debug!("Item Classes: {}", entries.len());
-                    return Ok(offset_classes(entries, offset));
-                }
-            };
+impl MissionSettings {
+    pub fn from_build_config(build_config: &BuildContext) -> Result<MissionSettings> {
+        let mission_settings: MissionSettings = build_config.plugin_settings(MISSION_SETTINGS_KEY)?;
+        mission_settings.validate_maps()?;
+        mission_settings.respawn()?;
+
+        if !(0. ..).contains(&mission_settings.respawn_delay) {
+            return Err(invalid_setting(
+                "respawn_delay",
+                format!(
+                    "Invalid respawn_delay in the [{}] section: {} is negative",
+                    MISSION_SETTINGS_KEY, mission_settings.respawn_delay
+                ),
+            ));
         }
 
-        Err("Failed to get offseted items".into())
-    }
-}
+        for entry in mission_settings.compositions() {
+            let unused = entry
+                .maps
+                .iter()
+                .flatten()
+                .filter(|map| !mission_settings.maps.iter().any(|built| built.map_name().eq_ignore_ascii_case(map)))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
 
-fn get_center_from_field(array: &ConfigArray) -> (f32, f32, f32) {
-    let map_elem = |x: &ConfigArrayElement| {
-        match x {
-            ConfigArrayElement::FloatElement(x) => *x,
-            ConfigArrayElement::IntElement(x) => *x as f32,
-            _ => 0.
+            if !unused.is_empty() {
+                warn!("Composition {} lists maps that aren't built: {}", entry.path.display(), unused.join(", "));
+            }
         }
-    };
 
-    let x = array.elements.get(0).map(map_elem).unwrap_or_default();
-    let y = array.elements.get(1).map(map_elem).unwrap_or_default();
-    let z = array.elements.get(2).map(map_elem).unwrap_or_default();
+        mission_settings.validate_environment()?;
 
-    (x, y ,z)
-}
+        if let Some(repeat) = mission_settings.repeat {
+            if repeat.rows == 0 || repeat.cols == 0 {
+                return Err(invalid_setting(
+                    "repeat",
+                    format!("repeat in the [{}] section needs at least one row and column", MISSION_SETTINGS_KEY),
+                ));
+            }
+        }
 
-type EntryList = Vec<(String, ConfigEntry)>;
+        for slot in &mission_settings.slots {
+            slot.sqm_side()?;
+        }
 
-/// Offset classes recursively
-#[instrument(skip(entries, composition_offset))]
-fn offset_classes(entries: EntryList, composition_offset: (f32, f32, f32)) -> EntryList {
-    let offsets = [
-        composition_offset.0,
-        composition_offset.1,
-        composition_offset.2,
-    ];
+        respawn_markers(&mission_settings.respawn_positions, (0., 0., 0.), 0)?;
+        if !mission_settings.respawn_positions.is_empty() && mission_settings.respawn()? != 3 {
+            warn!(
+                "respawn_positions are only used with the BASE respawn_type, not {}",
+                mission_settings.respawn_type
+            );
+        }
 
-    entries
-        .into_iter()
-        .map(|(name, entry)| {
-            let entry = if let ConfigEntry::ClassEntry(mut class) = entry {
-                    // Offset
-                    class.entries = class.entries.map(|mut entries| {
-                        entries.iter_mut().find(|(name, _)| name == "position").map(
-                            |(name, entry)| {
-                                if let ConfigEntry::ArrayEntry(position) = entry {
-                                    position.elements = position
-                                        .elements
-                                        .iter_mut()
-                                        .enumerate()
-                                        .map(|(idx, el)| add_to_element(el.clone(), offsets[idx]))
-                                        .collect();
-
-                                    (name, entry)
-                                } else {
-                                    (name, entry)
-                                }
-                            },
-                        );
+        mission_settings.mission_attributes()?;
+        mission_settings.scenario_attributes()?;
+        mission_settings.validate_addon_names()?;
+        mission_settings.validate_categories()?;
+        mission_settings.validate_picture()?;
+        mission_settings.validate_missions_subfolder()?;
+        mission_settings.validate_required_addons()?;
+        mission_settings.class_filter()?;
 
-                        entries
-                    });
+        Ok(mission_settings)
+    }
 
-                    // Recurse
-                    class.entries = class
-                        .entries
-                        .map(|entries| offset_classes(entries, composition_offset));
+    /// Every `composition` entry, if there are any
+    fn compositions(&self) -> Vec<CompositionEntry> {
+        self.composition.as_ref().map(CompositionConfig::entries).unwrap_or_default()
+    }
 
-                ConfigEntry::ClassEntry(class)
-            } else {
-                entry
-            };
+    /// `include_classes` and `exclude_classes`
+    fn class_filter(&self) -> Result<ClassFilter> {
+        let patterns = |name: &str, classes: &[String]| {
+            classes
+                .iter()
+                .map(|class| {
+                    Pattern::new(class).map_err(|why| {
+                        invalid_setting(name, format!("Invalid class pattern {:?} in {}: {}", class, name, why))
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        };
 
-            (name, entry)
+        Ok(ClassFilter {
+            include: patterns("include_classes", &self.include_classes)?,
+            exclude: patterns("exclude_classes", &self.exclude_classes)?,
         })
-        .collect()
-}
-
-fn add_to_element(element: ConfigArrayElement, increment: f32) -> ConfigArrayElement {
-    match element {
-        ConfigArrayElement::StringElement(_) => {}
-        ConfigArrayElement::FloatElement(float) => {
-            return ConfigArrayElement::FloatElement(float + increment);
-        }
-        ConfigArrayElement::IntElement(int) => {
-            return ConfigArrayElement::FloatElement(int as f32 + increment);
-        }
-        ConfigArrayElement::ArrayElement(_) => {}
     }
 
-    element
-}
+    /// Problems with the compositions, CBA settings and scripts these settings point at
+    fn missing_files(&self) -> Vec<LaatError> {
+        let mut problems = Vec::new();
 
-#[instrument(err)]
-async fn load_composition(
-    composition_path: &PathBuf,
-    composition_offset: (f32, f32, f32),
-    ignore_center: bool
-) -> Result<Composition> {
-    info!("Loading composition at: {:?}", composition_path);
-    Ok(Composition::from_path(composition_path, composition_offset, ignore_center).await?)
-}
+        for entry in self.compositions() {
+            if composition_url(&entry.path).is_none() && !entry.path.exists() {
+                problems.push(invalid_setting(
+                    "composition",
+                    format!("Composition {} doesn't exist", entry.path.display()),
+                ));
+            }
+        }
 
-#[instrument(err)]
-async fn create_missions(
-    mission_settings: &MissionSettings,
-    build_config: &BuildContext,
-) -> Result<Vec<Mission>> {
-    info!("Creating missions...");
-    Ok(mission_settings
-        .maps
-        .iter()
-        .filter_map(|map| {
-            Mission::new(
-                build_config.prefix.clone(),
-                mission_settings.mission_name.clone(),
-                map.clone(),
-                &mission_settings,
-                &build_config,
-            )
-            .ok()
-        })
-        .collect())
-}
+        if let Some(cba_settings_file) = &self.cba_settings_file {
+            if !cba_settings_file.is_file() {
+                problems.push(invalid_setting(
+                    "cba_settings_file",
+                    format!("cba_settings_file {} is not a file", cba_settings_file.display()),
+                ));
+            }
+        }
 
-struct Mission {
-    map_name: String,
-    mission_name: String,
-    prefix: String,
-    offset_override: Option<(f32, f32, f32)>,
+        if let Err(why) = self.scripts() {
+            problems.push(why);
+        }
 
-    sqm: Config,
-}
+        problems
+    }
 
-impl Mission {
-    #[instrument(skip(mission_settings), err)]
-    pub fn new(
-        prefix: String,
-        mission_name: String,
-        map: MapConfig,
-        mission_settings: &MissionSettings,
-        build_config: &BuildContext,
-    ) -> Result<Self> {
-        let handlebars = create_handlebars()?;
+    /// Every file in `scripts_dir`, with its path relative to it
+    fn scripts(&self) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+        let scripts_dir = match &self.scripts_dir {
+            Some(scripts_dir) => scripts_dir,
+            None => return Ok(Vec::new()),
+        };
+
+        if !scripts_dir.is_dir() {
+            return Err(invalid_setting(
+                "scripts_dir",
+                format!("scripts_dir {} in the [{}] section is not a folder", scripts_dir.display(), MISSION_SETTINGS_KEY),
+            ));
+        }
+
+        let mut scripts = Vec::new();
 
-        let (map_name, offset_override) = match map {
-            MapConfig::Map(map_name) => (map_name, None),
-            MapConfig::MapOffset((map_name, offset)) => (map_name, Some(offset)),
+        for entry in walkdir::WalkDir::new(scripts_dir).sort_by_file_name() {
+            let entry = entry?;
+
+            if entry.file_type().is_file() {
+                let relative = entry.path().strip_prefix(scripts_dir)?.to_owned();
+                scripts.push((relative, std::fs::read(entry.path())?));
+            }
+        }
+
+        debug!("Adding {} files from {} to every mission", scripts.len(), scripts_dir.display());
+
+        Ok(scripts)
+    }
+
+    /// Check that `picture` is an image Arma can show
+    fn validate_picture(&self) -> Result<()> {
+        let picture = match &self.picture {
+            Some(picture) => picture,
+            None => return Ok(()),
         };
 
-        #[derive(Serialize)]
-        struct MissionTemplate {
-            author: String,
-            respawn_delay: usize,
-            mission_name: String,
+        let is_image = picture
+            .extension()
+            .is_some_and(|extension| ["paa", "jpg"].iter().any(|image| extension.eq_ignore_ascii_case(image)));
+
+        if !is_image {
+            return Err(invalid_setting(
+                "picture",
+                format!("picture {} in the [{}] section is not a .paa or .jpg", picture.display(), MISSION_SETTINGS_KEY),
+            ));
         }
 
-        let template = MissionTemplate {
-            author: build_config
-                .extra
-                .get("author")
-                .map(|v| v.to_string())
-                .unwrap_or_default(),
-            mission_name: mission_name.clone(),
-            respawn_delay: mission_settings.respawn_delay,
+        if !picture.is_file() {
+            return Err(invalid_setting(
+                "picture",
+                format!("picture {} in the [{}] section is not a file", picture.display(), MISSION_SETTINGS_KEY),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// File name and contents of `picture`, to copy into every mission's folder
+    fn picture(&self) -> Result<Option<(PathBuf, Vec<u8>)>> {
+        let picture = match &self.picture {
+            Some(picture) => picture,
+            None => return Ok(None),
         };
 
-        let sqm = handlebars.render("mission.sqm", &template)?;
+        let file_name = picture
+            .file_name()
+            .ok_or_else(|| format!("Failed to get file name for picture: {:?}", picture))?;
 
-        let config = Config::read(&mut sqm.as_bytes(), None, &Vec::new())?;
+        Ok(Some((file_name.into(), std::fs::read(picture)?)))
+    }
 
-        Ok(Mission {
-            map_name,
-            offset_override,
-            mission_name,
-            prefix,
-            sqm: config,
-        })
+    /// Folder of `mission` in its addon
+    fn mission_folder(&self, mission: &Mission) -> PathBuf {
+        Path::new(&self.missions_subfolder).join(mission.mission_name())
     }
 
-    #[instrument(skip(self, composition))]
-    pub fn merge_composition(&mut self, composition: &Composition) -> Result<()> {
-        let items = composition.get_offseted_items(self.offset_override)?;
+    /// Check that `required_addons` and `class_addons` only list CfgPatches class names
+    fn validate_required_addons(&self) -> Result<()> {
+        for addon in self.required_addons.iter().chain(self.class_addons.values()) {
+            let is_class_name = !addon.is_empty() && addon.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+            if !is_class_name {
+                return Err(invalid_setting(
+                    "required_addons",
+                    format!("Invalid addon {:?} in the [{}] section, expected a CfgPatches class name", addon, MISSION_SETTINGS_KEY),
+                ));
+            }
+        }
 
-        let mut class = self.sqm.inner_mut();
+        Ok(())
+    }
 
-        // Mission.Entities = items
+    /// Check that `missions_subfolder` is a relative path within the addon
+    fn validate_missions_subfolder(&self) -> Result<()> {
+        let subfolder = Path::new(&self.missions_subfolder);
 
-        class.entries = class.entries.clone().map(|entries| {
-            entries.into_iter().map(|(name, config)| {
-                if name == "Mission" {
-                    if let ConfigEntry::ClassEntry(mut mission) = config {
-                        let parent = mission.parent.clone();
+        let is_valid = !self.missions_subfolder.trim().is_empty()
+            && !self.missions_subfolder.contains('\\')
+            && subfolder
+                .components()
+                .all(|component| matches!(component, std::path::Component::Normal(_)));
 
-                        mission.entries = mission.entries.map(|entries| {
-                            let mut map: HashMap<String, ConfigEntry> = entries.into_iter().collect();
-                            let entities = ConfigEntry::ClassEntry(ConfigClass {
-                                parent,
-                                is_external: false,
-                                is_deletion: false,
-                                entries: Some(items.clone())
-                            });
+        if !is_valid {
+            return Err(invalid_setting(
+                "missions_subfolder",
+                format!(
+                    "Invalid missions_subfolder {:?} in the [{}] section: expected a relative path inside the addon using /, i.e. \"missions\"",
+                    self.missions_subfolder, MISSION_SETTINGS_KEY
+                ),
+            ));
+        }
 
-                            map.insert("Entities".to_string(), entities);
+        Ok(())
+    }
 
-                            map.into_iter().collect()
-                        });
+    /// The addon the mission for `map_name` goes into
+    fn addon_name_for(&self, map_name: &str) -> String {
+        self.map_addons
+            .iter()
+            .find(|(map, _)| map.eq_ignore_ascii_case(map_name))
+            .map(|(_, addon_name)| addon_name)
+            .unwrap_or(&self.addon_name)
+            .replace("{{map}}", map_name)
+    }
 
-                        return (name, ConfigEntry::ClassEntry(mission));
-                    }
+    /// The CfgMissions category the mission for `map_name` goes into, if any
+    fn category_for(&self, map_name: &str) -> Option<String> {
+        self.map_categories
+            .iter()
+            .find(|(map, _)| map.eq_ignore_ascii_case(map_name))
+            .map(|(_, category)| category)
+            .or(self.category.as_ref())
+            .map(|category| category.replace("{{map}}", map_name))
+    }
+
+    /// Check that categories can be written as a `briefingName`, and that `map_categories` only
+    /// lists built maps
+    fn validate_categories(&self) -> Result<()> {
+        for map in &self.maps {
+            if let Some(category) = self.category_for(map.map_name()) {
+                if category.trim().is_empty() || category.contains('"') {
+                    return Err(invalid_setting(
+                        "category",
+                        format!(
+                            "Invalid category {:?} in the [{}] section: it can't be empty or contain quotes",
+                            category, MISSION_SETTINGS_KEY
+                        ),
+                    ));
                 }
+            }
+        }
 
-                (name, config)
-            }).collect()
-        });
+        let unused = self
+            .map_categories
+            .keys()
+            .filter(|map| !self.maps.iter().any(|built| built.map_name().eq_ignore_ascii_case(map)))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        if !unused.is_empty() {
+            warn!("map_categories lists maps that aren't built: {}", unused.join(", "));
+        }
 
         Ok(())
     }
 
-    /// Convert this mission to SQM
-    pub fn to_sqm(&self) -> Result<String> {
-        let mut buffer = Vec::new();
-        self.sqm.write(&mut buffer)?;
+    /// Every addon built from these settings, without duplicates
+    fn addon_names(&self) -> Vec<String> {
+        let mut addon_names = self
+            .maps
+            .iter()
+            .map(|map| self.addon_name_for(map.map_name()))
+            .collect::<Vec<_>>();
+        addon_names.sort();
+        addon_names.dedup();
 
-        Ok(std::str::from_utf8(&buffer)?.to_string())
+        addon_names
     }
 
-    /// Return the class_name for this mission
-    pub fn mission_name(&self) -> String {
-        format!("{}.{}", self.class_name(), self.map_name)
+    /// Check that every addon name can be used as a folder and PBO name, and that `map_addons`
+    /// only lists built maps
+    fn validate_addon_names(&self) -> Result<()> {
+        for addon_name in self.addon_names() {
+            if addon_name.trim().is_empty() || addon_name.contains(['/', '\\']) {
+                return Err(invalid_setting(
+                    "addon_name",
+                    format!(
+                        "Invalid addon name {:?} in the [{}] section: it can't be empty or contain slashes",
+                        addon_name, MISSION_SETTINGS_KEY
+                    ),
+                ));
+            }
+        }
+
+        let unused = self
+            .map_addons
+            .keys()
+            .filter(|map| !self.maps.iter().any(|built| built.map_name().eq_ignore_ascii_case(map)))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        if !unused.is_empty() {
+            warn!("map_addons lists maps that aren't built: {}", unused.join(", "));
+        }
+
+        Ok(())
     }
 
-    pub fn class_name(&self) -> String {
-        format!("{}_{}{}", self.prefix, self.map_name, self.mission_name,)
+    /// Check every map against the known Arma worlds and `extra_maps`
+    fn validate_maps(&self) -> Result<()> {
+        let is_known = |map: &str| {
+            KNOWN_MAPS
+                .iter()
+                .copied()
+                .chain(self.extra_maps.iter().map(String::as_str))
+                .any(|known| known.eq_ignore_ascii_case(map))
+        };
+
+        let unknown: Vec<&str> = self
+            .maps
+            .iter()
+            .map(MapConfig::map_name)
+            .filter(|map| !is_known(map))
+            .collect();
+
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_setting(
+                "maps",
+                format!(
+                    "Unknown maps: {}. Add modded terrains to `extra_maps` in the [{}] section.",
+                    unknown.join(", "),
+                    MISSION_SETTINGS_KEY
+                ),
+            ))
+        }
+    }
+
+    /// Check that the time of day and weather are within range
+    fn validate_environment(&self) -> Result<()> {
+        let ranges = [
+            ("time_of_day", self.time_of_day, 24.),
+            ("fog", self.fog, 1.),
+            ("overcast", self.overcast, 1.),
+            ("rain", self.rain, 1.),
+        ];
+
+        for (name, value, max) in ranges.iter() {
+            if let Some(value) = value {
+                if !(0. ..=*max).contains(value) {
+                    return Err(invalid_setting(
+                        name,
+                        format!(
+                            "Invalid {} in the [{}] section: {} is not between 0 and {}",
+                            name, MISSION_SETTINGS_KEY, value, max
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `mission_attributes` as CfgMissions entries
+    fn mission_attributes(&self) -> Result<Vec<MissionAttribute>> {
+        self.mission_attributes
+            .iter()
+            .map(|(name, value)| MissionAttribute::new("mission_attributes", name, value))
+            .collect()
+    }
+
+    fn scenario_attributes(&self) -> Result<Vec<MissionAttribute>> {
+        self.scenario_attributes
+            .iter()
+            .map(|(name, value)| MissionAttribute::new("scenario_attributes", name, value))
+            .collect()
+    }
+
+    /// Start hour and minute of the mission
+    fn start_time(&self) -> (u32, u32) {
+        match self.time_of_day {
+            Some(time_of_day) => {
+                let minutes = (time_of_day * 60.).round() as u32 % (24 * 60);
+
+                (minutes / 60, minutes % 60)
+            }
+            None => (14, 20),
+        }
+    }
+
+    /// The numeric SQM value of `respawn_type`
+    fn respawn(&self) -> Result<usize> {
+        RESPAWN_TYPES
+            .iter()
+            .position(|respawn| respawn.eq_ignore_ascii_case(&self.respawn_type))
+            .ok_or_else(|| {
+                invalid_setting(
+                    "respawn_type",
+                    format!(
+                        "Unknown respawn_type: {}. Expected one of: {}",
+                        self.respawn_type,
+                        RESPAWN_TYPES.join(", ")
+                    ),
+                )
+            })
     }
 }
 
-#[derive(Serialize)]
-struct Addon {
-    prefix: String,
-    addon_name: String,
-    missions: Vec<MissionClass>,
+/// An error for the `[missions]` setting `name`
+fn invalid_setting(name: &str, reason: String) -> LaatError {
+    LaatError::InvalidConfig {
+        key: format!("{}.{}", MISSION_SETTINGS_KEY, name),
+        reason,
+    }
 }
 
-impl Addon {
-    pub fn from_parts(
-        prefix: String,
-        addon_name: String,
-        missions: Vec<(PathBuf, Mission)>,
-    ) -> Self {
-        let missions = missions
-            .into_iter()
-            .map(|(directory, mission)| {
-                let directory = format!(
-                    r"{}\{}\missions\{}",
-                    prefix,
-                    addon_name,
-                    directory
-                        .parent()
-                        .map(|p| p.file_name().map(|p| p.to_string_lossy().to_owned()))
-                        .flatten()
-                        .unwrap()
+fn default_addon_name() -> String {
+    "Missions".to_string()
+}
+
+fn default_missions_subfolder() -> String {
+    "missions".to_string()
+}
+
+fn default_mission_name() -> String {
+    "ZeusMission".to_string()
+}
+
+fn default_respawn_delay() -> f32 {
+    2.
+}
+
+fn default_respawn_type() -> String {
+    "BASE".to_string()
+}
+
+struct Composition {
+    /// Where the composition was loaded from
+    path: PathBuf,
+    #[allow(dead_code)]
+    header: Option<Config>,
+    /// Where the composition was saved. Item positions are relative to it.
+    center: Option<Position>,
+    /// The Eden items, as found in the SQE's "items" class
+    items: Option<EntryList>,
+    /// `items` placed at the composition's own offset, shared by every map without an offset
+    /// override
+    placed_items: Option<EntryList>,
+    offset: (f32, f32, f32),
+    /// Offset of this composition relative to the others in the mission
+    local_offset: (f32, f32, f32),
+    rotation: f32,
+    scale: f32,
+    ignore_center: bool,
+    /// The `composition` entry it was loaded from
+    entry: Option<CompositionEntry>,
+}
+
+impl Composition {
+    /// Load a composition from a folder (or `.zip` archive) containing `header.sqe` and
+    /// `composition.sqe`, or from a `.sqm` file, in which case its `Mission.Entities` are used as
+    /// the items. Reads that fail because the file is locked are retried up to `io_retries` times.
+    #[instrument(err)]
+    pub async fn from_path(
+        path: &PathBuf,
+        offset: (f32, f32, f32),
+        local_offset: (f32, f32, f32),
+        rotation: f32,
+        scale: f32,
+        ignore_center: bool,
+        io_retries: usize,
+    ) -> Result<Self> {
+        let (header, center, items) = if path.extension().is_some_and(|extension| extension == "sqm") {
+            let (center, items) = read_sqm_entities(path, io_retries).await?;
+
+            (None, Some(center), Some(items))
+        } else {
+            let (header, composition) = if path.extension().is_some_and(|extension| extension == "zip") {
+                read_zipped_composition(path, io_retries).await?
+            } else {
+                let (header, composition) = tokio::join!(
+                    read_config(path.join("header.sqe"), io_retries),
+                    read_config(path.join("composition.sqe"), io_retries)
                 );
-                MissionClass {
-                    briefing_name: format!("[{}] {}", prefix, mission.class_name()),
-                    class_name: mission.class_name(),
-                    directory,
-                }
-            })
-            .collect();
 
-        Addon {
-            prefix,
-            addon_name,
-            missions,
+                let composition = composition?;
+                (header?, composition)
+            };
+
+            let composition = composition.into_inner().entries.unwrap_or_default();
+            let items = get_items(&composition);
+
+            // Older exports have no center[], so center their items around the centroid instead
+            let (center, items) = match (get_center(&composition), items) {
+                (None, Some(items)) => match centroid(&items) {
+                    Some(center) => {
+                        info!("No center[] in {}, using the centroid of its items: {:?}", path.display(), center);
+                        (Some(center), Some(recenter(items, center)))
+                    }
+                    None => (None, Some(items)),
+                },
+                (center, items) => (center, items),
+            };
+
+            (Some(header), center, items)
+        };
+
+        let mut composition = Composition {
+            path: path.clone(),
+            header,
+            center,
+            items,
+            placed_items: None,
+            offset,
+            local_offset,
+            rotation,
+            scale,
+            ignore_center,
+            entry: None,
+        };
+
+        // Errors are reported when the items are first used
+        composition.placed_items = composition.place_items(None).ok();
+
+        Ok(composition)
+    }
+
+    /// Get "center[]" from SQE, cast it into a tuple
+    pub fn get_center(&self) -> Result<(f32, f32, f32)> {
+        let center = self
+            .center
+            .ok_or_else(|| self.parse_error("it has no center[] and no positioned items to infer one from"))?;
+
+        if !self.ignore_center {
+            Ok(center)
+        } else {
+            Ok((0., 0., 0.))
         }
     }
+
+    pub fn get_offset(&self) -> Result<(f32, f32, f32)> {
+        let (x1, y1, z1) = self.get_center()?;
+        let (x2, y2, z2) = self.offset;
+        let (x3, y3, z3) = self.local_offset;
+
+        Ok((x1 + x2 + x3, y1 + y2 + y3, z1 + z2 + z3))
+    }
+
+    /// Where the composition's center ends up in the mission
+    pub fn placed_offset(&self, offset_override: Option<(f32, f32, f32)>) -> Result<(f32, f32, f32)> {
+        if let Some((x1, y1, z1)) = offset_override {
+            let (x2, y2, z2) = self.local_offset;
+
+            Ok((x1 + x2, y1 + y2, z1 + z2))
+        }
+        else {
+            self.get_offset()
+        }
+    }
+
+    /// Check that the composition's center lands within world coordinates on `map_name`, to catch
+    /// offsets that put it in the ocean or off the map
+    pub fn check_placement(&self, map_name: &str, offset_override: Option<(f32, f32, f32)>) -> Result<()> {
+        // Compositions without a center fail to merge, which is reported on its own
+        let (x, _, y) = match self.placed_offset(offset_override) {
+            Ok(offset) => offset,
+            Err(_) => return Ok(()),
+        };
+
+        let world = 0. ..=MAX_WORLD_SIZE;
+        if world.contains(&x) && world.contains(&y) {
+            return Ok(());
+        }
+
+        Err(invalid_setting(
+            "composition_offset",
+            format!(
+                "Composition {} is centered at [{}, {}] on {}, outside of the map (0 to {} meters). Check composition_offset and the map's offset.",
+                self.path.display(),
+                x,
+                y,
+                map_name,
+                MAX_WORLD_SIZE
+            ),
+        ))
+    }
+
+    /// Get and offset items from the SQE
+    pub fn get_offseted_items(&self, offset_override: Option<(f32, f32, f32)>) -> Result<EntryList> {
+        if offset_override.is_some() {
+            info!("Overriding offset...");
+        } else if let Some(items) = &self.placed_items {
+            return Ok(items.clone());
+        }
+
+        self.place_items(offset_override)
+    }
+
+    fn place_items(&self, offset_override: Option<(f32, f32, f32)>) -> Result<EntryList> {
+        let offset = self.placed_offset(offset_override)?;
+
+        let placement = Placement {
+            offset,
+            rotation: self.rotation,
+            scale: self.scale,
+        };
+
+        let entries = self.items().ok_or_else(|| self.parse_error("it has no items class"))?;
+        debug!("Item Classes: {}", entries.len());
+
+        Ok(offset_classes(entries, placement))
+    }
+
+    /// Returns false if the composition's `maps` filter leaves out `map_name`
+    pub fn includes_map(&self, map_name: &str) -> bool {
+        self.entry.as_ref().is_none_or(|entry| entry.includes_map(map_name))
+    }
+
+    fn parse_error(&self, reason: &str) -> LaatError {
+        LaatError::CompositionParse {
+            path: self.path.clone(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// The classes inside "items" in the SQE
+    fn items(&self) -> Option<EntryList> {
+        self.items.clone()
+    }
+
+    /// Number of placed entities (objects, markers, triggers, waypoints and logic), counting the
+    /// members of groups and layers rather than the groups themselves
+    pub fn object_count(&self) -> usize {
+        self.items().map(|items| count_objects(&items)).unwrap_or_default()
+    }
+
+    /// Smallest and largest X, Z, Y over every position in the composition, relative to its
+    /// center. `None` if nothing in it has a position.
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let mut positions = Vec::new();
+        collect_positions(&self.items()?, &mut positions);
+
+        let first = *positions.first()?;
+
+        Some(positions.iter().fold((first, first), |(min, max), &(x, y, z)| {
+            (
+                (min.0.min(x), min.1.min(y), min.2.min(z)),
+                (max.0.max(x), max.1.max(y), max.2.max(z)),
+            )
+        }))
+    }
 }
 
-#[derive(Serialize)]
-struct MissionClass {
-    class_name: String,
-    briefing_name: String,
-    directory: String,
+/// Output format of `laat preview`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreviewFormat {
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for PreviewFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> std::result::Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "json" => Ok(PreviewFormat::Json),
+            "csv" => Ok(PreviewFormat::Csv),
+            _ => Err(format!("Unknown preview format {:?}, expected json or csv", format)),
+        }
+    }
+}
+
+/// An entity of a composition, where it ends up in the mission
+#[derive(Debug, Serialize)]
+struct PlacedObject {
+    composition: String,
+    data_type: String,
+    /// The item's `type`, i.e. its vehicle class or marker type
+    class: String,
+    /// East
+    x: f32,
+    /// North
+    y: f32,
+    /// Height
+    z: f32,
+}
+
+/// Where every entity of the compositions is placed on `map`, or the first of `maps`. Slots and
+/// `repeat` copies are left out.
+pub(crate) async fn preview(build_config: &BuildContext, map: Option<&str>, format: PreviewFormat) -> Result<String> {
+    let mission_settings = MissionSettings::from_build_config(build_config)?;
+
+    let map = match map {
+        Some(name) => mission_settings
+            .maps
+            .iter()
+            .find(|map| map.map_name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| invalid_setting("maps", format!("{} is not one of the maps in the [{}] section", name, MISSION_SETTINGS_KEY)))?,
+        None => mission_settings
+            .maps
+            .first()
+            .ok_or_else(|| invalid_setting("maps", format!("No maps in the [{}] section", MISSION_SETTINGS_KEY)))?,
+    };
+
+    let compositions = load_compositions(&mission_settings, build_config).await?;
+    let mut objects = Vec::new();
+
+    for composition in compositions.iter().filter(|composition| composition.includes_map(map.map_name())) {
+        let items = composition.get_offseted_items(map.offset_override())?;
+        collect_placed_objects(&items, &composition.path.display().to_string(), &mut objects);
+    }
+
+    info!("{} objects placed on {}", objects.len(), map.map_name());
+
+    Ok(match format {
+        PreviewFormat::Json => serde_json::to_string_pretty(&objects)?,
+        PreviewFormat::Csv => {
+            let quote = |field: &str| {
+                if field.contains([',', '"', '\n']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.to_string()
+                }
+            };
+
+            std::iter::once("composition,data_type,class,x,y,z".to_string())
+                .chain(objects.iter().map(|object| {
+                    format!(
+                        "{},{},{},{},{},{}",
+                        quote(&object.composition),
+                        quote(&object.data_type),
+                        quote(&object.class),
+                        object.x,
+                        object.y,
+                        object.z
+                    )
+                }))
+                .map(|line| line + "\n")
+                .collect()
+        }
+    })
+}
+
+/// Collect the placed entities, looking inside groups and layers
+fn collect_placed_objects(entries: &[(String, ConfigEntry)], composition: &str, objects: &mut Vec<PlacedObject>) {
+    for (_, entry) in entries {
+        let item = match entry {
+            ConfigEntry::ClassEntry(class) => class.entries.as_deref().unwrap_or_default(),
+            _ => continue,
+        };
+
+        match data_type(item) {
+            Some("Group") | Some("Layer") => {
+                if let Some(entities) = get_class(item, "Entities") {
+                    collect_placed_objects(&entities, composition, objects);
+                }
+            }
+            Some(data_type) => {
+                let mut positions = Vec::new();
+                collect_positions(item, &mut positions);
+
+                let class = item.iter().find_map(|(name, entry)| match entry {
+                    ConfigEntry::StringEntry(class) if name == "type" => Some(class.clone()),
+                    _ => None,
+                });
+
+                // SQM positions are X, Z (height), Y
+                if let Some(&(x, z, y)) = positions.first() {
+                    objects.push(PlacedObject {
+                        composition: composition.to_string(),
+                        data_type: data_type.to_string(),
+                        class: class.unwrap_or_default(),
+                        x,
+                        y,
+                        z,
+                    });
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+fn count_objects(entries: &[(String, ConfigEntry)]) -> usize {
+    entries
+        .iter()
+        .filter_map(|(_, entry)| match entry {
+            ConfigEntry::ClassEntry(class) => class.entries.as_ref(),
+            _ => None,
+        })
+        .map(|entries| match data_type(entries) {
+            Some("Group") | Some("Layer") | None => count_objects(entries),
+            Some(_) => 1,
+        })
+        .sum()
+}
+
+fn collect_positions(entries: &[(String, ConfigEntry)], positions: &mut Vec<Position>) {
+    for (name, entry) in entries {
+        match entry {
+            ConfigEntry::ArrayEntry(position) if name == "position" => {
+                let values = position.elements.iter().map(element_value).collect::<Vec<_>>();
+
+                if let [Some(x), Some(y), Some(z)] = values[..] {
+                    positions.push((x, y, z));
+                }
+            }
+            ConfigEntry::ClassEntry(class) => {
+                if let Some(entries) = &class.entries {
+                    collect_positions(entries, positions);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn get_center(entries: &[(String, ConfigEntry)]) -> Option<Position> {
+    entries.iter().find_map(|(name, entry)| match entry {
+        ConfigEntry::ArrayEntry(array) if name == "center" => {
+            debug!("Center Array: {:?}", array);
+
+            if array.elements.iter().filter_map(element_value).count() < 3 {
+                warn!("Ignoring malformed center[]: {:?}", array);
+                return None;
+            }
+
+            Some(get_center_from_field(array))
+        }
+        _ => None,
+    })
+}
+
+/// The average X/Y of every position in `entries`, at a height of 0. `None` if nothing in it
+/// has a position.
+fn centroid(entries: &[(String, ConfigEntry)]) -> Option<Position> {
+    let mut positions = Vec::new();
+    collect_positions(entries, &mut positions);
+
+    if positions.is_empty() {
+        return None;
+    }
+
+    let count = positions.len() as f32;
+    let (x, y) = positions.iter().fold((0., 0.), |(x, y), position| (x + position.0, y + position.2));
+
+    Some((x / count, 0., y / count))
+}
+
+/// Make the positions in `entries` relative to `center`, keeping heights as they are
+fn recenter(entries: EntryList, center: Position) -> EntryList {
+    let placement = Placement {
+        offset: (-center.0, 0., -center.2),
+        rotation: 0.,
+        scale: 1.,
+    };
+
+    offset_classes(entries, placement)
+}
+
+fn get_items(entries: &[(String, ConfigEntry)]) -> Option<EntryList> {
+    get_class(entries, "items")
+}
+
+fn get_class(entries: &[(String, ConfigEntry)], class_name: &str) -> Option<EntryList> {
+    entries.iter().find_map(|(name, entry)| match entry {
+        ConfigEntry::ClassEntry(class) if name.eq_ignore_ascii_case(class_name) => class.entries.clone(),
+        _ => None,
+    })
+}
+
+/// Read the `Mission.Entities` (or top-level `Entities`) class of a mission.sqm as composition
+/// items. SQM positions are absolute, so they are made relative to the middle of the entities,
+/// which becomes the composition's center.
+async fn read_sqm_entities(path: &Path, io_retries: usize) -> Result<(Position, EntryList)> {
+    let sqm = read_config(path.to_path_buf(), io_retries).await?.into_inner().entries.unwrap_or_default();
+
+    let entities = get_class(&sqm, "Mission")
+        .and_then(|mission| get_class(&mission, "Entities"))
+        .or_else(|| get_class(&sqm, "Entities"))
+        .ok_or_else(|| LaatError::CompositionParse {
+            path: path.to_path_buf(),
+            reason: "it has no Mission.Entities class".to_string(),
+        })?;
+
+    let mut positions = Vec::new();
+    collect_positions(&entities, &mut positions);
+
+    // Keep heights as they are, only the X/Y plane is centered
+    let center = match positions.first() {
+        Some(&first) => {
+            let (min, max) = positions.iter().fold((first, first), |(min, max), &(x, _, y)| {
+                ((min.0.min(x), 0., min.2.min(y)), (max.0.max(x), 0., max.2.max(y)))
+            });
+
+            ((min.0 + max.0) / 2., 0., (min.2 + max.2) / 2.)
+        }
+        None => (0., 0., 0.),
+    };
+
+    Ok((center, recenter(entities, center)))
+}
+
+/// Read a config file, derapifying it first if it is binarized
+async fn read_config(path: PathBuf, io_retries: usize) -> Result<Config> {
+    let description = format!("read {}", path.display());
+    let buffer = retry_io(io_retries, &description, || tokio::fs::read(&path)).await?;
+
+    parse_config(&path, buffer)
+}
+
+/// Parse a config read from `source`, derapifying it if needed
+fn parse_config(source: &Path, buffer: Vec<u8>) -> Result<Config> {
+    if buffer.starts_with(RAP_MAGIC) {
+        debug!("Derapifying: {:?}", source);
+        Ok(Config::read_rapified(&mut Cursor::new(buffer))?)
+    } else {
+        let text = decode_config(source, &buffer);
+        Ok(Config::read(&mut text.as_bytes(), None, &Vec::new())?)
+    }
+}
+
+/// Decode a text config as UTF-8. A byte order mark is stripped, UTF-16 with a byte order mark is
+/// converted, and anything else that isn't valid UTF-8 is read as Windows-1252, the code page
+/// Eden exports use on western systems.
+fn decode_config(source: &Path, buffer: &[u8]) -> String {
+    if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(buffer) {
+        if encoding != encoding_rs::UTF_8 {
+            debug!("Converting {} from {}", source.display(), encoding.name());
+        }
+
+        return encoding.decode_without_bom_handling(&buffer[bom_length..]).0.into_owned();
+    }
+
+    match std::str::from_utf8(buffer) {
+        Ok(text) => text.to_string(),
+        Err(_) => {
+            warn!("{} isn't UTF-8, reading it as Windows-1252", source.display());
+            encoding_rs::WINDOWS_1252.decode_without_bom_handling(buffer).0.into_owned()
+        }
+    }
+}
+
+/// Read the header and composition SQE from a zip archive. They can be at the root of the
+/// archive or in a folder inside it, as long as there's only one `composition.sqe`.
+async fn read_zipped_composition(path: &Path, io_retries: usize) -> Result<(Config, Config)> {
+    let parse_error = |reason: String| LaatError::CompositionParse {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let description = format!("read {}", path.display());
+    let buffer = retry_io(io_retries, &description, || tokio::fs::read(path)).await?;
+    let mut archive = zip::ZipArchive::new(Cursor::new(buffer))
+        .map_err(|why| parse_error(format!("it can't be opened as a zip archive: {}", why)))?;
+
+    let compositions = archive
+        .file_names()
+        .filter(|name| *name == "composition.sqe" || name.ends_with("/composition.sqe"))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let composition = match &compositions[..] {
+        [composition] => composition.clone(),
+        [] => return Err(parse_error("the archive has no composition.sqe".to_string())),
+        _ => {
+            return Err(parse_error(format!(
+                "the archive has more than one composition.sqe: {}",
+                compositions.join(", ")
+            )))
+        }
+    };
+    let header = format!("{}header.sqe", composition.trim_end_matches("composition.sqe"));
+
+    let mut read_member = |name: &str| -> Result<Config> {
+        let mut member = archive
+            .by_name(name)
+            .map_err(|why| parse_error(format!("{} can't be read from the archive: {}", name, why)))?;
+
+        let mut buffer = Vec::new();
+        member.read_to_end(&mut buffer)?;
+
+        parse_config(&path.join(name), buffer)
+    };
+
+    let header = read_member(&header)?;
+    let composition = read_member(&composition)?;
+
+    Ok((header, composition))
+}
+
+fn get_center_from_field(array: &ConfigArray) -> (f32, f32, f32) {
+    let map_elem = |x: &ConfigArrayElement| {
+        match x {
+            ConfigArrayElement::FloatElement(x) => *x,
+            ConfigArrayElement::IntElement(x) => *x as f32,
+            _ => 0.
+        }
+    };
+
+    let x = array.elements.first().map(map_elem).unwrap_or_default();
+    let y = array.elements.get(1).map(map_elem).unwrap_or_default();
+    let z = array.elements.get(2).map(map_elem).unwrap_or_default();
+
+    (x, y ,z)
+}
+
+type EntryList = Vec<(String, ConfigEntry)>;
+
+/// X, Z (height), Y, in the order SQM positions are stored
+type Position = (f32, f32, f32);
+
+/// Where composition items end up in a mission. Items are spread out (or pulled in) from the
+/// composition center and rotated around it first, then translated by the offset.
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    /// X, Y, Z offset added to every position
+    offset: (f32, f32, f32),
+    /// Rotation, in degrees, around the vertical axis
+    rotation: f32,
+    /// Multiplier for the horizontal distance from the center. Heights are kept.
+    scale: f32,
+}
+
+/// Offset classes recursively
+#[instrument(skip(entries, placement))]
+fn offset_classes(entries: EntryList, placement: Placement) -> EntryList {
+    entries
+        .into_iter()
+        .map(|(name, entry)| {
+            let entry = if let ConfigEntry::ClassEntry(mut class) = entry {
+                class.entries = class.entries.map(|entries| {
+                    // Offset
+                    let entries = match data_type(&entries) {
+                        // Markers, triggers and waypoints keep their position on the item itself
+                        Some("Marker") | Some("Trigger") | Some("Waypoint") => {
+                            place_entries(entries, placement)
+                        }
+                        // Objects and logic keep it in their PositionInfo, groups in their members
+                        Some(_) => entries,
+                        // Not an Eden item, i.e. PositionInfo itself
+                        None => place_entries(entries, placement),
+                    };
+
+                    // Recurse
+                    offset_classes(entries, placement)
+                });
+
+                ConfigEntry::ClassEntry(class)
+            } else {
+                entry
+            };
+
+            (name, entry)
+        })
+        .collect()
+}
+
+/// The Eden entity type ("Object", "Marker", "Trigger", "Group", ...) of an item class
+fn data_type(entries: &[(String, ConfigEntry)]) -> Option<&str> {
+    entries.iter().find_map(|(name, entry)| match entry {
+        ConfigEntry::StringEntry(data_type) if name == "dataType" => Some(data_type.as_str()),
+        _ => None,
+    })
+}
+
+/// Rotate and translate the `position[]` of a single class, turning its heading (`angles[]`
+/// for objects, `angle` for markers and triggers, or a legacy `dir`) with it. Classes without
+/// a position are left untouched.
+fn place_entries(mut entries: EntryList, placement: Placement) -> EntryList {
+    if !entries.iter().any(|(name, _)| name == "position") {
+        return entries;
+    }
+
+    let offsets = [placement.offset.0, placement.offset.1, placement.offset.2];
+
+    for (name, entry) in entries.iter_mut() {
+        match (name.as_str(), &mut *entry) {
+            ("position", ConfigEntry::ArrayEntry(position)) => {
+                scale_position(position, placement.scale);
+                rotate_position(position, placement.rotation);
+
+                position.elements = offset_elements(&position.elements, &offsets);
+            }
+            ("angles", ConfigEntry::ArrayEntry(angles)) if placement.rotation != 0. => {
+                // angles[] are radians, with the heading stored in the middle element
+                if let Some(heading) = angles.elements.get(1).and_then(element_value) {
+                    let heading = (heading + placement.rotation.to_radians()).rem_euclid(TAU);
+                    angles.elements[1] = ConfigArrayElement::FloatElement(heading);
+                }
+            }
+            ("dir", ConfigEntry::FloatEntry(_))
+            | ("dir", ConfigEntry::IntEntry(_))
+            | ("angle", ConfigEntry::FloatEntry(_))
+            | ("angle", ConfigEntry::IntEntry(_))
+                if placement.rotation != 0. =>
+            {
+                // dir and angle are degrees
+                let dir = match entry {
+                    ConfigEntry::FloatEntry(dir) => *dir,
+                    ConfigEntry::IntEntry(dir) => *dir as f32,
+                    _ => unreachable!(),
+                };
+
+                *entry = ConfigEntry::FloatEntry((dir + placement.rotation).rem_euclid(360.));
+            }
+            _ => {}
+        }
+    }
+
+    entries
+}
+
+/// Scale the horizontal part of a position, leaving its height alone.
+///
+/// SQM positions are stored as X, Z (height), Y.
+fn scale_position(position: &mut ConfigArray, scale: f32) {
+    if scale == 1. {
+        return;
+    }
+
+    for idx in [0, 2] {
+        if let Some(value) = position.elements.get(idx).and_then(element_value) {
+            position.elements[idx] = ConfigArrayElement::FloatElement(value * scale);
+        }
+    }
+}
+
+/// Rotate a position clockwise around the vertical axis, matching Arma's compass headings.
+///
+/// SQM positions are stored as X, Z (height), Y.
+fn rotate_position(position: &mut ConfigArray, degrees: f32) {
+    if degrees == 0. {
+        return;
+    }
+
+    let x = position.elements.first().and_then(element_value);
+    let y = position.elements.get(2).and_then(element_value);
+
+    if let (Some(x), Some(y)) = (x, y) {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+
+        position.elements[0] = ConfigArrayElement::FloatElement(x * cos + y * sin);
+        position.elements[2] = ConfigArrayElement::FloatElement(y * cos - x * sin);
+    }
+}
+
+fn element_value(element: &ConfigArrayElement) -> Option<f32> {
+    match element {
+        ConfigArrayElement::FloatElement(x) => Some(*x),
+        ConfigArrayElement::IntElement(x) => Some(*x as f32),
+        _ => None,
+    }
+}
+
+/// Add `offsets` to the elements of a position, index by index
+fn offset_elements(elements: &[ConfigArrayElement], offsets: &[f32]) -> Vec<ConfigArrayElement> {
+    elements
+        .iter()
+        .enumerate()
+        .map(|(idx, el)| add_to_element(el.clone(), offsets, idx))
+        .collect()
+}
+
+/// Add the offset for index `idx` to a position element. Ints stay ints when the offset is a
+/// whole number, and nested arrays (i.e. positions stored as `{{x, z, y}}`) are offset as
+/// positions of their own.
+fn add_to_element(element: ConfigArrayElement, offsets: &[f32], idx: usize) -> ConfigArrayElement {
+    let increment = offsets.get(idx).copied().unwrap_or_default();
+
+    match element {
+        ConfigArrayElement::FloatElement(float) => ConfigArrayElement::FloatElement(float + increment),
+        ConfigArrayElement::IntElement(int) if increment.fract() == 0. => {
+            ConfigArrayElement::IntElement(int + increment as i32)
+        }
+        ConfigArrayElement::IntElement(int) => ConfigArrayElement::FloatElement(int as f32 + increment),
+        ConfigArrayElement::ArrayElement(mut array) => {
+            array.elements = offset_elements(&array.elements, offsets);
+            ConfigArrayElement::ArrayElement(array)
+        }
+        ConfigArrayElement::StringElement(_) => element,
+    }
+}
+
+/// Load the composition of `entry`, placed by the mission settings, the entry and the
+/// composition's `transform.toml`, in that order of precedence
+#[instrument(err, skip(mission_settings, build_config))]
+async fn load_composition(
+    mut entry: CompositionEntry,
+    mission_settings: &MissionSettings,
+    build_config: &BuildContext,
+) -> Result<Composition> {
+    info!("Loading composition at: {:?}", entry.path);
+    let io_retries = build_config.io_retries;
+
+    let path = match composition_url(&entry.path) {
+        Some(url) => download_composition(url, build_config).await?,
+        None => entry.path.clone(),
+    };
+
+    let transform = CompositionTransform::from_composition(&path, io_retries).await?;
+
+    entry.offset = entry.offset.or(transform.offset);
+    entry.maps = entry.maps.or(transform.maps);
+
+    let mut composition = Composition::from_path(
+        &path,
+        mission_settings.composition_offset,
+        entry.offset.unwrap_or_default(),
+        mission_settings.composition_rotation.or(transform.rotation).unwrap_or(0.),
+        mission_settings.composition_scale.or(transform.scale).unwrap_or(1.),
+        mission_settings.ignore_center,
+        io_retries,
+    )
+    .await?;
+
+    let object_count = composition.object_count();
+
+    match composition.bounds() {
+        Some((min, max)) if object_count > 0 => info!(
+            "Loaded {} objects from {:?}, spanning {:?} to {:?} around center {:?}",
+            object_count,
+            entry.path,
+            min,
+            max,
+            composition.get_center()
+        ),
+        _ => warn!("Composition at {:?} has no placed objects", entry.path),
+    }
+
+    composition.entry = Some(entry);
+
+    Ok(composition)
+}
+
+/// Load every composition listed in the mission settings
+async fn load_compositions(mission_settings: &MissionSettings, build_config: &BuildContext) -> Result<Vec<Composition>> {
+    let entries = mission_settings.compositions();
+
+    if entries.is_empty() {
+        info!("No composition in the [{}] section, generating missions without one", MISSION_SETTINGS_KEY);
+    }
+
+    let futs = entries
+        .into_iter()
+        .map(|entry| load_composition(entry, mission_settings, build_config));
+
+    join_all(futs).await.into_iter().collect()
+}
+
+/// The URL of a composition hosted on a web server, if `path` is one
+fn composition_url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Download the composition at `url` into the build cache, returning the local copy. A `.sqm` or
+/// `.zip` URL is a single file, any other URL a folder with `header.sqe`, `composition.sqe` and
+/// optionally `transform.toml`.
+#[cfg(feature = "http")]
+async fn download_composition(url: &str, build_config: &BuildContext) -> Result<PathBuf> {
+    use crate::cache::BuildCache;
+    use crate::download::download;
+    use sha2::{Digest, Sha256};
+
+    let url = url.trim_end_matches('/');
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let folder = BuildCache::new(build_config).path().join("compositions").join(&hash[..16]);
+
+    let file_name = url.rsplit('/').next().unwrap_or_default();
+    let is_file = Path::new(file_name)
+        .extension()
+        .is_some_and(|extension| extension == "sqm" || extension == "zip");
+
+    if is_file {
+        let target = folder.join(file_name);
+        download(url, &target, false).await?;
+
+        return Ok(target);
+    }
+
+    for file in ["header.sqe", "composition.sqe"] {
+        download(&format!("{}/{}", url, file), &folder.join(file), false).await?;
+    }
+    download(&format!("{}/{}", url, TRANSFORM_FILE), &folder.join(TRANSFORM_FILE), true).await?;
+
+    Ok(folder)
+}
+
+#[cfg(not(feature = "http"))]
+async fn download_composition(url: &str, _: &BuildContext) -> Result<PathBuf> {
+    Err(invalid_setting(
+        "composition",
+        format!("Can't load composition {}: LAAT was built without the `http` feature for loading compositions from URLs", url),
+    ))
+}
+
+/// Make sure no two missions or categories end up with the same CfgMissions class, which Arma
+/// rejects. Class names are compared the way Arma does, ignoring case. `prefix` is the one the
+/// category classes are generated with.
+fn check_class_names(missions: &[Mission], prefix: &str) -> Result<()> {
+    // Class name (lowercased) > what generated it
+    let mut seen: BTreeMap<String, String> = BTreeMap::new();
+    let mut claim = |class_name: String, source: String| match seen.get(&class_name.to_lowercase()) {
+        Some(first) => Some(format!("{} and {} both generate {}", first, source, class_name)),
+        None => {
+            seen.insert(class_name.to_lowercase(), source);
+            None
+        }
+    };
+
+    let collisions = missions
+        .iter()
+        .filter_map(|mission| claim(sanitize_classname(&mission.class_name()), mission.map_name.clone()))
+        .collect::<Vec<_>>();
+
+    if !collisions.is_empty() {
+        return Err(invalid_setting(
+            "maps",
+            format!("Missions with the same CfgMissions class: {}", collisions.join(", ")),
+        ));
+    }
+
+    // Missions sharing a category share its class
+    let categories = missions.iter().filter_map(|mission| mission.category.as_deref()).collect::<BTreeSet<_>>();
+    let collisions = categories
+        .into_iter()
+        .filter_map(|category| claim(category_class_name(prefix, category), format!("category {:?}", category)))
+        .collect::<Vec<_>>();
+
+    if collisions.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid_setting(
+            "category",
+            format!(
+                "Categories with the same CfgMissions class as another category or mission: {}",
+                collisions.join(", ")
+            ),
+        ))
+    }
+}
+
+/// The CfgMissions class of a category
+fn category_class_name(prefix: &str, category: &str) -> String {
+    format!("{}_{}", prefix, sanitize_classname(category))
+}
+
+#[instrument(err)]
+async fn create_missions(
+    mission_settings: &MissionSettings,
+    build_config: &BuildContext,
+) -> Result<Vec<Mission>> {
+    info!("Creating missions...");
+
+    if build_config.author.is_none() {
+        warn!("No author set in LAAT.toml, missions will have blank author credits");
+    }
+
+    let handlebars = Arc::new(create_project_handlebars(build_config)?);
+    let mission_settings = Arc::new(mission_settings.clone());
+
+    // Each map is independent, so render up to `jobs` of them at once on the blocking pool
+    let permits = Arc::new(Semaphore::new(build_config.jobs()));
+    let futs = mission_settings.maps.iter().cloned().map(|map| {
+        let handlebars = handlebars.clone();
+        let mission_settings = mission_settings.clone();
+        let build_config = build_config.clone();
+        let permits = permits.clone();
+
+        async move {
+            let _permit = permits.acquire_owned().await?;
+
+            tokio::task::spawn_blocking(move || {
+                Mission::new(
+                    &handlebars,
+                    build_config.namespaced_prefix(MISSION_SETTINGS_KEY),
+                    mission_settings.mission_name.clone(),
+                    map,
+                    &mission_settings,
+                    &build_config,
+                )
+            })
+            .await?
+        }
+    });
+
+    let mut missions = Vec::new();
+
+    for result in join_all(futs).await {
+        match result {
+            Ok(mission) => missions.push(mission),
+            Err(why) if mission_settings.strict => return Err(why),
+            Err(why) => warn!("Skipping map: {}", why),
+        }
+    }
+
+    Ok(missions)
+}
+
+struct Mission {
+    map_name: String,
+    mission_name: String,
+    category: Option<String>,
+    prefix: String,
+    offset_override: Option<(f32, f32, f32)>,
+    repeat: Option<RepeatConfig>,
+    slots: Vec<SlotConfig>,
+    respawn_positions: Vec<RespawnPositionConfig>,
+    required_addons: Vec<String>,
+    class_addons: BTreeMap<String, String>,
+    class_filter: ClassFilter,
+
+    sqm: Config,
+}
+
+impl Mission {
+    #[instrument(skip(handlebars, mission_settings), err)]
+    pub fn new(
+        handlebars: &Handlebars,
+        prefix: String,
+        mission_name: String,
+        map: MapConfig,
+        mission_settings: &MissionSettings,
+        build_config: &BuildContext,
+    ) -> Result<Self> {
+        let offset_override = map.offset_override();
+        let map_name = map.map_name().to_string();
+
+        #[derive(Serialize)]
+        struct MissionTemplate {
+            author: String,
+            respawn: usize,
+            respawn_delay: f32,
+            mission_name: String,
+            hour: u32,
+            minute: u32,
+            start_overcast: f32,
+            forecast_overcast: f32,
+            fog: f32,
+            rain: f32,
+            overview_text: Option<String>,
+            loading_text: Option<String>,
+            picture: Option<String>,
+        }
+
+        let (hour, minute) = mission_settings.start_time();
+
+        let template = MissionTemplate {
+            author: build_config.author.as_ref().map(Authors::credit).unwrap_or_default(),
+            mission_name: mission_name.clone(),
+            respawn: mission_settings.respawn()?,
+            respawn_delay: mission_settings.respawn_delay,
+            hour,
+            minute,
+            start_overcast: mission_settings.overcast.unwrap_or(0.4),
+            forecast_overcast: mission_settings.overcast.unwrap_or(0.25),
+            fog: mission_settings.fog.unwrap_or_default(),
+            rain: mission_settings.rain.unwrap_or_default(),
+            overview_text: mission_settings.overview_text.as_deref().map(sqm_text),
+            loading_text: mission_settings.loading_text.as_deref().map(sqm_text),
+            // Relative to the mission folder, which the picture is copied into
+            picture: mission_settings
+                .picture
+                .as_ref()
+                .and_then(|picture| picture.file_name())
+                .map(|file_name| sqm_text(&file_name.to_string_lossy())),
+        };
+
+        let sqm = render_template(handlebars, "mission.sqm", &template)?;
+
+        let mut config = Config::read(&mut sqm.as_bytes(), None, &Vec::new())?;
+        set_scenario_attributes(&mut config, &mission_settings.scenario_attributes()?)?;
+
+        Ok(Mission {
+            category: mission_settings.category_for(&map_name),
+            map_name,
+            offset_override,
+            mission_name,
+            prefix,
+            repeat: mission_settings.repeat,
+            slots: mission_settings.slots.clone(),
+            respawn_positions: mission_settings.respawn_positions.clone(),
+            required_addons: mission_settings.required_addons.clone(),
+            class_addons: mission_settings.class_addons.clone(),
+            class_filter: mission_settings.class_filter()?,
+            sqm: config,
+        })
+    }
+
+    /// Set `Mission.Entities` to the items of every composition plus the slot groups, numbered
+    /// `Item0..ItemN-1` with a matching `items` count
+    #[instrument(skip(self, compositions))]
+    pub fn merge_compositions(&mut self, compositions: &[Composition]) -> Result<()> {
+        let compositions = compositions
+            .iter()
+            .filter(|composition| {
+                let included = composition.includes_map(&self.map_name);
+                if !included {
+                    debug!("Skipping {} on {}", composition.path.display(), self.map_name);
+                }
+
+                included
+            })
+            .collect::<Vec<_>>();
+
+        let items = compositions
+            .iter()
+            .map(|composition| {
+                let items = composition.get_offseted_items(self.offset_override)?;
+                Ok(self.class_filter.apply(items))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let items = match self.repeat {
+            Some(repeat) => repeat_items(items, repeat),
+            None => items,
+        };
+
+        // Slots stand next to the first composition, with ids following the composition's
+        let origin = match compositions.first() {
+            Some(composition) => composition.placed_offset(self.offset_override)?,
+            None => self.offset_override.unwrap_or_default(),
+        };
+        let next_id = items.iter().filter_map(|items| max_id(items)).max().map_or(0, |id| id + 1);
+        let slots = slot_groups(&self.slots, origin, next_id)?;
+        let next_id = max_id(&slots).map_or(next_id, |id| id + 1);
+        let respawns = respawn_markers(&self.respawn_positions, origin, next_id)?;
+
+        let items = concat_items(items.into_iter().chain([slots, respawns]).collect());
+        let addons = self.required_addons(&items);
+
+        set_mission_entities(&mut self.sqm, items);
+        set_required_addons(&mut self.sqm, &addons)?;
+
+        Ok(())
+    }
+
+    /// `required_addons`, followed by the addons of the classes in `items` found in `class_addons`
+    fn required_addons(&self, items: &[(String, ConfigEntry)]) -> Vec<String> {
+        let mut classes = Vec::new();
+        collect_types(items, &mut classes);
+
+        let mut detected = classes
+            .iter()
+            .filter_map(|class| {
+                self.class_addons
+                    .iter()
+                    .find(|(addon_class, _)| addon_class.eq_ignore_ascii_case(class))
+                    .map(|(_, addon)| addon.clone())
+            })
+            .collect::<Vec<_>>();
+        detected.sort();
+
+        let mut addons: Vec<String> = Vec::new();
+        for addon in self.required_addons.iter().cloned().chain(detected) {
+            if !addons.iter().any(|existing| existing.eq_ignore_ascii_case(&addon)) {
+                addons.push(addon);
+            }
+        }
+
+        addons
+    }
+
+    /// Convert this mission to SQM, checking that Arma can load it
+    pub fn to_sqm(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        self.sqm.write(&mut buffer)?;
+
+        let sqm = std::str::from_utf8(&buffer)?.to_string();
+
+        validate_sqm(&sqm).map_err(|why| format!("Generated mission {} is invalid: {}", self.mission_name(), why))?;
+
+        Ok(sqm)
+    }
+
+    /// Convert this mission to a binarized (rapified) SQM
+    pub fn to_binarized_sqm(&self) -> Result<Vec<u8>> {
+        self.to_sqm()?;
+
+        Ok(self.sqm.to_cursor()?.into_inner().into_vec())
+    }
+
+    /// Return the class_name for this mission
+    pub fn mission_name(&self) -> String {
+        format!("{}.{}", self.class_name(), self.map_name)
+    }
+
+    /// CfgMissions class of this mission, i.e. `17th_missions_AltisZeusMission` with the prefix
+    /// namespaced for the missions plugin
+    pub fn class_name(&self) -> String {
+        format!("{}_{}{}", self.prefix, self.map_name, self.mission_name,)
+    }
+}
+
+/// Collect the `type` (object class) of every entity, looking inside groups and layers
+fn collect_types(entries: &[(String, ConfigEntry)], classes: &mut Vec<String>) {
+    for (name, entry) in entries {
+        match entry {
+            ConfigEntry::StringEntry(class) if name == "type" => classes.push(class.clone()),
+            ConfigEntry::ClassEntry(class) => collect_types(class.entries.as_deref().unwrap_or_default(), classes),
+            _ => {}
+        }
+    }
+}
+
+/// Set the `addons[]` and `AddonsMetaData` a mission.sqm requires to `addons`, which must be
+/// CfgPatches class names
+fn set_required_addons(sqm: &mut Config, addons: &[String]) -> Result<()> {
+    let items = addons
+        .iter()
+        .enumerate()
+        .map(|(index, addon)| format!("class Item{} {{ className = \"{}\"; name = \"{}\"; }};", index, addon, addon))
+        .collect::<String>();
+
+    let source = format!(
+        "addons[] = {{{}}}; class AddonsMetaData {{ class List {{ items = {}; {} }}; }};",
+        addons.iter().map(|addon| format!("\"{}\"", addon)).collect::<Vec<_>>().join(", "),
+        addons.len(),
+        items
+    );
+
+    let class = sqm.inner_mut();
+    let mut entries = class.entries.take().unwrap_or_default();
+
+    for (name, entry) in Config::from_string(source, None, &[])?.into_inner().entries.unwrap_or_default() {
+        set_entry(&mut entries, &name, entry);
+    }
+
+    class.entries = Some(entries);
+
+    Ok(())
+}
+
+/// Set `attributes` in the `ScenarioData` class of a mission.sqm, replacing the entries of the
+/// same name (ignoring case, like Arma)
+fn set_scenario_attributes(sqm: &mut Config, attributes: &[MissionAttribute]) -> Result<()> {
+    if attributes.is_empty() {
+        return Ok(());
+    }
+
+    let source = attributes
+        .iter()
+        .map(|attribute| format!("{} = {};", attribute.name, attribute.value))
+        .collect::<String>();
+    let parsed = Config::from_string(source, None, &[])?.into_inner().entries.unwrap_or_default();
+
+    let scenario = sqm.inner_mut().entries.iter_mut().flatten().find_map(|(name, entry)| match entry {
+        ConfigEntry::ClassEntry(class) if name == "ScenarioData" => Some(class),
+        _ => None,
+    });
+    let scenario = scenario.ok_or("The mission.sqm template has no ScenarioData class")?;
+    let entries = scenario.entries.get_or_insert_with(Vec::new);
+
+    for (name, entry) in parsed {
+        match entries.iter_mut().find(|(existing, _)| existing.eq_ignore_ascii_case(&name)) {
+            Some(existing) => *existing = (name, entry),
+            None => entries.push((name, entry)),
+        }
+    }
+
+    Ok(())
+}
+
+/// `text` as the contents of an SQM string, on a single line
+fn sqm_text(text: &str) -> String {
+    text.trim().lines().map(str::trim).collect::<Vec<_>>().join(" ").replace('"', "\"\"")
+}
+
+/// Replace the `Mission.Entities` class of a mission.sqm with `items`, i.e. the `items` count and
+/// `ItemN` classes returned by placing a composition. `Entities` keeps its position within
+/// `Mission`, or is added at the end. Configs without a `Mission` class are left as they are.
+pub fn set_mission_entities(sqm: &mut Config, items: EntryList) {
+    let class = sqm.inner_mut();
+
+    class.entries = class.entries.take().map(|entries| {
+        entries
+            .into_iter()
+            .map(|(name, config)| match config {
+                ConfigEntry::ClassEntry(mut mission) if name == "Mission" => {
+                    let parent = mission.parent.clone();
+
+                    mission.entries = mission.entries.map(|mut entries| {
+                        let entities = ConfigEntry::ClassEntry(ConfigClass {
+                            parent,
+                            is_external: false,
+                            is_deletion: false,
+                            entries: Some(items.clone()),
+                        });
+
+                        set_entry(&mut entries, "Entities", entities);
+
+                        entries
+                    });
+
+                    (name, ConfigEntry::ClassEntry(mission))
+                }
+                config => (name, config),
+            })
+            .collect()
+    });
+}
+
+/// Read a written mission.sqm back and check that it has a `Mission` class, and that every
+/// `Entities` class numbers its items `Item0..ItemN-1` with a matching `items` count
+fn validate_sqm(sqm: &str) -> std::result::Result<(), String> {
+    let config = Config::read(&mut sqm.as_bytes(), None, &Vec::new()).map_err(|why| format!("it can't be parsed: {}", why))?;
+    let entries = config.into_inner().entries.unwrap_or_default();
+
+    let mission = get_class(&entries, "Mission").ok_or("it has no Mission class")?;
+    get_class(&mission, "Entities").ok_or("it has no Mission.Entities class")?;
+
+    validate_entities(&entries, "")
+}
+
+fn validate_entities(entries: &[(String, ConfigEntry)], path: &str) -> std::result::Result<(), String> {
+    for (name, entry) in entries {
+        let class = match entry {
+            ConfigEntry::ClassEntry(class) => class.entries.as_deref().unwrap_or_default(),
+            _ => continue,
+        };
+        let path = if path.is_empty() { name.clone() } else { format!("{}.{}", path, name) };
+
+        if name == "Entities" {
+            let items = class.iter().find_map(|(name, entry)| match entry {
+                ConfigEntry::IntEntry(items) if name == "items" => Some(*items),
+                _ => None,
+            });
+            let classes = class
+                .iter()
+                .filter(|(_, entry)| matches!(entry, ConfigEntry::ClassEntry(_)))
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+
+            if items != Some(classes.len() as i32) {
+                return Err(format!("{} has items = {:?} but {} item classes", path, items, classes.len()));
+            }
+
+            if let Some((idx, name)) = classes.iter().enumerate().find(|(idx, name)| **name != format!("Item{}", idx)) {
+                return Err(format!("{}.{} should be named Item{}", path, name, idx));
+            }
+        }
+
+        validate_entities(class, &path)?;
+    }
+
+    Ok(())
+}
+
+/// Replace the entry called `name`, keeping its place, or append it if there is none. Entries
+/// stay in template order so generated missions are stable between builds.
+fn set_entry(entries: &mut EntryList, name: &str, entry: ConfigEntry) {
+    match entries.iter_mut().find(|(existing, _)| existing == name) {
+        Some((_, existing)) => *existing = entry,
+        None => entries.push((name.to_string(), entry)),
+    }
+}
+
+/// Concatenate the item lists of several compositions into a single `Entities` body,
+/// renumbering the `ItemN` classes so indices stay unique and updating the `items` count.
+fn concat_items(lists: Vec<EntryList>) -> EntryList {
+    let classes: Vec<ConfigEntry> = lists
+        .into_iter()
+        .flatten()
+        .filter_map(|(_, entry)| match entry {
+            ConfigEntry::ClassEntry(_) => Some(entry),
+            _ => None,
+        })
+        .collect();
+
+    let mut entries = vec![("items".to_string(), ConfigEntry::IntEntry(classes.len() as i32))];
+
+    entries.extend(
+        classes
+            .into_iter()
+            .enumerate()
+            .map(|(idx, class)| (format!("Item{}", idx), class)),
+    );
+
+    entries
+}
+
+/// Composition objects to keep, from `include_classes` and `exclude_classes`. Classes are
+/// matched case-insensitively, like Arma does.
+#[derive(Clone, Debug, Default)]
+struct ClassFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl ClassFilter {
+    fn keeps(&self, class: &str) -> bool {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let matches = |patterns: &[Pattern]| patterns.iter().any(|pattern| pattern.matches_with(class, options));
+
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+
+    /// Drop the objects this filter leaves out of `items`, including group members. Groups
+    /// without units and empty layers are dropped as well. Every `Entities` class is renumbered
+    /// with a matching `items` count, so the SQM stays valid.
+    fn apply(&self, items: EntryList) -> EntryList {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return items;
+        }
+
+        let kept = items
+            .into_iter()
+            .filter_map(|(name, entry)| {
+                let mut class = match entry {
+                    ConfigEntry::ClassEntry(class) => class,
+                    _ => return None,
+                };
+                let entries = class.entries.take().unwrap_or_default();
+
+                let kind = data_type(&entries).map(str::to_string);
+
+                let entries = match kind.as_deref() {
+                    Some("Object") => {
+                        let object_type = entries.iter().find_map(|(name, entry)| match entry {
+                            ConfigEntry::StringEntry(object_type) if name == "type" => Some(object_type.clone()),
+                            _ => None,
+                        });
+
+                        if let Some(object_type) = object_type.filter(|object_type| !self.keeps(object_type)) {
+                            debug!("Leaving out {}", object_type);
+                            return None;
+                        }
+
+                        entries
+                    }
+                    Some(kind @ ("Group" | "Layer")) => {
+                        let entries = self.apply_to_entities(entries);
+                        let members = get_class(&entries, "Entities").unwrap_or_default();
+
+                        let keep = if kind == "Group" {
+                            members.iter().any(|(_, member)| match member {
+                                ConfigEntry::ClassEntry(member) => {
+                                    data_type(member.entries.as_deref().unwrap_or_default()) == Some("Object")
+                                }
+                                _ => false,
+                            })
+                        } else {
+                            count_objects(&members) > 0
+                        };
+
+                        if !keep {
+                            return None;
+                        }
+
+                        entries
+                    }
+                    _ => entries,
+                };
+
+                class.entries = Some(entries);
+                Some((name, ConfigEntry::ClassEntry(class)))
+            })
+            .collect();
+
+        concat_items(vec![kept])
+    }
+
+    /// `apply` to the `Entities` class of a group or layer
+    fn apply_to_entities(&self, entries: EntryList) -> EntryList {
+        entries
+            .into_iter()
+            .map(|(name, entry)| match entry {
+                ConfigEntry::ClassEntry(mut entities) if name == "Entities" => {
+                    entities.entries = entities.entries.map(|items| self.apply(items));
+                    (name, ConfigEntry::ClassEntry(entities))
+                }
+                entry => (name, entry),
+            })
+            .collect()
+    }
+}
+
+/// Copy already placed items to every cell of the grid. Each copy gets its own range of Eden
+/// ids so they stay unique within the mission.
+fn repeat_items(lists: Vec<EntryList>, repeat: RepeatConfig) -> Vec<EntryList> {
+    let id_range = lists.iter().filter_map(|items| max_id(items)).max().map_or(0, |id| id + 1);
+
+    repeat
+        .offsets()
+        .into_iter()
+        .enumerate()
+        .flat_map(|(copy, offset)| {
+            let placement = Placement {
+                offset,
+                rotation: 0.,
+                scale: 1.,
+            };
+
+            lists
+                .iter()
+                .map(move |items| shift_ids(offset_classes(items.clone(), placement), id_range * copy as i32))
+        })
+        .collect()
+}
+
+/// Add `by` to every Eden `id` in `entries`, including group members
+fn shift_ids(entries: EntryList, by: i32) -> EntryList {
+    entries
+        .into_iter()
+        .map(|(name, entry)| {
+            let entry = match entry {
+                ConfigEntry::IntEntry(id) if name == "id" => ConfigEntry::IntEntry(id + by),
+                ConfigEntry::ClassEntry(mut class) => {
+                    class.entries = class.entries.map(|entries| shift_ids(entries, by));
+                    ConfigEntry::ClassEntry(class)
+                }
+                entry => entry,
+            };
+
+            (name, entry)
+        })
+        .collect()
+}
+
+/// Highest Eden `id` used by any item in `entries`, including group members
+fn max_id(entries: &[(String, ConfigEntry)]) -> Option<i32> {
+    entries
+        .iter()
+        .filter_map(|(name, entry)| match entry {
+            ConfigEntry::IntEntry(id) if name == "id" => Some(*id),
+            ConfigEntry::ClassEntry(class) => class.entries.as_ref().and_then(|entries| max_id(entries)),
+            _ => None,
+        })
+        .max()
+}
+
+/// Spacing, in meters, between playable units of a slot group
+const SLOT_SPACING: f32 = 2.;
+
+/// Render each slot as a group of playable units at `origin` plus the slot's offset, numbering
+/// groups and units from `first_id`.
+fn slot_groups(slots: &[SlotConfig], origin: (f32, f32, f32), first_id: i32) -> Result<EntryList> {
+    let mut id = first_id;
+    let mut groups = Vec::new();
+
+    for (idx, slot) in slots.iter().enumerate() {
+        let side = slot.sqm_side()?;
+        let (x, z, y) = (origin.0 + slot.offset.0, origin.1 + slot.offset.1, origin.2 + slot.offset.2);
+        let group_id = id;
+
+        let units = (0..slot.count)
+            .map(|unit| {
+                format!(
+                    r#"class Item{} {{
+                        dataType = "Object";
+                        class PositionInfo {{ position[] = {{{:?}, {:?}, {:?}}}; }};
+                        side = "{}";
+                        flags = {};
+                        class Attributes {{ isPlayable = 1; }};
+                        id = {};
+                        type = "{}";
+                    }};"#,
+                    unit,
+                    x + unit as f32 * SLOT_SPACING,
+                    z,
+                    y,
+                    side,
+                    // The first unit leads the group
+                    if unit == 0 { 7 } else { 5 },
+                    group_id + 1 + unit as i32,
+                    slot.unit_class
+                )
+            })
+            .collect::<Vec<_>>();
+
+        id += 1 + slot.count as i32;
+
+        let group = format!(
+            r#"class Item{} {{
+                dataType = "Group";
+                side = "{}";
+                class Entities {{
+                    items = {};
+                    {}
+                }};
+                class Attributes {{}};
+                id = {};
+            }};"#,
+            idx,
+            side,
+            units.len(),
+            units.join("\n"),
+            group_id
+        );
+
+        let group = Config::from_string(group, None, &[])?;
+        groups.extend(group.into_inner().entries.unwrap_or_default());
+    }
+
+    Ok(groups)
+}
+
+/// Render each respawn position as a marker at `origin` plus its offset, numbering them from
+/// `first_id`. Marker names must be unique, so unnamed markers of the same side are numbered.
+fn respawn_markers(positions: &[RespawnPositionConfig], origin: (f32, f32, f32), first_id: i32) -> Result<EntryList> {
+    let mut names: Vec<String> = Vec::new();
+    let mut markers = Vec::new();
+
+    for (idx, position) in positions.iter().enumerate() {
+        let (marker, color) = respawn_marker(sqm_side(&position.side, "respawn_positions")?);
+
+        let name = match &position.name {
+            Some(name) if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+                return Err(invalid_setting(
+                    "respawn_positions",
+                    format!("Invalid respawn marker name {:?}, expected letters, digits and _", name),
+                ))
+            }
+            Some(name) if name.to_lowercase().starts_with(marker) => name.clone(),
+            Some(name) => {
+                return Err(invalid_setting(
+                    "respawn_positions",
+                    format!("Respawn marker {} for {} has to start with {}", name, position.side, marker),
+                ))
+            }
+            // The first of `marker`, `marker_1`, `marker_2`, ... that isn't taken yet
+            None => std::iter::once(marker.to_string())
+                .chain((1..).map(|n| format!("{}_{}", marker, n)))
+                .find(|candidate| !names.iter().any(|taken| taken.eq_ignore_ascii_case(candidate)))
+                .unwrap_or_default(),
+        };
+
+        if names.iter().any(|taken| taken.eq_ignore_ascii_case(&name)) {
+            return Err(invalid_setting(
+                "respawn_positions",
+                format!("Respawn marker {} is used more than once", name),
+            ));
+        }
+
+        let (x, z, y) = (origin.0 + position.offset.0, origin.1 + position.offset.1, origin.2 + position.offset.2);
+        let marker = format!(
+            r#"class Item{} {{
+                dataType = "Marker";
+                position[] = {{{:?}, {:?}, {:?}}};
+                name = "{}";
+                type = "respawn_inf";
+                colorName = "{}";
+                id = {};
+            }};"#,
+            idx,
+            x,
+            z,
+            y,
+            name,
+            color,
+            first_id + idx as i32
+        );
+
+        markers.extend(Config::from_string(marker, None, &[])?.into_inner().entries.unwrap_or_default());
+        names.push(name);
+    }
+
+    Ok(markers)
+}
+
+#[derive(Serialize)]
+struct Addon {
+    prefix: String,
+    version: Option<String>,
+    addon_name: String,
+    /// Missions without a category
+    missions: Vec<MissionClass>,
+    categories: Vec<MissionCategory>,
+}
+
+impl Addon {
+    /// Collect the CfgMissions classes of an addon. Each mission comes with the path of its
+    /// `mission.sqm` in the addon, whose folder becomes the class's `directory`. Missions with a
+    /// category are nested under a class for it, sorted by category name.
+    pub fn from_parts(
+        prefix: String,
+        version: Option<String>,
+        addon_name: String,
+        missions_subfolder: &str,
+        attributes: Vec<MissionAttribute>,
+        missions: Vec<(PathBuf, Mission)>,
+    ) -> Result<Self> {
+        let mut uncategorized = Vec::new();
+        let mut categories: BTreeMap<String, Vec<MissionClass>> = BTreeMap::new();
+
+        for (path, mission) in missions {
+            let folder = path
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .ok_or_else(|| {
+                    format!(
+                        "Mission {} has no folder in its path {}, expected <mission folder>/mission.sqm",
+                        mission.class_name(),
+                        path.display()
+                    )
+                })?;
+
+            let directory = format!(
+                r"{}\{}\{}\{}",
+                prefix,
+                addon_name,
+                missions_subfolder.replace('/', r"\"),
+                folder.to_string_lossy()
+            );
+
+            let class = MissionClass {
+                briefing_name: format!("[{}] {}", prefix, mission.class_name()),
+                class_name: mission.class_name(),
+                directory,
+                attributes: attributes.clone(),
+            };
+
+            match mission.category {
+                Some(category) => categories.entry(category).or_default().push(class),
+                None => uncategorized.push(class),
+            }
+        }
+
+        let categories = categories
+            .into_iter()
+            .map(|(name, missions)| MissionCategory {
+                class_name: category_class_name(&prefix, &name),
+                name,
+                missions,
+            })
+            .collect();
+
+        Ok(Addon {
+            prefix,
+            version,
+            addon_name,
+            missions: uncategorized,
+            categories,
+        })
+    }
+}
+
+/// A class in CfgMissions grouping missions, from `category`
+#[derive(Serialize)]
+struct MissionCategory {
+    class_name: String,
+    /// Shown as the category's `briefingName`
+    name: String,
+    missions: Vec<MissionClass>,
+}
+
+#[derive(Serialize)]
+struct MissionClass {
+    class_name: String,
+    briefing_name: String,
+    directory: String,
+    attributes: Vec<MissionAttribute>,
+}
+
+/// An extra entry in a mission's CfgMissions class, from `mission_attributes`
+#[derive(Clone, Debug, Serialize)]
+struct MissionAttribute {
+    /// Entry name, ending in `[]` for arrays
+    name: String,
+    /// The value as written in config.cpp
+    value: String,
+}
+
+impl MissionAttribute {
+    /// An entry for the `setting` table (i.e. `mission_attributes`), in config syntax
+    fn new(setting: &str, name: &str, value: &toml::Value) -> Result<Self> {
+        let is_identifier = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && name.chars().next().is_some_and(|c| !c.is_ascii_digit());
+
+        if !is_identifier {
+            return Err(invalid_setting(
+                setting,
+                format!("Invalid attribute name {:?} in {}, expected letters, digits and _", name, setting),
+            ));
+        }
+
+        let name = match value {
+            toml::Value::Array(_) => format!("{}[]", name),
+            _ => name.to_string(),
+        };
+
+        Ok(MissionAttribute {
+            value: cfg_value(setting, &name, value)?,
+            name,
+        })
+    }
+}
+
+/// Format a TOML value as a config.cpp value
+fn cfg_value(setting: &str, name: &str, value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(string) => format!("\"{}\"", string.replace('"', "\"\"")),
+        toml::Value::Integer(int) => int.to_string(),
+        toml::Value::Float(float) => float.to_string(),
+        toml::Value::Boolean(boolean) => (*boolean as u8).to_string(),
+        toml::Value::Datetime(datetime) => format!("\"{}\"", datetime),
+        toml::Value::Array(values) => format!(
+            "{{{}}}",
+            values.iter().map(|value| cfg_value(setting, name, value)).collect::<Result<Vec<_>>>()?.join(", ")
+        ),
+        toml::Value::Table(_) => {
+            return Err(invalid_setting(
+                setting,
+                format!("Attribute {} in {} can't be a table", name, setting),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(class: &str) -> (String, ConfigEntry) {
+        (
+            "Item0".to_string(),
+            ConfigEntry::ClassEntry(ConfigClass {
+                parent: String::new(),
+                is_external: false,
+                is_deletion: false,
+                entries: Some(vec![("type".to_string(), ConfigEntry::StringEntry(class.to_string()))]),
+            }),
+        )
+    }
+
+    fn settings(toml: &str) -> MissionSettings {
+        let base = r#"
+            composition = "./composition"
+            ignore_center = false
+            missions_folder = "./missions"
+        "#;
+
+        toml::from_str(&format!("{}\n{}", base, toml)).unwrap()
+    }
+
+    fn test_mission(settings: &MissionSettings) -> Result<Mission> {
+        test_mission_on(settings, MapConfig::Map("VR".to_string()))
+    }
+
+    fn test_mission_on(settings: &MissionSettings, map: MapConfig) -> Result<Mission> {
+        let config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        Mission::new(
+            &create_project_handlebars(&config)?,
+            config.namespaced_prefix(MISSION_SETTINGS_KEY),
+            "Test".to_string(),
+            map,
+            settings,
+            &config,
+        )
+    }
+
+    fn test_composition(sqe: &str) -> Result<Composition> {
+        let sqe = Config::from_string(sqe.to_string(), None, &[])?.into_inner().entries.unwrap();
+
+        Ok(Composition {
+            path: PathBuf::from("composition"),
+            header: None,
+            center: get_center(&sqe),
+            items: get_items(&sqe),
+            placed_items: None,
+            offset: (0., 0., 0.),
+            local_offset: (0., 0., 0.),
+            rotation: 0.,
+            scale: 1.,
+            ignore_center: false,
+            entry: None,
+        })
+    }
+
+    #[test]
+    fn test_validate_maps() {
+        assert!(settings(r#"maps = ["Altis", "tanoa"]"#).validate_maps().is_ok());
+        assert!(settings(r#"maps = ["Altis3"]"#).validate_maps().is_err());
+        assert!(settings(r#"
+            maps = [["Chernarus", [0, 0, 0]]]
+            extra_maps = ["Chernarus"]
+        "#)
+        .validate_maps()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_respawn_type() {
+        assert_eq!(settings("maps = []").respawn().unwrap(), 3);
+        assert_eq!(settings(r#"
+            maps = []
+            respawn_type = "instant"
+        "#).respawn().unwrap(), 2);
+        assert!(settings(r#"
+            maps = []
+            respawn_type = "WAVE"
+        "#).respawn().is_err());
+    }
+
+    #[test]
+    fn test_scripts() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("scripts"))?;
+        std::fs::write(dir.path().join("init.sqf"), "hint \"init\";")?;
+        std::fs::write(dir.path().join("scripts/fn_setup.sqf"), "true")?;
+
+        let scripts_dir = format!("maps = []\nscripts_dir = '{}'", dir.path().display());
+        let scripts = settings(&scripts_dir).scripts()?;
+        let paths = scripts.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![PathBuf::from("init.sqf"), Path::new("scripts").join("fn_setup.sqf")]);
+        assert_eq!(scripts[0].1, b"hint \"init\";");
+
+        assert!(settings("maps = []").scripts()?.is_empty());
+        dir.close()?;
+        assert!(settings(&scripts_dir).scripts().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_intel() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("overview.paa"), b"paa")?;
+        std::fs::write(dir.path().join("overview.png"), b"png")?;
+
+        let with_picture = |picture: &str| {
+            settings(&format!(
+                "maps = ['VR']\n{}\nloading_text = 'Loading'\npicture = '{}'",
+                "overview_text = '''Hold the \"line\"\n  until dawn'''",
+                dir.path().join(picture).display()
+            ))
+        };
+        let mission_settings = with_picture("overview.paa");
+        let picture = mission_settings.picture();
+        let png = with_picture("overview.png").validate_picture();
+        let missing = with_picture("missing.paa").validate_picture();
+
+        assert_eq!(picture?, Some((PathBuf::from("overview.paa"), b"paa".to_vec())));
+        assert!(png.is_err());
+        assert!(missing.is_err());
+
+        let mut mission = test_mission(&mission_settings)?;
+        mission.merge_compositions(&[])?;
+
+        let sqm = Config::from_string(mission.to_sqm()?, None, &[])?.into_inner().entries.unwrap();
+        let string = |entries: &EntryList, name: &str| {
+            entries.iter().find(|(key, _)| key == name).and_then(|(_, entry)| match entry {
+                ConfigEntry::StringEntry(value) => Some(value.clone()),
+                _ => None,
+            })
+        };
+
+        let scenario = get_class(&sqm, "ScenarioData").unwrap();
+        assert_eq!(string(&scenario, "overviewText").as_deref(), Some("Hold the \"line\" until dawn"));
+        assert_eq!(string(&scenario, "overviewPicture").as_deref(), Some("overview.paa"));
+        assert_eq!(string(&scenario, "loadScreen").as_deref(), Some("overview.paa"));
+        assert_eq!(string(&scenario, "onLoadMission").as_deref(), Some("Loading"));
+
+        let intel = get_class(&sqm, "Mission").and_then(|mission| get_class(&mission, "Intel")).unwrap();
+        assert_eq!(string(&intel, "overviewText").as_deref(), Some("Hold the \"line\" until dawn"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missions_subfolder() -> Result<()> {
+        for valid in ["missions", "data/missions"] {
+            let mission_settings = settings(&format!("maps = ['VR']\nmissions_subfolder = '{}'", valid));
+            assert!(mission_settings.validate_missions_subfolder().is_ok());
+        }
+        for invalid in ["", "../missions", "/missions", "data\\missions"] {
+            let mission_settings = settings(&format!("maps = ['VR']\nmissions_subfolder = '{}'", invalid));
+            assert!(mission_settings.validate_missions_subfolder().is_err());
+        }
+
+        let mission_settings = settings("maps = ['VR']\nmissions_subfolder = 'data/missions'");
+        let mission = || test_mission(&mission_settings);
+
+        let folder = mission_settings.mission_folder(&mission()?);
+        assert_eq!(folder, Path::new("data/missions").join("LAAT_missions_VRTest.VR"));
+
+        let addon = Addon::from_parts(
+            "LAAT".to_string(),
+            None,
+            "Missions".to_string(),
+            &mission_settings.missions_subfolder,
+            vec![],
+            vec![(folder.join("mission.sqm"), mission()?)],
+        )?;
+        assert_eq!(addon.missions[0].directory, r"LAAT\Missions\data\missions\LAAT_missions_VRTest.VR");
+
+        let err = Addon::from_parts(
+            "LAAT".to_string(),
+            None,
+            "Missions".to_string(),
+            &mission_settings.missions_subfolder,
+            vec![],
+            vec![(PathBuf::from("mission.sqm"), mission()?)],
+        );
+        assert!(err.err().unwrap().to_string().starts_with("Mission LAAT_missions_VRTest has no folder"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_required_addons() -> Result<()> {
+        let mission_settings = settings(
+            r#"
+                maps = ["VR"]
+                slots = [{ side = "west", count = 2, unit_class = "rhsusf_army_ocp_rifleman" }]
+                required_addons = ["A3_Characters_F"]
+                class_addons = { RHSUSF_army_ocp_rifleman = "rhsusf_infantry", Land_Tank = "tank_mod" }
+            "#,
+        );
+
+        let mut mission = test_mission(&mission_settings)?;
+        mission.merge_compositions(&[])?;
+
+        let sqm = Config::from_string(mission.to_sqm()?, None, &[])?.into_inner().entries.unwrap();
+        let addons = sqm.iter().find_map(|(name, entry)| match entry {
+            ConfigEntry::ArrayEntry(array) if name == "addons" => Some(array.elements.clone()),
+            _ => None,
+        });
+        let addons = addons
+            .unwrap()
+            .iter()
+            .map(|element| match element {
+                ConfigArrayElement::StringElement(addon) => addon.clone(),
+                _ => String::new(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(addons, vec!["A3_Characters_F", "rhsusf_infantry"]);
+
+        let list = get_class(&sqm, "AddonsMetaData").and_then(|metadata| get_class(&metadata, "List")).unwrap();
+        assert!(matches!(list[0].1, ConfigEntry::IntEntry(2)));
+        assert_eq!(list.len(), 3);
+
+        assert!(mission_settings.validate_required_addons().is_ok());
+        assert!(settings("maps = []\nrequired_addons = ['bad\"addon']").validate_required_addons().is_err());
+        assert!(settings("maps = []\nclass_addons = { Land_Tank = '' }").validate_required_addons().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_class_names() -> Result<()> {
+        let mission_settings = settings(r#"maps = ["Altis", "VR", "altis"]"#);
+        let mut missions = mission_settings
+            .maps
+            .iter()
+            .cloned()
+            .map(|map| test_mission_on(&mission_settings, map))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert!(check_class_names(&missions[..2], "LAAT").is_ok());
+
+        let err = check_class_names(&missions, "LAAT").unwrap_err();
+        assert!(matches!(&err, LaatError::InvalidConfig { key, .. } if key == "missions.maps"));
+        assert!(err.to_string().contains("Altis and altis both generate LAAT_missions_altisTest"));
+
+        missions.truncate(2);
+        missions[0].category = Some("Altis Ops".to_string());
+        missions[1].category = Some("Altis Ops".to_string());
+        assert!(check_class_names(&missions, "LAAT").is_ok());
+
+        missions[1].category = Some("altis-ops".to_string());
+        let err = check_class_names(&missions, "LAAT").unwrap_err();
+        assert!(matches!(&err, LaatError::InvalidConfig { key, .. } if key == "missions.category"));
+        assert!(err
+            .to_string()
+            .contains(r#"category "Altis Ops" and category "altis-ops" both generate LAAT_altis_ops"#));
+
+        missions[1].category = Some("missions_VRTest".to_string());
+        let err = check_class_names(&missions, "LAAT").unwrap_err();
+        assert!(err.to_string().contains(r#"VR and category "missions_VRTest" both generate LAAT_missions_VRTest"#));
+
+        // Compared as written to CfgMissions, after sanitizing
+        let mut zeus = vec![test_mission(&mission_settings)?, test_mission(&mission_settings)?];
+        zeus[0].mission_name = "Zeus-Mission".to_string();
+        zeus[1].mission_name = "Zeus_Mission".to_string();
+        let err = check_class_names(&zeus, "LAAT").unwrap_err();
+        assert!(err.to_string().contains("both generate LAAT_missions_VRZeus_Mission"), "{}", err);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_without_composition() -> Result<()> {
+        let mut config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        config.set_plugin_settings(
+            MISSION_SETTINGS_KEY,
+            toml::toml! {
+                maps = ["VR"]
+                ignore_center = false
+                missions_folder = "./missions"
+            },
+        )?;
+
+        let mission_settings = MissionSettings::from_build_config(&config)?;
+        assert!(mission_settings.compositions().is_empty());
+        assert!(load_compositions(&mission_settings, &config).await?.is_empty());
+        assert!(mission_settings.missing_files().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_class_filter() -> Result<()> {
+        let items = r#"
+            class items {
+                items = 3;
+                class Item0 {
+                    dataType = "Group";
+                    class Entities {
+                        items = 3;
+                        class Item0 { dataType = "Object"; type = "B_Soldier_F"; };
+                        class Item1 { dataType = "Object"; type = "B_MRAP_01_F"; };
+                        class Item2 { dataType = "Waypoint"; };
+                    };
+                };
+                class Item1 {
+                    dataType = "Group";
+                    class Entities {
+                        items = 2;
+                        class Item0 { dataType = "Object"; type = "B_MRAP_01_hmg_F"; };
+                        class Item1 { dataType = "Waypoint"; };
+                    };
+                };
+                class Item2 { dataType = "Object"; type = "Land_Cargo_HQ_V1_F"; };
+                class Item3 { dataType = "Marker"; type = "mil_dot"; };
+            };
+        "#;
+        let items = Config::from_string(items.to_string(), None, &[])?.into_inner().entries.unwrap();
+        let items = get_items(&items).unwrap();
+
+        let filter = settings("maps = ['VR']\nexclude_classes = ['b_mrap_01_*']").class_filter()?;
+        let filtered = filter.apply(items.clone());
+        let entities = vec![(
+            "Entities".to_string(),
+            ConfigEntry::ClassEntry(ConfigClass {
+                parent: String::new(),
+                is_external: false,
+                is_deletion: false,
+                entries: Some(filtered.clone()),
+            }),
+        )];
+
+        // The group of only a vehicle is gone, the other group keeps its soldier and waypoint
+        validate_entities(&entities, "")?;
+        assert_eq!(count_objects(&filtered), 4);
+        let mut types = Vec::new();
+        collect_types(&filtered, &mut types);
+        assert_eq!(types, vec!["B_Soldier_F", "Land_Cargo_HQ_V1_F", "mil_dot"]);
+
+        // Markers aren't objects, so include_classes keeps them
+        let filter = settings("maps = ['VR']\ninclude_classes = ['Land_*']").class_filter()?;
+        let mut types = Vec::new();
+        collect_types(&filter.apply(items.clone()), &mut types);
+        assert_eq!(types, vec!["Land_Cargo_HQ_V1_F", "mil_dot"]);
+
+        assert_eq!(count_objects(&ClassFilter::default().apply(items.clone())), count_objects(&items));
+        assert!(settings("maps = ['VR']\nexclude_classes = ['[']").class_filter().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scenario_attributes() -> Result<()> {
+        let mission_settings = settings(
+            r#"
+                maps = ["VR"]
+
+                [scenario_attributes]
+                disabledai = false
+                joinUnassigned = true
+                aiKills = 1
+                briefingName = "Op \"Dawn\""
+                respawnTemplates = ["MenuPosition", "Tickets"]
+            "#,
+        );
+
+        let mut mission = test_mission(&mission_settings)?;
+        mission.merge_compositions(&[])?;
+
+        let sqm = mission.to_sqm()?;
+        let entries = Config::from_string(sqm.clone(), None, &[])?.into_inner().entries.unwrap();
+        let scenario = get_class(&entries, "ScenarioData").unwrap();
+        let entry = |name: &str| {
+            scenario.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, entry)| entry.clone())
+        };
+
+        // Replaces the generated disabledAI instead of adding a second one
+        assert_eq!(scenario.iter().filter(|(key, _)| key.eq_ignore_ascii_case("disabledAI")).count(), 1);
+        assert!(matches!(entry("disabledAI"), Some(ConfigEntry::IntEntry(0))));
+        assert!(matches!(entry("joinUnassigned"), Some(ConfigEntry::IntEntry(1))));
+        assert!(matches!(entry("briefingName"), Some(ConfigEntry::StringEntry(name)) if name == "Op \"Dawn\""));
+        assert!(matches!(entry("respawnTemplates"), Some(ConfigEntry::ArrayEntry(array)) if array.elements.len() == 2));
+        assert!(sqm.contains("respawnTemplates[]"), "{}", sqm);
+
+        assert!(settings("maps = ['VR']\nscenario_attributes = { 'bad name' = 1 }").scenario_attributes().is_err());
+        let nested = settings("maps = ['VR']\nscenario_attributes = { header = { gameType = 'Coop' } }");
+        assert!(nested.scenario_attributes().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_categories() -> Result<()> {
+        let mission_settings = settings(r#"
+            maps = ["Altis", "Stratis", "VR"]
+            category = "{{map}} Ops"
+            map_categories = { vr = "Training & Drills" }
+        "#);
+        assert_eq!(mission_settings.category_for("Altis").as_deref(), Some("Altis Ops"));
+        assert_eq!(mission_settings.category_for("VR").as_deref(), Some("Training & Drills"));
+        assert!(mission_settings.validate_categories().is_ok());
+        assert!(settings("maps = ['VR']\ncategory = 'The \"Best\"'").validate_categories().is_err());
+        assert_eq!(settings("maps = ['VR']").category_for("VR"), None);
+
+        let config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        let handlebars = create_project_handlebars(&config)?;
+        let mission = |map: &str| -> Result<(PathBuf, Mission)> {
+            let mission = test_mission_on(&mission_settings, MapConfig::Map(map.to_string()))?;
+            Ok((mission_settings.mission_folder(&mission).join("mission.sqm"), mission))
+        };
+
+        let addon = Addon::from_parts(
+            "LAAT".to_string(),
+            None,
+            "Missions".to_string(),
+            &mission_settings.missions_subfolder,
+            vec![],
+            vec![mission("VR")?, mission("Altis")?],
+        )?;
+        assert!(addon.missions.is_empty());
+        let names = addon.categories.iter().map(|category| category.class_name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["LAAT_Altis_Ops", "LAAT_Training___Drills"]);
+
+        let config_cpp = render_template(&handlebars, "missions_addon", &addon)?;
+        let config_cpp = config_cpp.split_whitespace().collect::<Vec<_>>().join(" ");
+        let category = r#"class LAAT_Training___Drills { briefingName = "Training & Drills"; class LAAT_missions_VRTest {"#;
+        assert!(config_cpp.contains(category), "{}", config_cpp);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_addon_names() {
+        let single = settings(r#"maps = ["Altis", "VR"]"#);
+        assert_eq!(single.addon_names(), vec!["Missions"]);
+
+        let split = settings(r#"
+            maps = ["Altis", "Stratis", "Tanoa", "VR"]
+            addon_name = "Missions_{{map}}"
+            map_addons = { altis = "Missions_Mediterranean", Stratis = "Missions_Mediterranean" }
+        "#);
+        assert_eq!(split.addon_name_for("VR"), "Missions_VR");
+        assert_eq!(split.addon_name_for("Altis"), "Missions_Mediterranean");
+        assert_eq!(split.addon_names(), vec!["Missions_Mediterranean", "Missions_Tanoa", "Missions_VR"]);
+        assert!(split.validate_addon_names().is_ok());
+
+        let invalid = settings(r#"
+            maps = ["VR"]
+            map_addons = { VR = "Missions/VR" }
+        "#);
+        assert!(invalid.validate_addon_names().is_err());
+    }
+
+    #[test]
+    fn test_mission_attributes() -> Result<()> {
+        let attributes = settings(
+            r#"
+                maps = ["VR"]
+
+                [mission_attributes]
+                gameType = "Coop"
+                minPlayers = 1
+                respawnTemplates = ["Tickets", "Counter"]
+                title = 'Say "hi"'
+                debug = true
+            "#,
+        )
+        .mission_attributes()?;
+
+        let attributes = attributes
+            .iter()
+            .map(|attribute| format!("{} = {};", attribute.name, attribute.value))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            attributes,
+            vec![
+                "debug = 1;",
+                "gameType = \"Coop\";",
+                "minPlayers = 1;",
+                "respawnTemplates[] = {\"Tickets\", \"Counter\"};",
+                "title = \"Say \"\"hi\"\"\";",
+            ]
+        );
+
+        let invalid = settings("maps = [\"VR\"]\nmission_attributes = { \"min players\" = 1 }");
+        assert!(invalid.mission_attributes().is_err());
+        assert!(settings("maps = [\"VR\"]\nmission_attributes = { header = { a = 1 } }").mission_attributes().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_environment() {
+        let night = settings(r#"
+            maps = []
+            time_of_day = 21.5
+            fog = 0.3
+        "#);
+
+        assert!(night.validate_environment().is_ok());
+        assert_eq!(night.start_time(), (21, 30));
+        assert_eq!(settings("maps = []").start_time(), (14, 20));
+
+        assert!(settings("maps = []\ntime_of_day = 25").validate_environment().is_err());
+        assert!(settings("maps = []\nrain = -0.1").validate_environment().is_err());
+        assert!(settings("maps = []\novercast = 1.5").validate_environment().is_err());
+    }
+
+    #[test]
+    fn test_respawn_delay() {
+        assert_eq!(settings("maps = []").respawn_delay, 2.);
+        assert_eq!(settings("maps = []\nrespawn_delay = 0").respawn_delay, 0.);
+        assert_eq!(settings("maps = []\nrespawn_delay = 0.5").respawn_delay, 0.5);
+
+        let mut config = crate::LaatConfig::new("LAAT", "LAAT Test Mod").unwrap();
+        config
+            .set_plugin_settings(MISSION_SETTINGS_KEY, toml::toml! {
+                maps = []
+                composition = "./composition"
+                ignore_center = false
+                missions_folder = "./missions"
+                respawn_delay = -1
+            })
+            .unwrap();
+
+        assert!(matches!(
+            MissionSettings::from_build_config(&config),
+            Err(LaatError::InvalidConfig { key, .. }) if key == "missions.respawn_delay"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_rapified_config() -> Result<()> {
+        let config = Config::from_string("center[]={1,2,3};".to_string(), None, &Vec::new())?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("rapified.sqe");
+        std::fs::write(&path, config.to_cursor()?.into_inner())?;
+
+        let config = read_config(path.clone(), 0).await?;
+
+        assert_eq!(config.to_string()?, "center[] = {1, 2, 3};\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_sqm_entities() -> Result<()> {
+        let sqm = r#"
+            version = 54;
+            class Mission {
+                class Entities {
+                    items = 2;
+                    class Item0 { dataType = "Marker"; position[] = {100, 0, 200}; };
+                    class Item1 { dataType = "Object"; class PositionInfo { position[] = {110, 5, 220}; }; };
+                };
+            };
+        "#;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("entities.sqm");
+        std::fs::write(&path, sqm)?;
+
+        let (center, items) = read_sqm_entities(&path, 0).await?;
+
+        let mut positions = Vec::new();
+        collect_positions(&items, &mut positions);
+
+        assert_eq!(center, (105., 0., 210.));
+        assert_eq!(positions, vec![(-5., 0., -10.), (5., 5., 10.)]);
+        assert_eq!(count_objects(&items), 2);
+
+        Ok(())
+    }
+
+    fn class(entries: EntryList) -> ConfigEntry {
+        ConfigEntry::ClassEntry(ConfigClass {
+            parent: String::new(),
+            is_external: false,
+            is_deletion: false,
+            entries: Some(entries),
+        })
+    }
+
+    /// ConfigArray can't be constructed outside of armake2, so parse one instead
+    fn float_array(values: &[f32]) -> ConfigEntry {
+        let values: Vec<String> = values.iter().map(|v| format!("{:?}", v)).collect();
+        let config = Config::from_string(format!("array[]={{{}}};", values.join(",")), None, &Vec::new()).unwrap();
+
+        config.into_inner().entries.unwrap().remove(0).1
+    }
+
+    fn floats(entry: &ConfigEntry) -> Vec<f32> {
+        match entry {
+            ConfigEntry::ArrayEntry(array) => array.elements.iter().filter_map(element_value).collect(),
+            _ => panic!("Not an array: {:?}", entry),
+        }
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_offset_marker() {
+        let marker = vec![
+            ("dataType".to_string(), ConfigEntry::StringEntry("Marker".to_string())),
+            ("position".to_string(), float_array(&[1., 0., 0.])),
+            ("angle".to_string(), ConfigEntry::FloatEntry(10.)),
+        ];
+
+        let placement = Placement {
+            offset: (10., 0., 10.),
+            rotation: 90.,
+            scale: 1.,
+        };
+
+        let entries = offset_classes(vec![("Item0".to_string(), class(marker))], placement);
+
+        let marker = match &entries[0].1 {
+            ConfigEntry::ClassEntry(class) => class.entries.clone().unwrap(),
+            _ => unreachable!(),
+        };
+
+        assert_close(&floats(&marker[1].1), &[10., 0., 9.]);
+        assert!(matches!(marker[2].1, ConfigEntry::FloatEntry(angle) if (angle - 100.).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_composition_metadata() -> Result<()> {
+        let sqe = r#"
+            center[] = {100, 5, 200};
+            class items {
+                items = 2;
+                class Item0 {
+                    dataType = "Group";
+                    class Entities {
+                        items = 2;
+                        class Item0 { dataType = "Object"; class PositionInfo { position[] = {1, 0, 2}; }; };
+                        class Item1 { dataType = "Object"; class PositionInfo { position[] = {4, 1, -2}; }; };
+                    };
+                };
+                class Item1 { dataType = "Marker"; position[] = {-3, 0, 6}; };
+            };
+        "#;
+
+        let composition = test_composition(sqe)?;
+
+        assert_eq!(composition.object_count(), 3);
+        assert_eq!(composition.bounds(), Some(((-3., 0., -2.), (4., 1., 6.))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_composition_with_bom() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("header.sqe"), b"\xEF\xBB\xBFversion = 54;")?;
+        std::fs::write(
+            dir.path().join("composition.sqe"),
+            [
+                &b"\xEF\xBB\xBF"[..],
+                br#"
+                    center[] = {100, 0, 200};
+                    class items {
+                        items = 1;
+                        class Item0 { dataType = "Marker"; text = "Caf\xC3\xA9"; position[] = {110, 0, 220}; };
+                    };
+                "#,
+            ]
+            .concat(),
+        )?;
+
+        let composition =
+            Composition::from_path(&dir.path().to_path_buf(), (0., 0., 0.), (0., 0., 0.), 0., 1., false, 0).await?;
+
+        assert_eq!(composition.get_center()?, (100., 0., 200.));
+        assert_eq!(composition.object_count(), 1);
+
+        let source = Path::new("composition.sqe");
+        let utf16 = "\u{FEFF}text = \"Café\";".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        assert_eq!(decode_config(source, &utf16), "text = \"Café\";");
+        assert_eq!(decode_config(source, b"text = \"Caf\xE9\";"), "text = \"Café\";");
+        assert_eq!(decode_config(source, "text = \"Café\";".as_bytes()), "text = \"Café\";");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zipped_composition() -> Result<()> {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("composition.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path)?);
+        let options = zip::write::FileOptions::default();
+
+        zip.start_file("Outpost/header.sqe", options)?;
+        zip.write_all(b"version = 54;")?;
+        zip.start_file("Outpost/composition.sqe", options)?;
+        zip.write_all(br#"
+            center[] = {100, 0, 200};
+            class items {
+                items = 1;
+                class Item0 { dataType = "Marker"; position[] = {110, 0, 220}; };
+            };
+        "#)?;
+        zip.finish()?;
+
+        let composition = Composition::from_path(&path, (0., 0., 0.), (0., 0., 0.), 0., 1., false, 0).await?;
+
+        assert_eq!(composition.get_center()?, (100., 0., 200.));
+        assert_eq!(composition.object_count(), 1);
+
+        let not_zip = dir.path().join("not_zip.zip");
+        std::fs::write(&not_zip, "composition.sqe")?;
+        let result = Composition::from_path(&not_zip, (0., 0., 0.), (0., 0., 0.), 0., 1., false, 0).await;
+        assert!(matches!(result, Err(LaatError::CompositionParse { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_composition_without_center() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("header.sqe"), "version = 54;")?;
+        std::fs::write(
+            dir.path().join("composition.sqe"),
+            r#"
+                class items {
+                    items = 2;
+                    class Item0 { dataType = "Marker"; position[] = {100, 0, 200}; };
+                    class Item1 { dataType = "Marker"; position[] = {110, 3, 220}; };
+                };
+            "#,
+        )?;
+
+        let composition =
+            Composition::from_path(&dir.path().to_path_buf(), (0., 0., 0.), (0., 0., 0.), 0., 1., false, 0).await?;
+
+        assert_eq!(composition.get_center()?, (105., 0., 210.));
+        assert_eq!(composition.bounds(), Some(((-5., 0., -10.), (5., 3., 10.))));
+
+        let positions = |items: EntryList| {
+            let mut positions = Vec::new();
+            collect_positions(&items, &mut positions);
+            positions
+        };
+        assert!(composition.placed_items.is_some());
+        assert_eq!(positions(composition.get_offseted_items(None)?), vec![(100., 0., 200.), (110., 3., 220.)]);
+        assert_eq!(positions(composition.get_offseted_items(Some((1., 0., 1.)))?), vec![(-4., 0., -9.), (6., 3., 11.)]);
+
+        assert!(composition.check_placement("Altis", None).is_ok());
+        assert!(matches!(
+            composition.check_placement("Altis", Some((40000., 0., 100.))),
+            Err(LaatError::InvalidConfig { key, .. }) if key == "missions.composition_offset"
+        ));
+        assert!(composition.check_placement("Altis", Some((100., 0., -5.))).is_err());
+
+        let empty = Composition {
+            center: None,
+            items: Some(vec![]),
+            placed_items: None,
+            ..composition
+        };
+        assert!(matches!(empty.get_center(), Err(LaatError::CompositionParse { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_composition_transform() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("header.sqe"), "version = 54;")?;
+        std::fs::write(
+            dir.path().join("composition.sqe"),
+            r#"
+                center[] = {0, 0, 0};
+                class items { items = 1; class Item0 { dataType = "Marker"; position[] = {10, 0, 0}; }; };
+            "#,
+        )?;
+        std::fs::write(
+            dir.path().join(TRANSFORM_FILE),
+            "offset = [100, 0, 0]\nrotation = 90\nscale = 2\nmaps = ['Altis']",
+        )?;
+
+        let load = |toml: &str| {
+            let mission_settings: MissionSettings = toml::from_str(&format!(
+                "maps = ['Altis', 'VR']\nignore_center = false\nmissions_folder = './missions'\n{}",
+                toml
+            ))
+            .unwrap();
+            let entry = mission_settings.compositions().remove(0);
+
+            async move {
+                let config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+                load_composition(entry, &mission_settings, &config).await
+            }
+        };
+
+        let composition = format!("composition = '{}'", dir.path().display());
+        let from_transform = load(&composition).await;
+        let overridden = load(&format!(
+            "composition = [{{ path = '{}', offset = [0, 0, 5], maps = ['VR'] }}]\ncomposition_scale = 1",
+            dir.path().display()
+        ))
+        .await;
+        std::fs::write(dir.path().join(TRANSFORM_FILE), "offset = [100, 0, 0]\nheight = 5")?;
+        let invalid = load(&composition).await;
+
+        let from_transform = from_transform?;
+        assert_eq!(from_transform.local_offset, (100., 0., 0.));
+        assert_eq!((from_transform.rotation, from_transform.scale), (90., 2.));
+        assert!(from_transform.includes_map("Altis"));
+        assert!(!from_transform.includes_map("VR"));
+
+        // LAAT.toml wins over transform.toml
+        let overridden = overridden?;
+        assert_eq!(overridden.local_offset, (0., 0., 5.));
+        assert_eq!((overridden.rotation, overridden.scale), (90., 1.));
+        assert!(overridden.includes_map("VR"));
+        assert!(!overridden.includes_map("Altis"));
+
+        assert!(matches!(invalid, Err(LaatError::CompositionParse { .. })));
+
+        Ok(())
+    }
+
+    /// Parse an SQE snippet, place it and write it back out
+    fn place_fixture(sqe: &str, placement: Placement) -> String {
+        let mut config = Config::from_string(sqe.to_string(), None, &[]).unwrap();
+        let class = config.inner_mut();
+        class.entries = class.entries.take().map(|entries| offset_classes(entries, placement));
+
+        let mut buffer = Vec::new();
+        config.write(&mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    /// Parse and write an SQE snippet, to compare it with placed output regardless of formatting
+    fn normalize_fixture(sqe: &str) -> String {
+        let mut buffer = Vec::new();
+        Config::from_string(sqe.to_string(), None, &[]).unwrap().write(&mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_offset_classes_fixture() {
+        let sqe = r#"
+            class Item0 {
+                dataType = "Group";
+                side = "West";
+                class Entities {
+                    items = 1;
+                    class Item0 {
+                        dataType = "Object";
+                        class PositionInfo { position[] = {1.5, 2, -3.25}; angles[] = {0, 1.5, 0}; };
+                        class Attributes { name = "obj"; init = "this setPos [1, 2, 3];"; };
+                        id = 1;
+                    };
+                };
+                class Attributes {};
+                id = 0;
+            };
+            class Item1 { dataType = "Marker"; position[] = {10, 0, 20}; type = "mil_dot"; id = 2; };
+            class Item2 { dataType = "Trigger"; position[] = {"ATL", 1, 4}; id = 3; };
+        "#;
+
+        let placement = Placement {
+            offset: (100., 5., 200.5),
+            rotation: 0.,
+            scale: 1.,
+        };
+
+        let expected = r#"
+            class Item0 {
+                dataType = "Group";
+                side = "West";
+                class Entities {
+                    items = 1;
+                    class Item0 {
+                        dataType = "Object";
+                        class PositionInfo { position[] = {101.5, 7, 197.25}; angles[] = {0, 1.5, 0}; };
+                        class Attributes { name = "obj"; init = "this setPos [1, 2, 3];"; };
+                        id = 1;
+                    };
+                };
+                class Attributes {};
+                id = 0;
+            };
+            class Item1 { dataType = "Marker"; position[] = {110, 5, 220.5}; type = "mil_dot"; id = 2; };
+            class Item2 { dataType = "Trigger"; position[] = {"ATL", 6, 204.5}; id = 3; };
+        "#;
+
+        assert_eq!(place_fixture(sqe, placement), normalize_fixture(expected));
+    }
+
+    #[test]
+    fn test_rotate_position() {
+        let rotated = |position: &[f32], degrees: f32| {
+            let mut position = match float_array(position) {
+                ConfigEntry::ArrayEntry(array) => array,
+                _ => unreachable!(),
+            };
+            rotate_position(&mut position, degrees);
+
+            floats(&ConfigEntry::ArrayEntry(position))
+        };
+
+        // Clockwise, like a compass: north (+Y) turns to east (+X)
+        assert_close(&rotated(&[0., 5., 1.], 90.), &[1., 5., 0.]);
+        assert_close(&rotated(&[1., 5., 0.], 90.), &[0., 5., -1.]);
+        assert_close(&rotated(&[1., 5., 0.], -90.), &[0., 5., 1.]);
+        assert_close(&rotated(&[3., 2., -4.], 180.), &[-3., 2., 4.]);
+        assert_close(&rotated(&[3., 2., -4.], 360.), &[3., 2., -4.]);
+    }
+
+    #[test]
+    fn test_rotate_classes_fixture() {
+        let sqe = r#"
+            class Item0 {
+                dataType = "Group";
+                class Entities {
+                    items = 2;
+                    class Item0 {
+                        dataType = "Object";
+                        class PositionInfo { position[] = {2, 1, 0}; angles[] = {0.1, 6, 0.2}; };
+                        id = 1;
+                    };
+                    class Item1 { position[] = {0, 0, 3}; dir = 350; id = 2; };
+                };
+                id = 0;
+            };
+        "#;
+
+        let placement = Placement {
+            offset: (10., 0., 20.),
+            rotation: 90.,
+            scale: 1.,
+        };
+
+        let placed = Config::from_string(place_fixture(sqe, placement), None, &[]).unwrap();
+        let root = ConfigEntry::ClassEntry(placed.into_inner());
+        let entry = |path: &[&str]| {
+            path.iter().fold(root.clone(), |entry, name| match entry {
+                ConfigEntry::ClassEntry(class) => {
+                    class.entries.unwrap().into_iter().find(|(n, _)| n == name).unwrap().1
+                }
+                _ => panic!("Not a class: {}", name),
+            })
+        };
+
+        // Positions are turned around the center before the offset is added
+        let object = ["Item0", "Entities", "Item0", "PositionInfo"];
+        assert_close(&floats(&entry(&[&object[..], &["position"]].concat())), &[10., 1., 18.]);
+
+        // Only the heading of angles[] turns, wrapping around at 2π
+        let heading = (6. + std::f32::consts::FRAC_PI_2).rem_euclid(TAU);
+        assert_close(&floats(&entry(&[&object[..], &["angles"]].concat())), &[0.1, heading, 0.2]);
+
+        let legacy = ["Item0", "Entities", "Item1"];
+        assert_close(&floats(&entry(&[&legacy[..], &["position"]].concat())), &[13., 0., 20.]);
+        assert!(matches!(
+            entry(&[&legacy[..], &["dir"]].concat()),
+            ConfigEntry::FloatEntry(dir) if (dir - 80.).abs() < 1e-4
+        ));
+    }
+
+    #[test]
+    fn test_offset_classes_noop() {
+        // Classes without a position, and a zero offset, are left as they are
+        let sqe = r#"
+            class Item0 {
+                dataType = "Logic";
+                class PositionInfo { position[] = {3, 0.5, "x"}; };
+                class CustomAttributes { class Attribute0 { property = "speed"; value = 10; }; };
+            };
+            class Connections { class LinkIDProvider { nextID = 1; }; };
+        "#;
+
+        let placement = Placement {
+            offset: (0., 0., 0.),
+            rotation: 0.,
+            scale: 1.,
+        };
+
+        assert_eq!(place_fixture(sqe, placement), normalize_fixture(sqe));
+    }
+
+    #[test]
+    fn test_scale_object() {
+        let object = vec![
+            ("dataType".to_string(), ConfigEntry::StringEntry("Object".to_string())),
+            ("PositionInfo".to_string(), class(vec![("position".to_string(), float_array(&[2., 3., -4.]))])),
+        ];
+
+        let placement = Placement {
+            offset: (100., 10., 200.),
+            rotation: 0.,
+            scale: 1.5,
+        };
+
+        let entries = offset_classes(vec![("Item0".to_string(), class(object))], placement);
+
+        let position_info = match &entries[0].1 {
+            ConfigEntry::ClassEntry(object) => object.entries.clone().unwrap().remove(1).1,
+            _ => unreachable!(),
+        };
+        let position = match position_info {
+            ConfigEntry::ClassEntry(class) => class.entries.unwrap().remove(0).1,
+            _ => unreachable!(),
+        };
+
+        assert_close(&floats(&position), &[103., 13., 194.]);
+    }
+
+    #[test]
+    fn test_slot_groups() -> Result<()> {
+        let composition = Config::from_string(
+            r#"class Item0 { dataType = "Object"; id = 4; }; class Item1 { dataType = "Marker"; id = 9; };"#.to_string(),
+            None,
+            &[],
+        )?
+        .into_inner()
+        .entries
+        .unwrap();
+
+        let slots = vec![
+            SlotConfig {
+                side: "west".to_string(),
+                count: 3,
+                unit_class: "B_Soldier_F".to_string(),
+                offset: (0., 0., 0.),
+            },
+            SlotConfig {
+                side: "GUER".to_string(),
+                count: 1,
+                unit_class: "I_Soldier_F".to_string(),
+                offset: (10., 0., 0.),
+            },
+        ];
+
+        let next_id = max_id(&composition).unwrap() + 1;
+        let groups = slot_groups(&slots, (100., 0., 200.), next_id)?;
+
+        let mut positions = Vec::new();
+        collect_positions(&groups, &mut positions);
+
+        assert_eq!(count_objects(&groups), 4);
+        assert_eq!(positions, vec![(100., 0., 200.), (102., 0., 200.), (104., 0., 200.), (110., 0., 200.)]);
+        assert_eq!(max_id(&groups), Some(next_id + 5));
+
+        let merged = concat_items(vec![composition, groups]);
+        assert!(matches!(merged[0].1, ConfigEntry::IntEntry(4)));
+
+        let invalid =
+            SlotConfig { side: "BLUE".to_string(), count: 1, unit_class: String::new(), offset: (0., 0., 0.) };
+        assert!(invalid.sqm_side().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_respawn_markers() -> Result<()> {
+        let position = |side: &str, name: Option<&str>| RespawnPositionConfig {
+            side: side.to_string(),
+            name: name.map(str::to_string),
+            offset: (5., 0., 0.),
+        };
+
+        let markers = respawn_markers(
+            &[position("west", None), position("WEST", None), position("guer", Some("respawn_guerrila_hq"))],
+            (100., 0., 200.),
+            10,
+        )?;
+
+        let names = |markers: &EntryList| {
+            markers
+                .iter()
+                .filter_map(|(_, entry)| match entry {
+                    ConfigEntry::ClassEntry(class) => class.entries.as_ref().and_then(|entries| {
+                        entries.iter().find_map(|(name, entry)| match entry {
+                            ConfigEntry::StringEntry(value) if name == "name" => Some(value.clone()),
+                            _ => None,
+                        })
+                    }),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut positions = Vec::new();
+        collect_positions(&markers, &mut positions);
+
+        assert_eq!(names(&markers), vec!["respawn_west", "respawn_west_1", "respawn_guerrila_hq"]);
+        assert_eq!(positions, vec![(105., 0., 200.); 3]);
+        assert_eq!(max_id(&markers), Some(12));
+
+        // Unnamed markers take the first free number, ignoring case
+        let markers = respawn_markers(
+            &[position("west", Some("respawn_west_1")), position("west", None), position("west", None)],
+            (0., 0., 0.),
+            0,
+        )?;
+        assert_eq!(names(&markers), vec!["respawn_west_1", "respawn_west", "respawn_west_2"]);
+        let markers =
+            respawn_markers(&[position("west", Some("Respawn_West")), position("west", None)], (0., 0., 0.), 0)?;
+        assert_eq!(names(&markers), vec!["Respawn_West", "respawn_west_1"]);
+
+        assert!(respawn_markers(&[position("east", Some("respawn_west"))], (0., 0., 0.), 0).is_err());
+        let duplicate = [position("east", None), position("east", Some("respawn_east"))];
+        assert!(respawn_markers(&duplicate, (0., 0., 0.), 0).is_err());
+        assert!(respawn_markers(&[position("blue", None)], (0., 0., 0.), 0).is_err());
+        assert!(respawn_markers(&[position("west", Some("respawn_west\"; hq"))], (0., 0., 0.), 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeat_items() -> Result<()> {
+        let items = vec![
+            ("items".to_string(), ConfigEntry::IntEntry(1)),
+            (
+                "Item0".to_string(),
+                class(vec![
+                    ("dataType".to_string(), ConfigEntry::StringEntry("Marker".to_string())),
+                    ("position".to_string(), float_array(&[10., 0., 20.])),
+                    ("id".to_string(), ConfigEntry::IntEntry(2)),
+                ]),
+            ),
+        ];
+
+        let repeat = RepeatConfig { rows: 2, cols: 3, spacing: 50. };
+        let merged = concat_items(repeat_items(vec![items], repeat));
+
+        let mut positions = Vec::new();
+        collect_positions(&merged, &mut positions);
+
+        assert_eq!(count_objects(&merged), 6);
+        assert_eq!(positions[..4], [(10., 0., 20.), (60., 0., 20.), (110., 0., 20.), (10., 0., 70.)]);
+        assert_eq!(max_id(&merged), Some(2 + 3 * 5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_to_element() {
+        let offsets = [10., -2., 0.5];
+
+        assert!(matches!(
+            add_to_element(ConfigArrayElement::FloatElement(-1.5), &offsets, 0),
+            ConfigArrayElement::FloatElement(x) if x == 8.5
+        ));
+        assert!(matches!(
+            add_to_element(ConfigArrayElement::IntElement(-3), &offsets, 1),
+            ConfigArrayElement::IntElement(-5)
+        ));
+        assert!(matches!(
+            add_to_element(ConfigArrayElement::IntElement(4), &offsets, 2),
+            ConfigArrayElement::FloatElement(x) if x == 4.5
+        ));
+        assert!(matches!(
+            add_to_element(ConfigArrayElement::IntElement(4), &offsets, 3),
+            ConfigArrayElement::IntElement(4)
+        ));
+        assert!(matches!(
+            add_to_element(ConfigArrayElement::StringElement("ASL".to_string()), &offsets, 0),
+            ConfigArrayElement::StringElement(s) if s == "ASL"
+        ));
+
+        let nested = match float_array(&[1., 2., 3.]) {
+            ConfigEntry::ArrayEntry(array) => array,
+            _ => unreachable!(),
+        };
+        let outer = vec![ConfigArrayElement::ArrayElement(nested), ConfigArrayElement::FloatElement(7.)];
+
+        match &offset_elements(&outer, &offsets)[..] {
+            [ConfigArrayElement::ArrayElement(nested), ConfigArrayElement::FloatElement(outer)] => {
+                let values: Vec<f32> = nested.elements.iter().filter_map(element_value).collect();
+                assert_close(&values, &[11., 0., 3.5]);
+                assert_eq!(*outer, 5.);
+            }
+            elements => panic!("Unexpected elements: {:?}", elements),
+        }
+    }
+
+    #[test]
+    fn test_merge_compositions_items_count() -> Result<()> {
+        let mut compositions = vec![
+            test_composition(
+                r#"
+                    center[] = {0, 0, 0};
+                    class items {
+                        items = 2;
+                        class Item0 { dataType = "Object"; id = 0; class PositionInfo { position[] = {1, 0, 2}; }; };
+                        class Item1 { dataType = "Marker"; id = 1; position[] = {-3, 0, 6}; };
+                    };
+                "#,
+            )?,
+            test_composition(
+                r#"
+                    center[] = {0, 0, 0};
+                    class items {
+                        items = 1;
+                        class Item4 { dataType = "Object"; id = 2; class PositionInfo { position[] = {5, 0, 5}; }; };
+                    };
+                "#,
+            )?,
+        ];
+
+        let settings = settings(
+            r#"
+                maps = ["VR"]
+                slots = [{ side = "west", count = 2, unit_class = "B_Soldier_F" }]
+            "#,
+        );
+
+        let entities = |compositions: &[Composition]| -> Result<EntryList> {
+            let mut mission = test_mission(&settings)?;
+            assert_eq!(mission.class_name(), "LAAT_missions_VRTest");
+            mission.merge_compositions(compositions)?;
+
+            let sqm = Config::from_string(mission.to_sqm()?, None, &[])?.into_inner().entries.unwrap();
+            Ok(get_class(&sqm, "Mission").and_then(|mission| get_class(&mission, "Entities")).unwrap())
+        };
+
+        let merged = entities(&compositions)?;
+        let names = merged.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        // Three composition items plus the slot group
+        assert_eq!(names, vec!["items", "Item0", "Item1", "Item2", "Item3"]);
+        assert!(matches!(merged[0].1, ConfigEntry::IntEntry(4)));
+
+        // Limited to another map, so left out
+        compositions[1].entry = Some(CompositionEntry {
+            path: PathBuf::from("composition"),
+            offset: None,
+            maps: Some(vec!["Altis".to_string()]),
+        });
+        assert!(matches!(entities(&compositions)?[0].1, ConfigEntry::IntEntry(3)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_placed_objects() -> Result<()> {
+        let items = Config::from_string(
+            r#"
+                class Item0 {
+                    dataType = "Group";
+                    class Entities {
+                        items = 1;
+                        class Item0 { dataType = "Object"; type = "B_Soldier_F"; class PositionInfo { position[] = {1, 5, 2}; }; };
+                    };
+                };
+                class Item1 { dataType = "Marker"; type = "mil_dot"; position[] = {-3, 0, 6}; };
+                class Item2 { dataType = "Comment"; };
+            "#
+            .to_string(),
+            None,
+            &[],
+        )?
+        .into_inner()
+        .entries
+        .unwrap();
+
+        let mut objects = Vec::new();
+        collect_placed_objects(&items, "Base", &mut objects);
+
+        let placed = objects
+            .iter()
+            .map(|object| (object.data_type.as_str(), object.class.as_str(), (object.x, object.y, object.z)))
+            .collect::<Vec<_>>();
+        assert_eq!(placed, vec![("Object", "B_Soldier_F", (1., 2., 5.)), ("Marker", "mil_dot", (-3., 6., 0.))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_mission_entities() -> Result<()> {
+        let mut sqm = Config::from_string(
+            r#"
+                version = 54;
+                class Mission {
+                    class Intel { timeOfChanges = 1800; };
+                    class Entities { items = 0; };
+                    class Connections {};
+                };
+            "#
+            .to_string(),
+            None,
+            &[],
+        )?;
+
+        set_mission_entities(&mut sqm, concat_items(vec![vec![item("B_Soldier_F")]]));
+
+        let entries = sqm.into_inner().entries.unwrap();
+        let mission = get_class(&entries, "Mission").unwrap();
+        let names = mission.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Intel", "Entities", "Connections"]);
+
+        let entities = get_class(&mission, "Entities").unwrap();
+        assert!(matches!(entities[0].1, ConfigEntry::IntEntry(1)));
+        assert!(matches!(
+            get_class(&entities, "Item0").unwrap()[0].1,
+            ConfigEntry::StringEntry(ref class) if class == "B_Soldier_F"
+        ));
+
+        // Nothing to replace
+        let mut header = Config::from_string("version = 54;".to_string(), None, &[])?;
+        set_mission_entities(&mut header, vec![]);
+        assert_eq!(header.into_inner().entries.unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_sqm() {
+        let sqm = |entities: &str| format!("version = 54; class Mission {{ class Entities {{ {} }}; }};", entities);
+
+        assert!(validate_sqm(&sqm("items = 0;")).is_ok());
+        assert!(validate_sqm(&sqm(
+            r#"items = 2; class Item0 { class Entities { items = 1; class Item0 {}; }; }; class Item1 {};"#
+        ))
+        .is_ok());
+
+        assert!(validate_sqm("version = 54;").unwrap_err().contains("no Mission class"));
+        assert!(validate_sqm(&sqm("items = 2; class Item0 {};")).unwrap_err().contains("Mission.Entities"));
+        assert!(validate_sqm(&sqm("items = 2; class Item0 {}; class Item2 {};")).unwrap_err().contains("Item1"));
+        assert!(validate_sqm(&sqm(r#"items = 1; class Item0 { class Entities { class Item0 {}; }; };"#))
+            .unwrap_err()
+            .contains("Mission.Entities.Item0.Entities"));
+    }
+
+    #[test]
+    fn test_set_entry_keeps_order() {
+        let mut entries = ["Intel", "Entities", "Connections"]
+            .iter()
+            .map(|name| (name.to_string(), class(vec![])))
+            .collect::<EntryList>();
+
+        set_entry(&mut entries, "Entities", ConfigEntry::IntEntry(1));
+        set_entry(&mut entries, "Attributes", ConfigEntry::IntEntry(2));
+
+        let names = entries.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Intel", "Entities", "Connections", "Attributes"]);
+        assert!(matches!(entries[1].1, ConfigEntry::IntEntry(1)));
+    }
+
+    #[test]
+    fn test_concat_items_renumbers() {
+        let first = vec![("items".to_string(), ConfigEntry::IntEntry(1)), item("Land_A")];
+        let second = vec![("items".to_string(), ConfigEntry::IntEntry(1)), item("Land_B")];
+
+        let merged = concat_items(vec![first, second]);
+        let names: Vec<&str> = merged.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, vec!["items", "Item0", "Item1"]);
+        assert!(matches!(merged[0].1, ConfigEntry::IntEntry(2)));
+    }
 }
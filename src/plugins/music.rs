@@ -15,7 +15,7 @@ use std::path::Path;
 
 use walkdir::DirEntry;
 use std::path::PathBuf;
-use crate::{Result, context::BuildContext, create_handlebars};
+use crate::{Result, context::BuildContext, create_project_handlebars, render_template};
 
 use serde::{Serialize, Deserialize};
 
@@ -47,7 +47,7 @@ pub async fn build_music_addon(
         ..
     } = build_context.clone();
 
-    let mut manager = AddonManager::from_context(ADDON_NAME.to_string(), build_context);
+    let mut manager = AddonManager::from_context(ADDON_NAME.to_string(), build_context.clone());
 
     // Walkdir through ./assets/music
     let dir = walkdir::WalkDir::new(format!("{}/music", assets_path));
@@ -70,7 +70,7 @@ pub async fn build_music_addon(
                 // references the CfgMusicClasses for it's folder.
                 // Also - calculate the length (in seconds) of the ogg file, and add that into the template
                 } else if file_type.is_file() && file_name.contains(".ogg") {
-                    if let Ok(entry) = Track::from_dir_entry(entry, &prefix, &ADDON_NAME) {
+                    if let Ok(entry) = Track::from_dir_entry(entry, &prefix, ADDON_NAME) {
                         music_files.push(entry);
                     }
                 }
@@ -98,14 +98,14 @@ pub async fn build_music_addon(
 
 
     // Create the config.cpp
-    let handlebars = create_handlebars()?;
-    let config_cpp = handlebars.render("music_addon", &music_addon)?;
+    let handlebars = create_project_handlebars(&build_context)?;
+    let config_cpp = render_template(&handlebars, "music_addon", &music_addon)?;
 
-    manager.add_file(config_cpp, "config.cpp".into());
+    manager.add_config(config_cpp, "config.cpp".into())?;
 
     // Copy the music files over
     for track in music_files {
-        let new_path = manager.add_asset(track.path, Some("data/Music".into()))?;
+        manager.add_asset(track.path, Some("data/Music".into()))?;
     }
 
     manager.build_addon().await?;
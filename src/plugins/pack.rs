@@ -0,0 +1,97 @@
+//! Compiler Plugin for packing hand-authored addons listed in `[pack] include_folders`.
+//!
+//! Every include folder that contains a `config.cpp` is copied into the build folder as its own
+//! addon, named after the folder, so `laat pack` turns it into `{folder}.pbo` with the prefix
+//! `{prefix}\{folder}`. Files matching `[pack] excludes` are left out, and `header_extensions`
+//! are applied when packing, like for every other addon. Folders without a `config.cpp` are
+//! only used to resolve `#include`s.
+
+use crate::context::AddonManager;
+use crate::pack::{Excludes, IGNORE_FILE};
+use crate::{BuildContext, Plugin, Result};
+use std::path::{Path, PathBuf};
+
+const ADDON_CONFIG: &str = "config.cpp";
+
+pub struct PackPlugin;
+
+#[async_trait]
+impl Plugin for PackPlugin {
+    async fn build(&self, build_context: BuildContext) -> Result<()> {
+        let excludes = Excludes::new(&build_context.pack.excludes)?.with_ignore_file(Path::new(IGNORE_FILE))?;
+
+        for folder in addon_folders(&build_context)? {
+            let mut manager = AddonManager::from_context(addon_name(&folder)?, build_context.clone());
+
+            add_folder(&mut manager, &folder, &excludes)?;
+
+            info!("Adding {} as {}", folder.display(), manager.addon_path().display());
+            manager.build_addon().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn clean(&self, build_context: BuildContext) -> Result<()> {
+        for folder in addon_folders(&build_context)? {
+            AddonManager::from_context(addon_name(&folder)?, build_context.clone())
+                .clean_addon()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "pack".to_string()
+    }
+}
+
+/// The include folders that are addons themselves
+fn addon_folders(build_context: &BuildContext) -> Result<Vec<PathBuf>> {
+    let folders = build_context.pack.expanded_include_folders()?;
+
+    Ok(folders
+        .into_iter()
+        .filter(|folder| {
+            let is_addon = folder.join(ADDON_CONFIG).is_file();
+            if !is_addon {
+                debug!("Skipping {}, it has no {}", folder.display(), ADDON_CONFIG);
+            }
+
+            is_addon
+        })
+        .collect())
+}
+
+fn addon_name(folder: &Path) -> Result<String> {
+    folder
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Failed to get addon name for: {:?}", folder).into())
+}
+
+/// Add every file in `folder` that isn't excluded, by `excludes` or the folder's own
+/// `.laatignore`, as an asset, keeping its relative path
+fn add_folder(manager: &mut AddonManager, folder: &Path, excludes: &Excludes) -> Result<()> {
+    let excludes = excludes.with_ignore_file(&folder.join(IGNORE_FILE))?;
+
+    for entry in walkdir::WalkDir::new(folder) {
+        let entry = entry?;
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(folder)?;
+
+        if excludes.is_excluded(relative) {
+            trace!("Excluding {}", relative.display());
+            continue;
+        }
+
+        manager.add_asset(entry.path().to_owned(), relative.parent().map(Path::to_owned))?;
+    }
+
+    Ok(())
+}
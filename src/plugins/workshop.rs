@@ -0,0 +1,249 @@
+//! Compiler Plugin for Steam Workshop metadata, from the `[workshop]` section of LAAT.toml.
+//!
+//! The preview image is staged into `{release_path}/workshop`, next to (not inside) the mod
+//! folder, together with a SteamCMD `workshop_build_item` VDF and a `workshop.json` listing every
+//! setting (including the tags) for the Arma 3 Publisher. `laat release` uploads with the same
+//! title, description and preview.
+
+use crate::{create_and_write_file, create_project_handlebars, render_template};
+use crate::{BuildContext, LaatError, Plugin, Result, WorkshopItem};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub(crate) const WORKSHOP_SETTINGS_KEY: &str = "workshop";
+
+/// Folder in `release_path` the metadata is staged into
+const STAGING_FOLDER: &str = "workshop";
+
+/// Steam rejects previews over 1 MB
+const MAX_PREVIEW_SIZE: u64 = 1024 * 1024;
+
+pub struct WorkshopPlugin;
+
+#[async_trait]
+impl Plugin for WorkshopPlugin {
+    async fn build(&self, build_context: BuildContext) -> Result<()> {
+        let settings = WorkshopSettings::from_build_config(&build_context)?;
+        let staging = Path::new(&build_context.release_path).join(STAGING_FOLDER);
+
+        if build_context.dry_run || build_context.diff {
+            info!("Would stage workshop metadata in {}", staging.display());
+            return Ok(());
+        }
+
+        tokio::fs::create_dir_all(&staging).await?;
+
+        let preview = match &settings.preview_image {
+            Some(preview) => {
+                let file_name = preview
+                    .file_name()
+                    .ok_or_else(|| format!("Failed to get file name for preview image: {:?}", preview))?;
+                let staged = staging.join(file_name);
+                tokio::fs::copy(preview, &staged).await?;
+
+                Some(build_context.project_path(staged)?)
+            }
+            None => None,
+        };
+
+        let item = settings.workshop_item(&build_context, preview, None)?;
+
+        let handlebars = create_project_handlebars(&build_context)?;
+        let vdf = render_template(&handlebars, "workshop_upload.vdf", &item)?;
+        create_and_write_file(staging.join("workshop_upload.vdf"), vdf).await?;
+
+        let metadata = WorkshopMetadata {
+            item: &item,
+            tags: &settings.tags,
+        };
+        create_and_write_file(staging.join("workshop.json"), serde_json::to_string_pretty(&metadata)?).await?;
+
+        info!("Staged workshop metadata in {}", staging.display());
+
+        Ok(())
+    }
+
+    async fn clean(&self, build_context: BuildContext) -> Result<()> {
+        let staging = Path::new(&build_context.release_path).join(STAGING_FOLDER);
+
+        if staging.exists() {
+            tokio::fs::remove_dir_all(staging).await?;
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "workshop".to_string()
+    }
+
+    fn settings_schema(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(schemars::schema_for!(WorkshopSettings)).ok()
+    }
+
+    fn validate(&self, build_context: &BuildContext) -> Vec<LaatError> {
+        WorkshopSettings::from_build_config(build_context).err().into_iter().collect()
+    }
+}
+
+#[derive(Serialize)]
+struct WorkshopMetadata<'a> {
+    #[serde(flatten)]
+    item: &'a WorkshopItem,
+    tags: &'a [String],
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub(crate) struct WorkshopSettings {
+    #[serde(default)]
+    /// Published file id, defaults to `[release] workshop_id`
+    id: Option<usize>,
+
+    #[serde(default)]
+    /// Title of the workshop item
+    title: Option<String>,
+
+    #[serde(default)]
+    /// Text file with the description of the workshop item
+    description_file: Option<PathBuf>,
+
+    #[serde(default)]
+    /// JPG, PNG or GIF shown on the workshop page, up to 1 MB
+    preview_image: Option<PathBuf>,
+
+    #[serde(default)]
+    /// Workshop tags, i.e. "Mod" or "Scenario"
+    tags: Vec<String>,
+}
+
+impl WorkshopSettings {
+    /// Load and check the `[workshop]` section
+    pub(crate) fn from_build_config(build_config: &BuildContext) -> Result<Self> {
+        let settings: WorkshopSettings = build_config.plugin_settings(WORKSHOP_SETTINGS_KEY)?;
+
+        if let Some(preview) = &settings.preview_image {
+            let metadata = std::fs::metadata(preview).map_err(|why| {
+                invalid_setting(
+                    "preview_image",
+                    format!("Workshop preview_image {} can't be read: {}", preview.display(), why),
+                )
+            })?;
+
+            if !metadata.is_file() {
+                return Err(invalid_setting(
+                    "preview_image",
+                    format!("Workshop preview_image {} is not a file", preview.display()),
+                ));
+            }
+
+            if metadata.len() > MAX_PREVIEW_SIZE {
+                warn!(
+                    "Workshop preview_image {} is {} bytes, Steam only accepts previews up to 1 MB",
+                    preview.display(),
+                    metadata.len()
+                );
+            }
+        }
+
+        settings.description()?;
+
+        if settings.tags.iter().any(|tag| tag.trim().is_empty() || tag.contains(',')) {
+            return Err(invalid_setting(
+                "tags",
+                "Workshop tags can't be empty or contain commas".to_string(),
+            ));
+        }
+
+        Ok(settings)
+    }
+
+    /// The contents of `description_file`
+    fn description(&self) -> Result<Option<String>> {
+        self.description_file
+            .as_ref()
+            .map(|file| {
+                std::fs::read_to_string(file).map_err(|why| {
+                    invalid_setting(
+                        "description_file",
+                        format!("Workshop description_file {} can't be read: {}", file.display(), why),
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// The SteamCMD workshop item for the released mod folder
+    pub(crate) fn workshop_item(
+        &self,
+        build_config: &BuildContext,
+        preview_file: Option<PathBuf>,
+        changenotes: Option<String>,
+    ) -> Result<WorkshopItem> {
+        Ok(WorkshopItem {
+            app_id: build_config.release.app_id,
+            file_id: self.id.unwrap_or(build_config.release.workshop_id),
+            content_folder: build_config.project_path(build_config.released_addon_path())?,
+            preview_file,
+            // VDF values can't contain quotes
+            title: self.title.as_ref().map(|title| title.replace('"', "")),
+            description: self.description()?.map(|description| description.trim_end().replace('"', "")),
+            changenotes,
+        })
+    }
+
+    /// `preview_image`, as an absolute path
+    pub(crate) fn preview_file(&self, build_config: &BuildContext) -> Result<Option<PathBuf>> {
+        match &self.preview_image {
+            Some(preview) => Ok(Some(build_config.project_path(preview)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn invalid_setting(name: &str, reason: String) -> LaatError {
+    LaatError::InvalidConfig {
+        key: format!("{}.{}", WORKSHOP_SETTINGS_KEY, name),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workshop_settings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("preview.png"), b"png")?;
+        std::fs::write(dir.path().join("description.txt"), "The \"best\" mod")?;
+
+        let mut config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        config.release.workshop_id = 42;
+
+        let settings = |config: &mut BuildContext, preview: &str, tag: &str| {
+            let settings: toml::Value = toml::from_str(&format!(
+                "title = 'LAAT Test Mod'\ndescription_file = '{}'\npreview_image = '{}'\ntags = ['{}']",
+                dir.path().join("description.txt").display(),
+                dir.path().join(preview).display(),
+                tag
+            ))?;
+            config.set_plugin_settings(WORKSHOP_SETTINGS_KEY, settings)?;
+
+            WorkshopSettings::from_build_config(config)
+        };
+
+        let item = settings(&mut config, "preview.png", "Mod").and_then(|loaded| loaded.workshop_item(&config, None, None));
+        let missing = settings(&mut config, "missing.png", "Mod");
+        let bad_tags = settings(&mut config, "preview.png", "Mod,Scenario");
+
+        let item = item?;
+        assert_eq!(item.file_id, 42);
+        assert_eq!(item.description.as_deref(), Some("The best mod"));
+
+        assert!(matches!(missing, Err(LaatError::InvalidConfig { key, .. }) if key == "workshop.preview_image"));
+        assert!(matches!(bad_tags, Err(LaatError::InvalidConfig { key, .. }) if key == "workshop.tags"));
+
+        Ok(())
+    }
+}
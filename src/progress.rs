@@ -0,0 +1,89 @@
+//! Progress of long-running plugins, for progress bars and GUIs embedding LAAT.
+//!
+//! Plugins report through `build_context.report_progress`. Without a sink set with
+//! `LaatCompiler::set_progress_sink`, reports go nowhere and the CLI output is unchanged.
+
+use serde::Serialize;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// A step of a plugin's build, i.e. the 3rd of 12 missions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    /// Name of the plugin reporting
+    pub plugin: String,
+    /// Steps finished so far
+    pub done: usize,
+    pub total: usize,
+    /// What the plugin is doing, i.e. "Building mission LAAT_missions_VRMission"
+    pub message: String,
+}
+
+/// Receives the `Progress` plugins report. Every clone of a `BuildContext` shares the same sink.
+#[derive(Clone, Default)]
+pub struct ProgressSink(Option<Arc<dyn Fn(Progress) + Send + Sync>>);
+
+impl ProgressSink {
+    /// Call `callback` with every report. It runs on the plugin's task, so it should return quickly.
+    pub fn new(callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(callback)))
+    }
+
+    /// A sink sending every report to the returned receiver, which stays open until the sink and
+    /// all its clones are dropped
+    pub fn channel() -> (Self, UnboundedReceiver<Progress>) {
+        let (sender, receiver) = unbounded_channel();
+
+        let sink = Self::new(move |progress| {
+            // Nobody listening any more is fine
+            let _ = sender.send(progress);
+        });
+
+        (sink, receiver)
+    }
+
+    pub fn report(&self, progress: Progress) {
+        if let Some(callback) = &self.0 {
+            callback(progress);
+        }
+    }
+}
+
+impl Debug for ProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ProgressSink").field(&self.0.is_some()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_progress_channel() -> crate::Result<()> {
+        let mut config = crate::LaatConfig::new("LAAT", "LAAT Test Mod")?;
+        config.plugin = Some("missions".to_string());
+
+        // Without a sink, reports are dropped
+        config.report_progress(0, 2, "Building mission A");
+
+        let (sink, mut receiver) = ProgressSink::channel();
+        config.progress = sink;
+        config.report_progress(1, 2, "Building mission B");
+        drop(config);
+
+        assert_eq!(
+            receiver.recv().await,
+            Some(Progress {
+                plugin: "missions".to_string(),
+                done: 1,
+                total: 2,
+                message: "Building mission B".to_string(),
+            })
+        );
+        assert_eq!(receiver.recv().await, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Summary of what a build did, for logging and tooling.
+
+use crate::diff::FileChange;
+use crate::manifest::ManifestEntry;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Collected results of `LaatCompiler::build`, written as JSON with `--report`
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BuildReport {
+    /// Plugins in the order they ran
+    pub plugins: Vec<PluginReport>,
+    /// Addons written to the build folder
+    pub addons: Vec<AddonReport>,
+    /// Missions generated by the missions plugin, one per map and mission
+    pub missions: Vec<MissionReport>,
+    /// PBOs in the mod folder, only filled in by `laat ship`
+    pub pbos: Vec<ManifestEntry>,
+    /// Files that differ from the last build, only filled in with `--diff`
+    pub changes: Vec<FileChange>,
+    /// Warnings logged during the build
+    pub warnings: Vec<String>,
+}
+
+/// An addon written to the build folder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddonReport {
+    /// Prefixed addon path, i.e. `LAAT/Music`
+    pub path: String,
+    pub files: usize,
+    /// Total size of the files, in bytes
+    pub size: u64,
+}
+
+/// A mission generated for a map
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissionReport {
+    pub map: String,
+    /// CfgMissions class
+    pub class: String,
+    /// Addon the mission is in, i.e. `Missions`
+    pub addon: String,
+    /// Path of the mission folder in the addon, i.e. `missions/LAAT_missions_VRMission.VR`
+    pub path: String,
+}
+
+/// How a single plugin's build went
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginReport {
+    pub name: String,
+    /// Wall-clock time spent in the plugin's `build`
+    pub duration: Duration,
+    pub success: bool,
+}
+
+impl BuildReport {
+    pub fn add_plugin(&mut self, name: String, duration: Duration, success: bool) {
+        self.plugins.push(PluginReport {
+            name,
+            duration,
+            success,
+        });
+    }
+
+    /// Total time spent building plugins
+    pub fn duration(&self) -> Duration {
+        self.plugins.iter().map(|plugin| plugin.duration).sum()
+    }
+
+    /// Log a line per plugin with its timing and outcome
+    pub fn log_summary(&self) {
+        info!("Build summary:");
+
+        for plugin in &self.plugins {
+            info!(
+                "  {:<12} {:>8.2}s  {}",
+                plugin.name,
+                plugin.duration.as_secs_f64(),
+                if plugin.success { "ok" } else { "FAILED" }
+            );
+        }
+
+        info!("  {:<12} {:>8.2}s", "total", self.duration().as_secs_f64());
+    }
+}
+
+/// Collects what plugins produce while building. Every clone of a `BuildContext` shares the same
+/// collector, so plugins add to it through `build_context.report`.
+///
+/// Also a tracing layer, which records every warning LAAT logs once installed with the subscriber.
+#[derive(Debug, Default, Clone)]
+pub struct ReportCollector(Arc<Mutex<Collected>>);
+
+#[derive(Debug, Default)]
+struct Collected {
+    addons: Vec<AddonReport>,
+    missions: Vec<MissionReport>,
+    changes: Vec<FileChange>,
+    warnings: Vec<String>,
+}
+
+impl ReportCollector {
+    pub fn add_addon(&self, addon: AddonReport) {
+        self.lock().addons.push(addon);
+    }
+
+    pub fn add_mission(&self, mission: MissionReport) {
+        self.lock().missions.push(mission);
+    }
+
+    pub fn add_changes(&self, changes: Vec<FileChange>) {
+        self.lock().changes.extend(changes);
+    }
+
+    pub fn add_warning(&self, warning: String) {
+        self.lock().warnings.push(warning);
+    }
+
+    /// Move everything collected so far into `report`
+    pub fn drain_into(&self, report: &mut BuildReport) {
+        let mut collected = self.lock();
+
+        report.addons.append(&mut collected.addons);
+        report.missions.append(&mut collected.missions);
+        report.changes.append(&mut collected.changes);
+        report.warnings.append(&mut collected.warnings);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Collected> {
+        // A panic while holding the lock doesn't leave the lists half updated
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ReportCollector {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        if *metadata.level() == Level::WARN && metadata.target().starts_with("laat") {
+            let mut message = MessageVisitor(String::new());
+            event.record(&mut message);
+            self.add_warning(message.0);
+        }
+    }
+}
+
+/// Reads the formatted message of a tracing event
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_report_collector() {
+        let collector = ReportCollector::default();
+        let subscriber = tracing_subscriber::registry().with(collector.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            warn!("Mission {} is off the map", "Altis");
+            info!("Not a warning");
+            tracing::warn!(target: "armake2", "Not from LAAT");
+        });
+
+        collector.clone().add_addon(AddonReport {
+            path: "LAAT/Music".to_string(),
+            files: 2,
+            size: 10,
+        });
+
+        let mut report = BuildReport::default();
+        collector.drain_into(&mut report);
+
+        assert_eq!(report.warnings, vec!["Mission Altis is off the map"]);
+        assert_eq!(report.addons[0].path, "LAAT/Music");
+
+        // Drained, so a second build starts empty
+        let mut report = BuildReport::default();
+        collector.drain_into(&mut report);
+        assert!(report.addons.is_empty() && report.warnings.is_empty());
+    }
+}
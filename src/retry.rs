@@ -0,0 +1,105 @@
+//! Retries for file operations that fail for a moment, i.e. on Windows while antivirus or the
+//! search indexer holds a file open.
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+/// Delay before the first retry. It doubles with every retry, up to `MAX_DELAY`.
+const FIRST_DELAY: Duration = Duration::from_millis(50);
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Run `operation`, retrying it up to `retries` times with a growing delay when it fails with an
+/// error that may go away on its own. `description` says what failed in the logged warning, i.e.
+/// "write build/LAAT/Music/config.cpp".
+pub(crate) async fn retry_io<T, F, Fut>(retries: usize, description: &str, mut operation: F) -> io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        match operation().await {
+            Err(why) if attempt < retries && is_transient(&why) => {
+                attempt += 1;
+                let delay = delay(attempt);
+
+                warn!(
+                    "Failed to {}: {}. Retrying in {} ms ({}/{})",
+                    description,
+                    why,
+                    delay.as_millis(),
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn delay(attempt: usize) -> Duration {
+    FIRST_DELAY
+        .checked_mul(1 << (attempt - 1).min(16))
+        .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY))
+}
+
+/// Errors caused by another process holding the file, rather than by the file itself
+fn is_transient(error: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    const WINDOWS_LOCKED: &[i32] = &[32, 33];
+
+    matches!(
+        error.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    ) || (cfg!(windows) && error.raw_os_error().is_some_and(|code| WINDOWS_LOCKED.contains(&code)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn test_retry_io() {
+        let attempts = Cell::new(0);
+        let result = retry_io(3, "read", || {
+            attempts.set(attempts.get() + 1);
+            let result = if attempts.get() < 3 {
+                Err(io::Error::from(io::ErrorKind::PermissionDenied))
+            } else {
+                Ok(attempts.get())
+            };
+
+            async move { result }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        // Gives up after `retries` retries
+        attempts.set(0);
+        let result: io::Result<()> = retry_io(1, "read", || {
+            attempts.set(attempts.get() + 1);
+            async { Err(io::Error::from(io::ErrorKind::PermissionDenied)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 2);
+
+        // A missing file won't show up by waiting
+        attempts.set(0);
+        let result: io::Result<()> = retry_io(3, "read", || {
+            attempts.set(attempts.get() + 1);
+            async { Err(io::Error::from(io::ErrorKind::NotFound)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+
+        assert_eq!(delay(1), FIRST_DELAY);
+        assert_eq!(delay(3), FIRST_DELAY * 4);
+        assert_eq!(delay(50), MAX_DELAY);
+    }
+}